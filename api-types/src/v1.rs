@@ -10,5 +10,47 @@ pub mod token_info;
 pub use token_info::*;
 pub mod sleep_state;
 pub use sleep_state::*;
+pub mod event;
+pub use event::*;
+pub mod tag;
+pub use tag::*;
+pub mod sleep_check_in;
+pub use sleep_check_in::*;
+pub mod sleep_interruption;
+pub use sleep_interruption::*;
+pub mod sleep_batch;
+pub use sleep_batch::*;
+pub mod sleep_export;
+pub use sleep_export::*;
+pub mod sleep_feed;
+pub use sleep_feed::*;
+pub mod sleep_import;
+pub use sleep_import::*;
+pub mod sleep_revision;
+pub use sleep_revision::*;
+pub mod sleep_sync;
+pub use sleep_sync::*;
+pub mod sleep_stream;
+pub use sleep_stream::*;
+pub mod sleep_stats;
+pub use sleep_stats::*;
+pub mod sleep_analysis;
+pub use sleep_analysis::*;
+pub mod sleep_goal;
+pub use sleep_goal::*;
+pub mod sleep_share;
+pub use sleep_share::*;
+pub mod subject;
+pub use subject::*;
+pub mod share_link;
+pub use share_link::*;
+pub mod notification;
+pub use notification::*;
+pub mod webhook;
+pub use webhook::*;
+pub mod user_settings;
+pub use user_settings::*;
+pub mod data_export;
+pub use data_export::*;
 
 pub type DateTimeUtc = DateTime<Utc>;
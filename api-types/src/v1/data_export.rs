@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum DataExportStatus {
+    /// The archive is waiting to be built.
+    Pending,
+
+    /// The archive can be downloaded until it expires.
+    Ready,
+
+    /// The archive could not be built. Request a new export to try again.
+    Failed,
+
+    /// The archive was deleted. Request a new export to get the data again.
+    Expired,
+}
+
+/// An archive of everything stored about the user, as requested with `POST /v1/account/export`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataExport {
+    pub id: Snowflake,
+    pub status: DataExportStatus,
+    pub requested_at: DateTimeUtc,
+
+    /// When the archive was built, or building it failed.
+    pub completed_at: Option<DateTimeUtc>,
+
+    /// When the archive will be deleted, if it is ready.
+    pub expires_at: Option<DateTimeUtc>,
+
+    /// The size of the ZIP file, if it is ready.
+    pub size_bytes: Option<u64>,
+}
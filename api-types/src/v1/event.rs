@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+/// A user-defined kind of thing to record, like caffeine, exercise or medication.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EventType {
+    pub id: Snowflake,
+    pub name: String,
+    pub kind: EventKind,
+
+    /// The unit of the values of the events, like `mg` or `km`.
+    /// If this is empty, the events usually do not have values.
+    pub unit: Option<String>,
+}
+
+/// Request body for creating an event type.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewEventType {
+    pub name: String,
+    pub kind: EventKind,
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EventKind {
+    /// Events that happen at a single moment, like drinking a coffee.
+    Instant,
+
+    /// Events that last for some time, like exercise or screen time.
+    /// Like sleep states, they can be started now and ended later.
+    Interval,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Event {
+    pub id: Snowflake,
+    pub event_type_id: Snowflake,
+
+    /// When the event happened, or when it started for interval events.
+    pub start: DateTimeUtc,
+
+    /// When an interval event ended.
+    /// If this is empty for an interval event, then it is not over yet.
+    /// Instant events never have an end.
+    pub end: Option<DateTimeUtc>,
+
+    /// The amount, measured in the unit of the event type.
+    pub value: Option<f64>,
+
+    pub comment: Option<String>,
+}
+
+/// Request body for creating an event with arbitrary times.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewEvent {
+    pub event_type_id: Snowflake,
+    pub start: DateTimeUtc,
+    pub end: Option<DateTimeUtc>,
+    pub value: Option<f64>,
+    pub comment: Option<String>,
+}
+
+/// Request body for starting an event now.
+/// Both fields are optional, so the body may be empty.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct NewEventNow {
+    pub value: Option<f64>,
+    pub comment: Option<String>,
+}
+
+/// Query parameters for listing events.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EventListQuery {
+    /// Only include events of this type.
+    pub event_type_id: Option<Snowflake>,
+
+    /// Only include events that start at or after this moment.
+    pub from: Option<DateTimeUtc>,
+
+    /// Only include events that start before this moment.
+    pub to: Option<DateTimeUtc>,
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+/// How a user wants to receive their reminders.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Display, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ReminderChannel {
+    /// An email to the address of the account.
+    Email,
+
+    /// A JSON [`Notification`] POSTed to the user's webhook URL.
+    Webhook,
+
+    /// The notification inbox, which the client polls.
+    #[default]
+    Inbox,
+}
+
+/// Settings of the bedtime reminders.
+///
+/// Reminders are sent before the target bedtime of the goal that applies to each night,
+/// unless the user is already asleep.
+/// Any fields that are missing when sending the settings are reset to their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReminderSettings {
+    pub enabled: bool,
+
+    /// How long before the target bedtime to start winding down, in minutes.
+    pub minutes_before: u32,
+
+    pub channel: ReminderChannel,
+
+    /// Where to send reminders when the channel is `webhook`.
+    /// This must be an HTTPS URL of a public server.
+    pub webhook_url: Option<String>,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            minutes_before: 30,
+            channel: ReminderChannel::default(),
+            webhook_url: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NextReminder {
+    /// When the next reminder will be sent.
+    /// This is empty if reminders are disabled, or no goal with a target bedtime applies soon.
+    pub at: Option<DateTimeUtc>,
+
+    /// The target bedtime that the reminder is for.
+    pub bedtime: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NotificationKind {
+    BedtimeReminder,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Notification {
+    pub id: Snowflake,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub created_at: DateTimeUtc,
+
+    /// When the user marked the notification as read, if they did.
+    pub read_at: Option<DateTimeUtc>,
+}
+
+/// Query parameters for listing notifications.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NotificationListQuery {
+    /// Only include notifications that have not been read yet.
+    #[serde(default)]
+    pub unread_only: bool,
+}
@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::{DateTimeUtc, ExportedSleepState, SharedField, SleepStateSummary};
+
+/// Body of `POST /v1/sharing/links/new`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewShareLink {
+    /// The report covers the sleep states whose sleep date is on or after this date.
+    pub from_date: NaiveDate,
+
+    /// The report covers the sleep states whose sleep date is on or before this date.
+    pub to_date: NaiveDate,
+
+    /// These parts of the sleep states are left out of the report.
+    #[serde(default)]
+    pub hidden_fields: Vec<SharedField>,
+
+    /// When the link stops working. This must be in the future, and at most 90 days from now.
+    pub expires_at: DateTimeUtc,
+
+    /// The subject whose sleep states are reported. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+/// A public link to a report of the user's sleep states, which can be opened without logging in.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShareLink {
+    pub id: Snowflake,
+
+    /// The secret part of the link, as in `/v1/sleep/report/<token>`.
+    /// This is only returned when the link is created.
+    pub token: Option<String>,
+
+    pub subject_id: Snowflake,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub hidden_fields: Vec<SharedField>,
+    pub created_at: DateTimeUtc,
+    pub expires_at: DateTimeUtc,
+
+    /// How many times the report was opened.
+    pub access_count: u32,
+    pub last_accessed_at: Option<DateTimeUtc>,
+}
+
+/// The report that a public link leads to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepReport {
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub generated_at: DateTimeUtc,
+
+    /// When the link to the report stops working.
+    pub expires_at: DateTimeUtc,
+
+    /// Averages over the sleep states, computed without the hidden fields.
+    pub summary: SleepStateSummary,
+
+    /// The sleep states in chronological order with their interruptions, without the hidden fields.
+    pub sleep_states: Vec<ExportedSleepState>,
+}
@@ -0,0 +1,108 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::SleepKind;
+
+/// Query parameters for `GET /v1/sleep/analysis`.
+///
+/// Exactly one of `tag_id` and `event_type_id` must be given: that is the factor being analyzed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SleepAnalysisQuery {
+    /// First sleep date to include (in the user's local time).
+    pub from: NaiveDate,
+
+    /// Last sleep date to include (in the user's local time).
+    pub to: NaiveDate,
+
+    /// A sleep has the factor if this tag is attached to it.
+    pub tag_id: Option<Snowflake>,
+
+    /// A sleep has the factor if an event of this type happened in the window before it started.
+    pub event_type_id: Option<Snowflake>,
+
+    /// How many hours before the start of a sleep an event counts towards it.
+    /// Interval events count if any part of them is in the window.
+    #[serde(default = "default_window_hours")]
+    pub window_hours: u32,
+
+    /// Only include sleeps of this kind.
+    /// By default, all sleeps are included.
+    pub kind: Option<SleepKind>,
+
+    /// Analyze the sleeps of this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+fn default_window_hours() -> u32 {
+    6
+}
+
+/// A comparison of the sleeps with a factor against the sleeps without it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepAnalysis {
+    pub window_hours: u32,
+
+    /// The sleeps that have the factor.
+    pub with_factor: SleepAnalysisGroup,
+
+    /// The sleeps that do not have the factor.
+    pub without_factor: SleepAnalysisGroup,
+
+    /// Net sleep duration in seconds.
+    pub duration: Option<SleepAnalysisEffect>,
+
+    /// The quality from the check-in, from 1 to 5.
+    /// Only sleeps with a quality in their check-in are counted.
+    pub quality: Option<SleepAnalysisEffect>,
+
+    /// Bedtime in seconds, measured from the circular mean bedtime of all the sleeps,
+    /// so that a positive difference means going to bed later.
+    pub bedtime: Option<SleepAnalysisEffect>,
+}
+
+/// Distributions of the sleep outcomes of one group of sleeps.
+///
+/// Averages and deviations are empty if there is not enough data to compute them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepAnalysisGroup {
+    pub sleep_count: u32,
+
+    pub average_duration_seconds: Option<f64>,
+    pub duration_std_dev_seconds: Option<f64>,
+
+    /// How many of the sleeps have a quality in their check-in.
+    pub quality_count: u32,
+    pub average_quality: Option<f64>,
+    pub quality_std_dev: Option<f64>,
+
+    /// Circular mean of the start times in the user's local time.
+    pub average_bedtime: Option<NaiveTime>,
+    /// Circular standard deviation of the start times.
+    pub bedtime_std_dev_seconds: Option<f64>,
+}
+
+/// How much an outcome differs between the sleeps with and without the factor.
+///
+/// This is only computed if both groups have at least two samples.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepAnalysisEffect {
+    /// The number of samples with the factor.
+    pub with_count: u32,
+
+    /// The number of samples without the factor.
+    pub without_count: u32,
+
+    /// The mean with the factor minus the mean without it.
+    pub difference: f64,
+
+    /// The 95% confidence interval of the difference, using Welch's t-test.
+    pub confidence_interval_low: f64,
+    pub confidence_interval_high: f64,
+
+    /// Hedges' g: the difference in units of the pooled standard deviation,
+    /// corrected for small samples.
+    /// This is empty if neither group varies at all.
+    pub effect_size: Option<f64>,
+}
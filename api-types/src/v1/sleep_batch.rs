@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::SleepState;
+
+/// Request body for `POST /v1/sleep/batch`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SleepBatch {
+    #[serde(default)]
+    pub mode: SleepBatchMode,
+
+    /// The operations to apply, in order.
+    pub operations: Vec<SleepBatchOperation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepBatchMode {
+    /// If any operation fails, none of them are applied, and the operations after it are not tried.
+    #[default]
+    AllOrNothing,
+
+    /// Every operation is tried, and the ones that do not fail are applied.
+    BestEffort,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "operation")]
+pub enum SleepBatchOperation {
+    /// Create a sleep state.
+    /// Its ID is chosen by the server, so the one in `sleep_state` is ignored.
+    Create { sleep_state: SleepState },
+
+    /// Change a sleep state, like `PUT /v1/sleep/<id>`.
+    Update { sleep_state: SleepState },
+
+    /// Move a sleep state to the trash, like `DELETE /v1/sleep/<id>`.
+    Delete { id: Snowflake },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepBatchResult {
+    /// Whether the operations that succeeded were applied.
+    /// This is false if an operation failed in all-or-nothing mode.
+    pub committed: bool,
+
+    /// The outcomes of the operations, in the same order as the operations.
+    pub results: Vec<SleepBatchOperationResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "status")]
+pub enum SleepBatchOperationResult {
+    /// The operation succeeded on the sleep state with this ID.
+    Ok { id: Snowflake },
+
+    /// The operation failed, with the status code that the single-item endpoint would have returned.
+    Failed { code: u16, error: String },
+
+    /// The operation was not tried, because an earlier one failed in all-or-nothing mode.
+    Skipped,
+}
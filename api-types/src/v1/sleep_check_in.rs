@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+/// How the user felt about a sleep, usually submitted in the morning when ending it.
+///
+/// Every field is optional, so that the user can skip any question.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SleepCheckIn {
+    /// How well the user slept, from 1 (terrible) to 5 (excellent).
+    pub quality: Option<u8>,
+
+    /// How long it took to fall asleep, in minutes.
+    pub sleep_latency_minutes: Option<u32>,
+
+    /// How many times the user remembers waking up.
+    pub awakenings: Option<u32>,
+
+    /// How rested the user feels, from 1 (exhausted) to 5 (fully rested).
+    pub restedness: Option<u8>,
+
+    pub dream_recall: Option<DreamRecall>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DreamRecall {
+    /// The user does not remember dreaming.
+    None,
+    /// The user remembers dreaming, but not what about.
+    Vague,
+    /// The user remembers the dream clearly.
+    Vivid,
+}
+
+/// Aggregates over the sleep states matching a list query.
+///
+/// Averages only take into account the sleep states where that field was filled in,
+/// and are empty if there are none.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepStateSummary {
+    pub count: u32,
+    pub average_net_sleep_seconds: Option<f64>,
+    pub average_quality: Option<f64>,
+    pub average_sleep_latency_minutes: Option<f64>,
+    pub average_awakenings: Option<f64>,
+    pub average_restedness: Option<f64>,
+    pub dream_recall_counts: DreamRecallCounts,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DreamRecallCounts {
+    pub none: u32,
+    pub vague: u32,
+    pub vivid: u32,
+}
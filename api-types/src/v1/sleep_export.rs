@@ -0,0 +1,118 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::{SleepInterruption, SleepState};
+
+/// A column of the CSV export of sleep states.
+/// The header of the column is its name in `snake_case`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SleepExportColumn {
+    Id,
+    /// The local night that the sleep is attributed to.
+    SleepDate,
+    /// In RFC 3339 format, in the timezone of the export.
+    Start,
+    /// In RFC 3339 format, in the timezone of the export. Empty if the sleep is not over yet.
+    End,
+    Kind,
+    TimeInBedSeconds,
+    NetSleepSeconds,
+    Quality,
+    SleepLatencyMinutes,
+    Awakenings,
+    Restedness,
+    DreamRecall,
+    /// The number of times the user woke up during the sleep.
+    Interruptions,
+    /// The names of the tags, separated by semicolons.
+    Tags,
+    Comment,
+    AutoClosed,
+}
+
+impl SleepExportColumn {
+    /// All the columns, in the order they are exported by default.
+    pub const ALL: &'static [SleepExportColumn] = &[
+        Self::Id,
+        Self::SleepDate,
+        Self::Start,
+        Self::End,
+        Self::Kind,
+        Self::TimeInBedSeconds,
+        Self::NetSleepSeconds,
+        Self::Quality,
+        Self::SleepLatencyMinutes,
+        Self::Awakenings,
+        Self::Restedness,
+        Self::DreamRecall,
+        Self::Interruptions,
+        Self::Tags,
+        Self::Comment,
+        Self::AutoClosed,
+    ];
+}
+
+/// A list of columns, written as a comma-separated string in query parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SleepExportColumnList(pub Vec<SleepExportColumn>);
+
+impl Display for SleepExportColumnList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let columns: Vec<String> = self.0.iter().map(SleepExportColumn::to_string).collect();
+        write!(f, "{}", columns.join(","))
+    }
+}
+
+impl Serialize for SleepExportColumnList {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'a> Deserialize<'a> for SleepExportColumnList {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'a>,
+    {
+        let string = String::deserialize(deserializer)?;
+        let columns = string
+            .split(',')
+            .filter(|column| !column.is_empty())
+            .map(|column| column.trim().parse().map_err(serde::de::Error::custom))
+            .collect::<Result<_, _>>()?;
+        Ok(Self(columns))
+    }
+}
+
+/// Query parameters for the exports of `GET /v1/sleep/export/...`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SleepExportQuery {
+    /// The timezone to write the times of the CSV export in, like `Europe/Berlin`.
+    /// By default, this is the timezone in the user's settings.
+    pub timezone: Option<String>,
+
+    /// The columns of the CSV export, in order.
+    /// By default, all of [`SleepExportColumn::ALL`] are exported.
+    pub columns: Option<SleepExportColumnList>,
+
+    /// Export the sleep states of this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+/// A line of the newline-delimited JSON export.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportedSleepState {
+    #[serde(flatten)]
+    pub sleep_state: SleepState,
+
+    pub interruptions: Vec<SleepInterruption>,
+}
@@ -0,0 +1,35 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+/// Query parameters for `GET /v1/sleep/feed/<token>`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SleepFeedQuery {
+    /// Only include sleep states whose sleep date is on or after this date.
+    /// By default, the feed starts 90 days ago.
+    pub from_date: Option<NaiveDate>,
+
+    /// Only include sleep states whose sleep date is on or before this date.
+    pub to_date: Option<NaiveDate>,
+
+    /// Show the sleep states of this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+/// A secret token that gives access to the user's calendar feed, and nothing else.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FeedToken {
+    pub id: Snowflake,
+
+    /// The secret part of the feed URL.
+    /// This is only returned when the token is created.
+    pub token: Option<String>,
+
+    pub created: DateTimeUtc,
+
+    /// When a calendar app last fetched the feed with this token.
+    pub last_used: Option<DateTimeUtc>,
+}
@@ -0,0 +1,91 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+/// A target sleep schedule, which applies from its effective date until the next goal takes effect.
+///
+/// Goals are never changed in place, so that past nights keep being evaluated against
+/// the goal that applied to them: to change the goal, add a new one.
+///
+/// At least one of the targets is set.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepGoal {
+    pub id: Snowflake,
+
+    /// The first sleep date (in the user's local time) that this goal applies to.
+    pub effective_from: NaiveDate,
+
+    /// The local time to go to bed at, with a precision of minutes.
+    pub target_bedtime: Option<NaiveTime>,
+
+    /// The local time to wake up at, with a precision of minutes.
+    pub target_wake_time: Option<NaiveTime>,
+
+    /// The least net sleep per night, in minutes.
+    pub min_duration_minutes: Option<u32>,
+
+    /// How far from the target times the actual times may be, in minutes.
+    pub tolerance_minutes: u32,
+}
+
+/// Request body for adding a goal.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewSleepGoal {
+    pub effective_from: NaiveDate,
+    pub target_bedtime: Option<NaiveTime>,
+    pub target_wake_time: Option<NaiveTime>,
+    pub min_duration_minutes: Option<u32>,
+    #[serde(default = "default_tolerance_minutes")]
+    pub tolerance_minutes: u32,
+}
+
+fn default_tolerance_minutes() -> u32 {
+    30
+}
+
+/// Query parameters for `GET /v1/goals/adherence`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SleepGoalAdherenceQuery {
+    /// First sleep date to include.
+    /// By default, this is the date from which the earliest goal applies.
+    pub from: Option<NaiveDate>,
+
+    /// Last sleep date to include.
+    /// By default, this is the current sleep date.
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepGoalAdherence {
+    /// Every night in the requested range that a goal applies to, in chronological order.
+    pub nights: Vec<SleepGoalNight>,
+
+    /// How many nights in a row the goal has been met, up to the end of the range.
+    ///
+    /// If the last night of the range has not been slept yet, it does not break the streak.
+    pub current_streak: u32,
+
+    /// The most nights in a row the goal has been met in the range.
+    pub longest_streak: u32,
+}
+
+/// How a night went compared to the goal that applied to it.
+///
+/// The individual targets are empty if the goal does not set them.
+/// If no main sleep was recorded for the night, all of its set targets count as missed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepGoalNight {
+    pub sleep_date: NaiveDate,
+    pub goal_id: Snowflake,
+
+    /// The main sleep of the night that was evaluated: the longest one, if there are several.
+    pub sleep_state_id: Option<Snowflake>,
+
+    pub bedtime_met: Option<bool>,
+    pub wake_time_met: Option<bool>,
+    pub duration_met: Option<bool>,
+
+    /// Whether all the targets that the goal sets were met.
+    pub met: bool,
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::{DateTimeUtc, SleepKind};
+
+/// The kinds of files that sleep states can be imported from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepImportFormat {
+    /// A CSV file with a header row, whose columns are given in the query.
+    GenericCsv,
+
+    /// The `sleep-export.csv` backup of Sleep as Android.
+    SleepAsAndroid,
+
+    /// The `sleep-<date>.json` files of a Fitbit data export.
+    Fitbit,
+
+    /// The `export.xml` file of an Apple Health export.
+    /// Only the sleep analysis records are read.
+    AppleHealth,
+}
+
+/// Query parameters for `POST /v1/sleep/import`. The body is the file to import.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SleepImportQuery {
+    pub format: SleepImportFormat,
+
+    /// Only report what would be imported, without importing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// The timezone of times in the file that do not say which timezone they are in.
+    /// By default, this is the timezone in the user's settings.
+    pub timezone: Option<String>,
+
+    /// For generic CSV: the header of the column with the start times.
+    pub start_column: Option<String>,
+
+    /// For generic CSV: the header of the column with the end times.
+    pub end_column: Option<String>,
+
+    /// For generic CSV: the header of the column with comments, if any.
+    pub comment_column: Option<String>,
+
+    /// For generic CSV: the header of the column with quality ratings from 1 to 5, if any.
+    pub quality_column: Option<String>,
+
+    /// Import the sleep states for this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+/// What an import did, or would do in a dry run.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepImportReport {
+    pub dry_run: bool,
+
+    /// How many sleeps were found in the file.
+    pub found_count: u32,
+
+    /// How many sleeps were imported, or would be in a dry run.
+    pub imported_count: u32,
+
+    /// How many sleeps were skipped because they overlap one that already exists or comes earlier in the file.
+    pub duplicate_count: u32,
+
+    /// The sleeps found in the file, in chronological order.
+    pub sleeps: Vec<ImportedSleep>,
+
+    /// The records of the file that could not be read.
+    pub errors: Vec<SleepImportError>,
+}
+
+/// A sleep found in an imported file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImportedSleep {
+    /// The number of the record in the file that the sleep comes from, starting at 1:
+    /// the line for CSV files, the position in the list for Fitbit,
+    /// and the position among the sleep analysis records for Apple Health.
+    pub record: u32,
+
+    pub start: DateTimeUtc,
+    pub end: DateTimeUtc,
+    pub explicit_kind: Option<SleepKind>,
+    pub comment: Option<String>,
+    pub quality: Option<u8>,
+
+    pub status: ImportedSleepStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "status")]
+pub enum ImportedSleepStatus {
+    /// The sleep was imported as a new sleep state with this ID.
+    /// In a dry run, there is no ID.
+    Imported { id: Option<Snowflake> },
+
+    /// The sleep overlaps this sleep state, which already exists, so it was skipped.
+    DuplicateOfExisting { id: Snowflake },
+
+    /// The sleep overlaps the one from this record earlier in the file, so it was skipped.
+    DuplicateInFile { record: u32 },
+}
+
+/// A record of an imported file that could not be read.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepImportError {
+    /// The number of the record in the file, starting at 1, counted like [`ImportedSleep::record`].
+    pub record: u32,
+
+    pub reason: String,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+/// A period of being awake in the middle of a sleep.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepInterruption {
+    pub id: Snowflake,
+
+    /// The sleep state that this interruption is a part of.
+    pub sleep_state_id: Snowflake,
+
+    pub start: DateTimeUtc,
+
+    /// If this is empty, then the user is still awake.
+    pub end: Option<DateTimeUtc>,
+
+    pub reason: Option<String>,
+}
+
+/// Request body for creating an interruption with arbitrary times.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewSleepInterruption {
+    pub start: DateTimeUtc,
+    pub end: Option<DateTimeUtc>,
+    pub reason: Option<String>,
+}
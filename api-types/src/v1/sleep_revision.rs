@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::{DateTimeUtc, SleepCheckIn, SleepKind};
+
+/// The values that a sleep state had before it was changed.
+///
+/// Revisions are never changed or deleted, except together with their sleep state when it is purged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepStateRevision {
+    pub id: Snowflake,
+    pub sleep_state_id: Snowflake,
+
+    /// When the change happened.
+    pub changed_at: DateTimeUtc,
+
+    /// The ID of the authentication token that made the change.
+    /// This is empty if the server made the change on its own,
+    /// like when ending a forgotten sleep automatically.
+    pub changed_by_token_id: Option<Snowflake>,
+
+    pub start: DateTimeUtc,
+    pub end: Option<DateTimeUtc>,
+    pub comment: Option<String>,
+    pub check_in: SleepCheckIn,
+    pub explicit_kind: Option<SleepKind>,
+    pub auto_closed: bool,
+}
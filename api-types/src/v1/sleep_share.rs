@@ -0,0 +1,93 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ShareAccess {
+    /// List sleep states, summaries and statistics.
+    Read,
+
+    /// Also change existing sleep states with `PUT /v1/sleep/<id>?user_id=<owner id>`.
+    ReadWrite,
+}
+
+/// Parts of a sleep state that can be kept from the user it is shared with.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SharedField {
+    /// The comment, and in reports also the reasons of the interruptions.
+    Comment,
+    CheckIn,
+    Tags,
+}
+
+/// What a sharing grant allows, as sent by the owner.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepShareSettings {
+    pub access: ShareAccess,
+
+    /// Only sleep states whose sleep date is on or after this date can be seen.
+    pub from_date: Option<NaiveDate>,
+
+    /// Only sleep states whose sleep date is on or before this date can be seen.
+    pub to_date: Option<NaiveDate>,
+
+    /// These parts of the sleep states are left out, and cannot be filtered by.
+    /// Only read grants can hide fields.
+    #[serde(default)]
+    pub hidden_fields: Vec<SharedField>,
+}
+
+/// Body of `POST /v1/sharing/granted/new`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepShareInvitation {
+    /// Where the invitation is sent. The user with this email address can accept it,
+    /// including one who registers later.
+    pub email: lettre::Address,
+    #[serde(flatten)]
+    pub settings: SleepShareSettings,
+}
+
+/// A grant of access to the user's sleep states, as seen by the user who gave it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepShare {
+    pub id: Snowflake,
+    pub email: String,
+
+    /// The user who accepted the invitation, or nothing while it is pending.
+    pub grantee_id: Option<Snowflake>,
+    #[serde(flatten)]
+    pub settings: SleepShareSettings,
+    pub created_at: DateTimeUtc,
+    pub accepted_at: Option<DateTimeUtc>,
+}
+
+/// A grant of access to another user's sleep states, as seen by the user who received it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReceivedSleepShare {
+    pub id: Snowflake,
+
+    /// Pass this as `user_id` to the sleep endpoints to see the owner's sleep states.
+    pub owner_id: Snowflake,
+    pub owner_username: String,
+    #[serde(flatten)]
+    pub settings: SleepShareSettings,
+    pub created_at: DateTimeUtc,
+
+    /// When the invitation was accepted, or nothing if it still has to be.
+    pub accepted_at: Option<DateTimeUtc>,
+}
+
+/// Query parameters of the sleep endpoints that can act on sleep states shared by another user.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SharedUserQuery {
+    /// The user whose sleep states to use. By default, your own.
+    pub user_id: Option<Snowflake>,
+}
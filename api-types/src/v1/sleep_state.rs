@@ -1,8 +1,10 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
 
 use crate::Snowflake;
 
-use super::DateTimeUtc;
+use super::{DateTimeUtc, DreamRecall, SleepCheckIn, Tag, TagFilter, TagIdList};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SleepState {
@@ -13,4 +15,157 @@ pub struct SleepState {
     pub end: Option<DateTimeUtc>,
 
     pub comment: Option<String>,
+
+    /// The user's own assessment of the sleep.
+    #[serde(default)]
+    pub check_in: SleepCheckIn,
+
+    /// The tags attached to this sleep state.
+    ///
+    /// This is ignored when sent by the client: use the `/<id>/tags` endpoints to change it.
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+
+    /// The local night that this sleep is attributed to,
+    /// according to the user's timezone and day boundary hour.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub sleep_date: Option<NaiveDate>,
+
+    /// The kind of this sleep: either the one in `explicit_kind`,
+    /// or the one inferred from its duration and time of day.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub kind: SleepKind,
+
+    /// The kind of this sleep as chosen by the client.
+    /// If this is empty, the server infers the kind automatically.
+    #[serde(default)]
+    pub explicit_kind: Option<SleepKind>,
+
+    /// How long the sleep lasted from start to end, in seconds.
+    /// Empty if the sleep is not over yet.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub time_in_bed_seconds: Option<i64>,
+
+    /// How much of the time in bed was actually spent asleep, that is,
+    /// the time in bed minus all interruptions, in seconds.
+    /// Empty if the sleep is not over yet.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub net_sleep_seconds: Option<i64>,
+
+    /// Whether the sleep was still going on after the user's maximum sleep length,
+    /// so the server ended it at a guessed time.
+    /// Changing the sleep with `PUT /<id>` confirms the times, and clears this.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub auto_closed: bool,
+
+    /// Whose sleep this is. Sleep states are created for the subject chosen with `?subject_id=<id>`,
+    /// and cannot be moved to another subject.
+    ///
+    /// This is computed by the server, and is ignored when sent by the client.
+    #[serde(default)]
+    pub subject_id: Option<Snowflake>,
+}
+
+/// A deleted sleep state, which can still be restored.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashedSleepState {
+    #[serde(flatten)]
+    pub sleep_state: SleepState,
+
+    pub deleted_at: DateTimeUtc,
+
+    /// When the sleep state will be deleted for good.
+    pub purge_at: DateTimeUtc,
+}
+
+/// Query parameters for `POST /v1/sleep/new`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NewSleepStateQuery {
+    /// If the current sleep is longer than the user's maximum sleep length,
+    /// end it automatically and start the new one, instead of returning 409.
+    #[serde(default)]
+    pub replace_stale: bool,
+
+    /// The subject who is going to sleep. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Display, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum SleepKind {
+    /// The main sleep of a night.
+    Main,
+
+    /// A short sleep during the day.
+    Nap,
+
+    /// The kind could not be determined yet (for example, because the sleep is not over).
+    #[default]
+    Unknown,
+}
+
+/// Query parameters for listing sleep states.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SleepStateListQuery {
+    /// Only include sleep states whose sleep date is on or after this date.
+    pub from_date: Option<NaiveDate>,
+
+    /// Only include sleep states whose sleep date is on or before this date.
+    pub to_date: Option<NaiveDate>,
+
+    /// Only include sleep states of this kind.
+    pub kind: Option<SleepKind>,
+
+    /// Only include sleep states rated at least this quality.
+    pub min_quality: Option<u8>,
+
+    /// Only include sleep states rated at most this quality.
+    pub max_quality: Option<u8>,
+
+    /// Only include sleep states after which the user felt at least this rested.
+    pub min_restedness: Option<u8>,
+
+    /// Only include sleep states after which the user felt at most this rested.
+    pub max_restedness: Option<u8>,
+
+    /// Only include sleep states with this dream recall.
+    pub dream_recall: Option<DreamRecall>,
+
+    /// Only include sleep states that have at least one of these tags.
+    pub tags_any: Option<TagIdList>,
+
+    /// Only include sleep states that have all of these tags.
+    pub tags_all: Option<TagIdList>,
+
+    /// Only include sleep states that have none of these tags.
+    pub tags_none: Option<TagIdList>,
+
+    /// List the sleep states of this user, who shared them with you. By default, your own.
+    pub user_id: Option<Snowflake>,
+
+    /// List the sleep states of this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+impl SleepStateListQuery {
+    pub fn tag_filter(&self) -> TagFilter<'_> {
+        TagFilter {
+            any: self.tags_any.as_ref(),
+            all: self.tags_all.as_ref(),
+            none: self.tags_none.as_ref(),
+        }
+    }
 }
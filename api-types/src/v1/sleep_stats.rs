@@ -0,0 +1,125 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::{SleepKind, TagFilter, TagIdList};
+
+/// How to split the requested date range into periods.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGranularity {
+    #[default]
+    Day,
+    /// ISO weeks, starting on Monday.
+    Week,
+    /// Calendar months.
+    Month,
+}
+
+/// Query parameters for `GET /v1/sleep/stats`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SleepStatsQuery {
+    /// First sleep date to include (in the user's local time).
+    pub from: NaiveDate,
+
+    /// Last sleep date to include (in the user's local time).
+    pub to: NaiveDate,
+
+    #[serde(default)]
+    pub granularity: StatsGranularity,
+
+    /// Only include sleeps of this kind.
+    /// By default, all sleeps are included.
+    pub kind: Option<SleepKind>,
+
+    /// Only include sleeps that have at least one of these tags.
+    pub tags_any: Option<TagIdList>,
+
+    /// Only include sleeps that have all of these tags.
+    pub tags_all: Option<TagIdList>,
+
+    /// Only include sleeps that have none of these tags.
+    pub tags_none: Option<TagIdList>,
+
+    /// Compute the statistics of this user, who shared their sleep states with you. By default, your own.
+    pub user_id: Option<Snowflake>,
+
+    /// Compute the statistics of this subject of the user. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
+
+impl SleepStatsQuery {
+    pub fn tag_filter(&self) -> TagFilter<'_> {
+        TagFilter {
+            any: self.tags_any.as_ref(),
+            all: self.tags_all.as_ref(),
+            none: self.tags_none.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepStats {
+    pub granularity: StatsGranularity,
+
+    /// The sleep goal that the sleep debt was computed against on the last night of the range.
+    ///
+    /// Each night, this is the minimum duration of the goal that applies to it,
+    /// or the `sleep_goal_minutes` setting if that goal doesn't set one.
+    pub goal_seconds: i64,
+
+    /// Statistics over the whole requested range.
+    pub overall: SleepStatsPeriod,
+
+    /// Statistics for each period in the requested range, in chronological order.
+    /// The first and last periods are cut to fit the range.
+    pub periods: Vec<SleepStatsPeriod>,
+}
+
+/// Statistics of the completed sleeps whose sleep dates fall in a period.
+///
+/// All times of day are in the user's local time.
+/// Averages and deviations are empty if there is not enough data to compute them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepStatsPeriod {
+    /// First sleep date of the period.
+    pub from: NaiveDate,
+
+    /// Last sleep date of the period.
+    pub to: NaiveDate,
+
+    pub sleep_count: u32,
+
+    /// Total time in bed, including interruptions.
+    pub total_time_in_bed_seconds: i64,
+    pub average_time_in_bed_seconds: Option<f64>,
+
+    /// Durations are of net sleep: the time in bed minus any interruptions.
+    pub total_duration_seconds: i64,
+    pub average_duration_seconds: Option<f64>,
+    pub duration_std_dev_seconds: Option<f64>,
+
+    /// Circular mean of the start times, so that 23:00 and 01:00 average to midnight.
+    pub average_bedtime: Option<NaiveTime>,
+    /// Circular standard deviation of the start times.
+    pub bedtime_std_dev_seconds: Option<f64>,
+
+    /// Circular mean of the end times.
+    pub average_wake_time: Option<NaiveTime>,
+    /// Circular standard deviation of the end times.
+    pub wake_time_std_dev_seconds: Option<f64>,
+
+    /// The Sleep Regularity Index: how likely the user is to be in the same state (asleep or awake)
+    /// at two moments 24 hours apart, scaled from -100 to 100.
+    /// 100 means a perfectly regular schedule.
+    ///
+    /// This needs at least two days of data.
+    pub sleep_regularity_index: Option<f64>,
+
+    /// Accumulated sleep debt at the end of the period, counted from the start of the requested range.
+    ///
+    /// Every night adds the difference between the goal and the actual net sleep.
+    /// Sleeping more than the goal pays the debt back, but the debt never goes below zero.
+    pub sleep_debt_seconds: i64,
+}
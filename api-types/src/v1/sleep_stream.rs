@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::SleepState;
+
+/// A change to one of the user's sleep states, as pushed by `GET /v1/events/stream`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "event")]
+pub enum SleepStateEvent {
+    /// A sleep state was created, imported, or restored from the trash.
+    Created { sleep_state: SleepState },
+
+    /// A sleep state, or one of its interruptions or tags, was changed.
+    Updated { sleep_state: SleepState },
+
+    /// A sleep state was moved to the trash.
+    Deleted { id: Snowflake },
+
+    /// The connection fell behind and some changes were not sent,
+    /// so the client should fetch its sleep states again.
+    Lagged { missed: u64 },
+}
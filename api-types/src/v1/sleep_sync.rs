@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::{DateTimeUtc, SleepState};
+
+/// Query parameters for `GET /v1/sleep/changes`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SleepChangesQuery {
+    /// The cursor returned by the previous sync.
+    /// Without it, every sleep state is returned, including the ones in the trash.
+    #[serde(default)]
+    pub since: u64,
+
+    /// The maximum number of changes to return.
+    pub limit: Option<u32>,
+
+    /// Sync the sleep states of this subject of the user. By default, the user themselves.
+    /// Keep a cursor for every subject that is synced.
+    pub subject_id: Option<Snowflake>,
+}
+
+/// The sleep states that changed since a cursor, in the order they changed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepChanges {
+    pub changes: Vec<SleepStateChange>,
+
+    /// Pass this as `since` to get the changes after these ones.
+    pub cursor: u64,
+
+    /// Whether there are more changes after the cursor already.
+    pub has_more: bool,
+}
+
+/// The latest version of a sleep state, or its tombstone if it was deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepStateChange {
+    pub id: Snowflake,
+
+    /// The position of this change in the user's sequence of changes.
+    /// Send it back as `base_sequence` when uploading a change to this sleep state.
+    pub sequence: u64,
+
+    /// The sleep state as it is now, or empty if it was deleted.
+    pub sleep_state: Option<SleepState>,
+
+    /// When the sleep state was deleted, if it was.
+    pub deleted_at: Option<DateTimeUtc>,
+}
+
+/// Request body for `POST /v1/sleep/changes`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SleepChangeBatch {
+    /// The changes to apply, in order.
+    pub changes: Vec<ClientSleepChange>,
+}
+
+/// A change that a client made while it was offline.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "operation")]
+pub enum ClientSleepChange {
+    /// Create a new sleep state.
+    /// Its ID is chosen by the server, so the one in `sleep_state` is ignored.
+    Create { sleep_state: SleepState },
+
+    /// Replace the values of a sleep state,
+    /// unless it has changed on the server since the client last saw it at `base_sequence`.
+    Update {
+        sleep_state: SleepState,
+        base_sequence: u64,
+    },
+
+    /// Move a sleep state to the trash,
+    /// unless it has changed on the server since the client last saw it at `base_sequence`.
+    Delete { id: Snowflake, base_sequence: u64 },
+}
+
+/// The outcomes of a batch of changes, in the same order as the changes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SleepChangeBatchResult {
+    pub results: Vec<ClientSleepChangeResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "status")]
+pub enum ClientSleepChangeResult {
+    /// The change was applied, and this is the sleep state as it is now.
+    Applied { change: SleepStateChange },
+
+    /// The sleep state was changed on the server after `base_sequence`, so nothing was applied.
+    /// This is the sleep state as it is now, for the client to resolve the conflict.
+    Conflict { current: SleepStateChange },
+
+    /// The change is not valid, so nothing was applied.
+    Rejected { reason: String },
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+use super::DateTimeUtc;
+
+/// Someone whose sleep is tracked: the user themselves, or someone in their care, like a baby or a pet.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Subject {
+    pub id: Snowflake,
+    pub name: String,
+
+    /// Whether this subject stands for the user themselves.
+    /// Every user has exactly one, with the same ID as the user, and it cannot be deleted.
+    pub is_self: bool,
+    pub created_at: DateTimeUtc,
+}
+
+/// Request body for creating or renaming a subject.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewSubject {
+    pub name: String,
+}
+
+/// Query parameters of the sleep endpoints that have no other parameters, to choose whose sleep states to use.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SubjectQuery {
+    /// The subject whose sleep states to use. By default, the user themselves.
+    pub subject_id: Option<Snowflake>,
+}
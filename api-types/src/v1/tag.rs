@@ -0,0 +1,80 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Snowflake;
+
+/// A user-defined label for sleep states, like "caffeine" or "travel".
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tag {
+    pub id: Snowflake,
+    pub name: String,
+
+    /// The color to show the tag with, as `#RRGGBB`.
+    pub color: String,
+}
+
+/// Request body for creating a tag.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NewTag {
+    pub name: String,
+    pub color: String,
+}
+
+/// A list of tag IDs, written in query strings as comma-separated values, like `?tags_any=1,2,3`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TagIdList(pub Vec<Snowflake>);
+
+impl Display for TagIdList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<String> = self.0.iter().map(Snowflake::to_string).collect();
+        write!(f, "{}", ids.join(","))
+    }
+}
+
+impl Serialize for TagIdList {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'a> Deserialize<'a> for TagIdList {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'a>,
+    {
+        let string = String::deserialize(deserializer)?;
+        let ids = string
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| id.trim().parse().map_err(serde::de::Error::custom))
+            .collect::<Result<_, _>>()?;
+        Ok(Self(ids))
+    }
+}
+
+/// Filters on the tags of a sleep state, as used in list and statistics queries.
+/// Empty lists do not filter anything.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TagFilter<'a> {
+    /// The sleep state must have at least one of these tags.
+    pub any: Option<&'a TagIdList>,
+    /// The sleep state must have all of these tags.
+    pub all: Option<&'a TagIdList>,
+    /// The sleep state must have none of these tags.
+    pub none: Option<&'a TagIdList>,
+}
+
+impl TagFilter<'_> {
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        let has = |id: &Snowflake| tags.iter().any(|tag| tag.id == *id);
+        self.any
+            .filter(|any| !any.0.is_empty())
+            .is_none_or(|any| any.0.iter().any(has))
+            && self.all.is_none_or(|all| all.0.iter().all(has))
+            && self.none.is_none_or(|none| !none.0.iter().any(has))
+    }
+}
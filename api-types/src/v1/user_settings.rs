@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings of a user.
+///
+/// Any fields that are missing when sending the settings are reset to their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct UserSettings {
+    /// The IANA name of the user's timezone, like `Europe/Moscow`.
+    pub timezone: String,
+
+    /// The local hour (0-23) at which a new day starts for the purpose of sleep dates.
+    ///
+    /// A sleep that starts before this hour is attributed to the previous night.
+    /// For example, with the default of 12, a sleep starting at 02:00 on the 2nd
+    /// belongs to the night of the 1st, but a nap starting at 14:00 on the 2nd belongs to the 2nd.
+    pub day_boundary_hour: u8,
+
+    /// How many minutes of sleep per night the user aims for.
+    /// Sleep debt is computed against this on nights without a goal that sets a minimum duration.
+    pub sleep_goal_minutes: u32,
+
+    /// Sleeps that start outside of the night and are at most this long are considered naps.
+    pub nap_max_minutes: u32,
+
+    /// The local hour (0-23) at which the night starts.
+    /// Sleeps that start during the night are always considered main sleeps.
+    pub night_start_hour: u8,
+
+    /// The local hour (0-23) at which the night ends.
+    pub night_end_hour: u8,
+
+    /// Sleeps that are still going on after this many minutes are considered forgotten,
+    /// and are ended automatically.
+    pub max_sleep_minutes: u32,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            timezone: "UTC".to_string(),
+            day_boundary_hour: 12,
+            sleep_goal_minutes: 8 * 60,
+            nap_max_minutes: 150,
+            night_start_hour: 20,
+            night_end_hour: 5,
+            max_sleep_minutes: 16 * 60,
+        }
+    }
+}
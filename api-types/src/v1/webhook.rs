@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::Snowflake;
+
+use super::{DateTimeUtc, SleepState};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum WebhookEventType {
+    /// A sleep state that is going on was created, like with `POST /v1/sleep/new`.
+    SleepStarted,
+
+    /// The sleep that was going on was ended, by the user with `POST /v1/sleep/@current`,
+    /// or automatically because it was forgotten.
+    SleepEnded,
+
+    /// Any other change to a sleep state, its interruptions or its tags,
+    /// including completed sleep states that were created or imported.
+    SleepUpdated,
+
+    /// A sleep state was moved to the trash.
+    SleepDeleted,
+}
+
+/// The settings of a webhook subscription, as sent by the client.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookSubscriptionSettings {
+    /// The URL that payloads are POSTed to. This must be an HTTPS URL of a public server.
+    pub url: String,
+
+    /// The events to send. At least one is needed.
+    pub event_types: Vec<WebhookEventType>,
+
+    /// Whether payloads are sent at all. Deliveries that are due while this is off wait for it to be on again.
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookSubscription {
+    pub id: Snowflake,
+    #[serde(flatten)]
+    pub settings: WebhookSubscriptionSettings,
+    pub created_at: DateTimeUtc,
+
+    /// The key that payloads are signed with.
+    /// This is only returned when the subscription is created, so it cannot be read back later.
+    ///
+    /// Each request has an `X-Oyasumi-Signature` header like `t=<unix time>,v1=<signature>`,
+    /// where the signature is the hexadecimal HMAC-SHA256 of `<unix time>.<body>` with this key.
+    pub secret: Option<String>,
+}
+
+/// The body POSTed to a webhook URL.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookPayload {
+    /// The ID of the delivery, which stays the same when it is retried or redelivered.
+    pub id: Snowflake,
+    pub event_type: WebhookEventType,
+    pub occurred_at: DateTimeUtc,
+    pub sleep_state_id: Snowflake,
+
+    /// The sleep state after the change, or nothing if it was deleted.
+    pub sleep_state: Option<SleepState>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    /// The payload is waiting to be sent, or to be retried.
+    Pending,
+
+    /// The URL answered with a 2xx status.
+    Delivered,
+
+    /// Every attempt failed. It can still be redelivered by hand.
+    Failed,
+}
+
+/// An entry of the delivery log of a webhook subscription.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub id: Snowflake,
+    pub subscription_id: Snowflake,
+    pub event_type: WebhookEventType,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub created_at: DateTimeUtc,
+
+    /// When the payload will be sent next, if it is pending.
+    pub next_attempt_at: Option<DateTimeUtc>,
+    pub last_attempt_at: Option<DateTimeUtc>,
+
+    /// The HTTP status of the last response, if there was one.
+    pub last_status_code: Option<u16>,
+
+    /// Why the last attempt failed, if it did.
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTimeUtc>,
+    pub payload: WebhookPayload,
+}
@@ -0,0 +1,20 @@
+/// Functions for fingerprinting data
+use orion::hash::digest;
+
+/// Make a fingerprint of some data, as a hexadecimal string.
+///
+/// The parts are length-prefixed before hashing,
+/// so that moving bytes from one part to the next changes the fingerprint.
+pub fn fingerprint(parts: &[&[u8]]) -> String {
+    let mut data = vec![];
+    for part in parts {
+        data.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        data.extend_from_slice(part);
+    }
+    let digest = digest(&data).unwrap();
+    digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -1,2 +1,4 @@
+pub mod digest;
 pub mod password;
+pub mod signature;
 pub mod token;
@@ -0,0 +1,17 @@
+/// Functions for signing messages with a shared secret
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Sign a message with HMAC-SHA256, as a hexadecimal string.
+///
+/// The receiver can check that the message comes from someone who knows the secret,
+/// by signing it again with their copy of the secret and comparing.
+pub fn sign(secret: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -1 +1,12 @@
+pub mod data_export;
+pub mod notification;
 pub mod registration;
+pub mod sharing;
+
+/// Make text safe to put inside HTML elements and attribute values.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,23 @@
+use crate::delivery::get_noreply_sender;
+use lettre::{
+    message::{Mailbox, Message, MultiPart},
+    Address,
+};
+
+/// Tell the user that their data export can be downloaded, until `expires` (already formatted for reading).
+pub fn make_data_export_ready_email(
+    where_to: Address,
+    download_path: &str,
+    expires: &str,
+) -> Message {
+    let where_to = Mailbox::new(None, where_to);
+    Message::builder()
+        .from(get_noreply_sender())
+        .to(where_to)
+        .subject("Your Oyasumi.app data export is ready")
+        .multipart(MultiPart::alternative_plain_html(
+            format!("The archive of your data is ready. While logged in, download it from {download_path} before {expires}, when it will be deleted."),
+            format!("<h1>Your data export is ready</h1><p>While logged in, download it from <code>{download_path}</code> before {expires}, when it will be deleted.</p>"),
+        ))
+        .unwrap()
+}
@@ -0,0 +1,24 @@
+use crate::delivery::get_noreply_sender;
+use lettre::{
+    message::{Mailbox, Message, MultiPart},
+    Address,
+};
+
+use super::escape_html;
+
+pub fn make_notification_email(where_to: Address, title: &str, body: &str) -> Message {
+    let where_to = Mailbox::new(None, where_to);
+    Message::builder()
+        .from(get_noreply_sender())
+        .to(where_to)
+        .subject(title)
+        .multipart(MultiPart::alternative_plain_html(
+            body.to_string(),
+            format!(
+                "<h1>{}</h1><p>{}</p>",
+                escape_html(title),
+                escape_html(body)
+            ),
+        ))
+        .unwrap()
+}
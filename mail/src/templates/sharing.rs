@@ -0,0 +1,29 @@
+use crate::delivery::get_noreply_sender;
+use lettre::{
+    message::{Mailbox, Message, MultiPart},
+    Address,
+};
+
+use super::escape_html;
+
+/// Tell someone that `owner_username` invited them to see their sleep states,
+/// which they can accept at `accept_path` once they are logged in.
+pub fn make_sleep_share_invitation_email(
+    where_to: Address,
+    owner_username: &str,
+    accept_path: &str,
+) -> Message {
+    let where_to = Mailbox::new(None, where_to);
+    Message::builder()
+        .from(get_noreply_sender())
+        .to(where_to)
+        .subject(format!("{owner_username} wants to share their sleep data with you on Oyasumi.app"))
+        .multipart(MultiPart::alternative_plain_html(
+            format!("{owner_username} invited you to see their sleep data. Log in with this email address (or register with it), then accept the invitation with POST {accept_path}. If you do not know them, you can ignore this email."),
+            format!(
+                "<h1>An invitation to see sleep data</h1><p>{} invited you to see their sleep data.</p><p>Log in with this email address (or register with it), then accept the invitation with <code>POST {accept_path}</code>.</p><p>If you do not know them, you can ignore this email.</p>",
+                escape_html(owner_username)
+            ),
+        ))
+        .unwrap()
+}
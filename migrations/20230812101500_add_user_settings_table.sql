@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id INTEGER NOT NULL PRIMARY KEY REFERENCES user(id),
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    day_boundary_hour INTEGER NOT NULL DEFAULT 12
+);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE user_settings ADD COLUMN sleep_goal_minutes INTEGER NOT NULL DEFAULT 480;
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+-- The kind that the client has explicitly chosen for a sleep.
+-- Existing rows have no explicit kind, so their kind is inferred from the user's settings.
+ALTER TABLE sleep_state ADD COLUMN kind TEXT CHECK (kind IN ('main', 'nap', 'unknown'));
+
+ALTER TABLE user_settings ADD COLUMN nap_max_minutes INTEGER NOT NULL DEFAULT 150;
+ALTER TABLE user_settings ADD COLUMN night_start_hour INTEGER NOT NULL DEFAULT 20;
+ALTER TABLE user_settings ADD COLUMN night_end_hour INTEGER NOT NULL DEFAULT 5;
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS sleep_interruption (
+    id INTEGER NOT NULL PRIMARY KEY,
+    sleep_state_id INTEGER NOT NULL REFERENCES sleep_state(id) ON DELETE CASCADE,
+    started_at_unix_time INTEGER NOT NULL,
+    ended_at_unix_time INTEGER,
+    reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS sleep_interruption_by_sleep_state ON sleep_interruption(sleep_state_id);
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE sleep_state ADD COLUMN quality INTEGER CHECK (quality BETWEEN 1 AND 5);
+ALTER TABLE sleep_state ADD COLUMN sleep_latency_minutes INTEGER CHECK (sleep_latency_minutes >= 0);
+ALTER TABLE sleep_state ADD COLUMN awakenings INTEGER CHECK (awakenings >= 0);
+ALTER TABLE sleep_state ADD COLUMN restedness INTEGER CHECK (restedness BETWEEN 1 AND 5);
+ALTER TABLE sleep_state ADD COLUMN dream_recall TEXT CHECK (dream_recall IN ('none', 'vague', 'vivid'));
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS tag (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    name TEXT NOT NULL,
+    color TEXT NOT NULL,
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS sleep_state_tag (
+    sleep_state_id INTEGER NOT NULL REFERENCES sleep_state(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+    PRIMARY KEY (sleep_state_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS sleep_state_tag_by_tag ON sleep_state_tag(tag_id);
@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS event_type (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('instant', 'interval')),
+    unit TEXT,
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS event (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    event_type_id INTEGER NOT NULL REFERENCES event_type(id) ON DELETE CASCADE,
+    started_at_unix_time INTEGER NOT NULL,
+    ended_at_unix_time INTEGER,
+    value REAL,
+    comment TEXT
+);
+
+CREATE INDEX IF NOT EXISTS event_by_user_and_time ON event(user_id, started_at_unix_time);
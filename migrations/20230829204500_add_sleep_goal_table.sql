@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS sleep_goal (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    -- The first sleep date (YYYY-MM-DD in the user's local time) that this goal applies to.
+    -- A goal applies until the next goal of the user takes effect.
+    effective_from TEXT NOT NULL,
+    -- Target times of day, in minutes after local midnight
+    target_bedtime_minute INTEGER CHECK (target_bedtime_minute BETWEEN 0 AND 1439),
+    target_wake_time_minute INTEGER CHECK (target_wake_time_minute BETWEEN 0 AND 1439),
+    min_duration_minutes INTEGER CHECK (min_duration_minutes > 0),
+    tolerance_minutes INTEGER NOT NULL CHECK (tolerance_minutes BETWEEN 0 AND 720),
+    UNIQUE (user_id, effective_from)
+);
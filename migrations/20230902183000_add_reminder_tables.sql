@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS reminder_settings (
+    user_id INTEGER NOT NULL PRIMARY KEY REFERENCES user(id),
+    enabled INTEGER NOT NULL DEFAULT 0,
+    minutes_before INTEGER NOT NULL DEFAULT 30 CHECK (minutes_before BETWEEN 0 AND 720),
+    channel TEXT NOT NULL DEFAULT 'inbox' CHECK (channel IN ('email', 'webhook', 'inbox')),
+    webhook_url TEXT,
+    -- The scheduled time of the last reminder that was handled, whether it was sent or skipped
+    last_reminder_at_unix_time INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS notification (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    kind TEXT NOT NULL CHECK (kind IN ('bedtime_reminder')),
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at_unix_time INTEGER NOT NULL,
+    read_at_unix_time INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS notification_by_user ON notification(user_id, created_at_unix_time);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE user_settings ADD COLUMN max_sleep_minutes INTEGER NOT NULL DEFAULT 960;
+ALTER TABLE sleep_state ADD COLUMN auto_closed INTEGER NOT NULL DEFAULT 0;
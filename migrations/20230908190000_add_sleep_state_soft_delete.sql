@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Deleted sleep states are kept in the trash until they are purged
+ALTER TABLE sleep_state ADD COLUMN deleted_at_unix_time INTEGER;
+
+CREATE INDEX IF NOT EXISTS sleep_state_by_deleted_at ON sleep_state(deleted_at_unix_time)
+    WHERE deleted_at_unix_time IS NOT NULL;
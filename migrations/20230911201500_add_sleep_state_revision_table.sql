@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Append-only history of the values that sleep states had before each change
+CREATE TABLE IF NOT EXISTS sleep_state_revision (
+    id INTEGER NOT NULL PRIMARY KEY,
+    sleep_state_id INTEGER NOT NULL REFERENCES sleep_state(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    -- The token that made the change, or NULL if the server made it on its own.
+    -- This is not a foreign key, so that the history survives logging out.
+    changed_by_token_id INTEGER,
+    changed_at_unix_time INTEGER NOT NULL,
+    started_at_unix_time INTEGER NOT NULL,
+    ended_at_unix_time INTEGER,
+    comment TEXT,
+    kind TEXT,
+    quality INTEGER,
+    sleep_latency_minutes INTEGER,
+    awakenings INTEGER,
+    restedness INTEGER,
+    dream_recall TEXT,
+    auto_closed INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sleep_state_revision_by_sleep_state ON sleep_state_revision(sleep_state_id);
@@ -0,0 +1,110 @@
+-- Add migration script here
+-- Every change to a sleep state gets the next number in a sequence per user,
+-- so that clients can download only what changed since they last synced.
+-- The sequence is maintained by triggers, so that no way of changing a sleep state can forget it.
+ALTER TABLE sleep_state ADD COLUMN change_sequence INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS sleep_state_sync (
+    user_id INTEGER NOT NULL PRIMARY KEY REFERENCES user(id) ON DELETE CASCADE,
+    last_sequence INTEGER NOT NULL,
+    -- The highest sequence of a sleep state that was purged from the trash.
+    -- Its tombstone is gone, so clients that synced before it must start over.
+    purged_sequence INTEGER NOT NULL DEFAULT 0
+);
+
+-- Number the existing sleep states in the order they were created
+UPDATE sleep_state SET change_sequence = (
+    SELECT COUNT(*) FROM sleep_state AS earlier
+    WHERE earlier.user_id = sleep_state.user_id AND earlier.id <= sleep_state.id
+);
+
+INSERT INTO sleep_state_sync (user_id, last_sequence)
+    SELECT user_id, MAX(change_sequence) FROM sleep_state GROUP BY user_id;
+
+CREATE INDEX IF NOT EXISTS sleep_state_by_change_sequence ON sleep_state(user_id, change_sequence);
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_inserted AFTER INSERT ON sleep_state
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence) VALUES (NEW.user_id, 1)
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = NEW.user_id)
+        WHERE id = NEW.id;
+END;
+
+-- Setting only `change_sequence` does not fire this again.
+-- Columns added to `sleep_state` later must be added to this list.
+CREATE TRIGGER IF NOT EXISTS sleep_state_updated AFTER UPDATE OF
+    started_at_unix_time, ended_at_unix_time, comment, kind, quality, sleep_latency_minutes,
+    awakenings, restedness, dream_recall, auto_closed, deleted_at_unix_time
+    ON sleep_state
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence) VALUES (NEW.user_id, 1)
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = NEW.user_id)
+        WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_purged AFTER DELETE ON sleep_state
+BEGIN
+    UPDATE sleep_state_sync SET purged_sequence = MAX(purged_sequence, OLD.change_sequence)
+        WHERE user_id = OLD.user_id;
+END;
+
+-- Interruptions and tags are part of a sleep state as clients see it.
+-- Sleep states in the trash are skipped, so that purging them does not touch them on the way out.
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_inserted AFTER INSERT ON sleep_interruption
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence)
+        SELECT user_id, 1 FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = sleep_state.user_id)
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_updated AFTER UPDATE ON sleep_interruption
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence)
+        SELECT user_id, 1 FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = sleep_state.user_id)
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_deleted AFTER DELETE ON sleep_interruption
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence)
+        SELECT user_id, 1 FROM sleep_state
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = sleep_state.user_id)
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_tag_inserted AFTER INSERT ON sleep_state_tag
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence)
+        SELECT user_id, 1 FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = sleep_state.user_id)
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_tag_deleted AFTER DELETE ON sleep_state_tag
+BEGIN
+    INSERT INTO sleep_state_sync (user_id, last_sequence)
+        SELECT user_id, 1 FROM sleep_state
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL
+        ON CONFLICT (user_id) DO UPDATE SET last_sequence = last_sequence + 1;
+    UPDATE sleep_state
+        SET change_sequence = (SELECT last_sequence FROM sleep_state_sync WHERE user_id = sleep_state.user_id)
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL;
+END;
@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Responses to requests with an `Idempotency-Key` header, so that retries can be answered the same way
+CREATE TABLE IF NOT EXISTS idempotency_key (
+    -- The user who made the request, or 0 for requests without a user, like registrations.
+    -- This is not a foreign key, because of the 0.
+    user_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    -- A hash of the method, URI and body, to catch a key that is reused for another request
+    fingerprint TEXT NOT NULL,
+    -- NULL while the first request with this key is still being handled
+    response_status INTEGER,
+    response_content_type TEXT,
+    response_body BLOB,
+    created_at_unix_time INTEGER NOT NULL,
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_key_by_created_at ON idempotency_key(created_at_unix_time);
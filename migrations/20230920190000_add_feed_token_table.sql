@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Secret tokens for the calendar feed, which are kept apart from login tokens:
+-- a feed URL is pasted into calendar apps, so it must not give access to anything else.
+CREATE TABLE IF NOT EXISTS feed_token (
+    id INTEGER NOT NULL PRIMARY KEY,
+    token TEXT NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    created_at_unix_time INTEGER NOT NULL,
+    -- NULL until a calendar app fetches the feed for the first time
+    last_used_at_unix_time INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS feed_token_by_user ON feed_token(user_id);
@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Archives of everything stored about a user, built in the background when the user asks for one
+CREATE TABLE IF NOT EXISTS data_export (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    status TEXT NOT NULL CHECK (status IN ('pending', 'ready', 'failed', 'expired')),
+    requested_at_unix_time INTEGER NOT NULL,
+    -- When the archive was built, or building it failed
+    completed_at_unix_time INTEGER,
+    -- After this, the archive is deleted and cannot be downloaded anymore
+    expires_at_unix_time INTEGER,
+    -- The ZIP file, while it can be downloaded
+    archive BLOB,
+    size_bytes INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS data_export_by_user ON data_export(user_id);
+CREATE INDEX IF NOT EXISTS data_export_by_status ON data_export(status);
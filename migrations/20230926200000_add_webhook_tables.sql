@@ -0,0 +1,37 @@
+-- Add migration script here
+-- URLs that users registered to be told about changes to their sleep states
+CREATE TABLE IF NOT EXISTS webhook_subscription (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    url TEXT NOT NULL,
+    -- The key that payloads are signed with, which the receiver uses to check them
+    secret TEXT NOT NULL,
+    -- Comma-separated, like `sleep_started,sleep_ended`
+    event_types TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at_unix_time INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_subscription_by_user ON webhook_subscription(user_id);
+
+-- The queue of payloads to send, which is also the log of what was sent
+CREATE TABLE IF NOT EXISTS webhook_delivery (
+    id INTEGER NOT NULL PRIMARY KEY,
+    subscription_id INTEGER NOT NULL REFERENCES webhook_subscription(id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL CHECK (event_type IN ('sleep_started', 'sleep_ended', 'sleep_updated', 'sleep_deleted')),
+    -- The JSON body, which stays the same across retries so that the receiver can deduplicate them
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at_unix_time INTEGER NOT NULL,
+    -- NULL once it is not pending anymore
+    next_attempt_at_unix_time INTEGER,
+    last_attempt_at_unix_time INTEGER,
+    -- The HTTP status of the last response, or NULL if there was none
+    last_status_code INTEGER,
+    last_error TEXT,
+    delivered_at_unix_time INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS webhook_delivery_by_subscription ON webhook_delivery(subscription_id, created_at_unix_time);
+CREATE INDEX IF NOT EXISTS webhook_delivery_by_next_attempt ON webhook_delivery(status, next_attempt_at_unix_time);
@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Grants that let another user see (or change) a user's sleep states.
+-- A grant starts as an invitation to an email address, and is accepted by the user who has that address.
+CREATE TABLE IF NOT EXISTS sleep_share (
+    id INTEGER NOT NULL PRIMARY KEY,
+    -- The user whose sleep states are shared
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    invited_email TEXT NOT NULL COLLATE NOCASE,
+    -- NULL until the invitation is accepted
+    grantee_user_id INTEGER REFERENCES user(id),
+    access TEXT NOT NULL CHECK (access IN ('read', 'read_write')),
+    -- The range of sleep dates (YYYY-MM-DD in the owner's local time) that can be seen, if limited
+    from_date TEXT,
+    to_date TEXT,
+    -- Comma-separated, like `comment,check_in`
+    hidden_fields TEXT NOT NULL DEFAULT '',
+    created_at_unix_time INTEGER NOT NULL,
+    accepted_at_unix_time INTEGER,
+    UNIQUE (user_id, invited_email)
+);
+
+CREATE INDEX IF NOT EXISTS sleep_share_by_grantee ON sleep_share(grantee_user_id);
+CREATE INDEX IF NOT EXISTS sleep_share_by_email ON sleep_share(invited_email);
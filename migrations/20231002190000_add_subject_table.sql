@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Whose sleep is tracked: the user themselves, or someone in their care, like a baby or a pet
+CREATE TABLE IF NOT EXISTS subject (
+    id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    name TEXT NOT NULL,
+    -- 1 for the subject that stands for the user themselves, which has the same ID as the user
+    is_self INTEGER NOT NULL DEFAULT 0,
+    created_at_unix_time INTEGER NOT NULL,
+    UNIQUE (user_id, name)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS subject_self_by_user ON subject(user_id) WHERE is_self;
+
+-- Every user has their own subject, which all their sleep states so far belong to
+INSERT INTO subject (id, user_id, name, is_self, created_at_unix_time)
+    SELECT id, id, 'Me', 1, CAST(strftime('%s', 'now') AS INTEGER) FROM user;
+
+-- SQLite cannot add a NOT NULL column with a foreign key, but every sleep state is given a subject from now on
+ALTER TABLE sleep_state ADD COLUMN subject_id INTEGER REFERENCES subject(id);
+UPDATE sleep_state SET subject_id=user_id;
+
+CREATE INDEX IF NOT EXISTS sleep_state_by_subject ON sleep_state(subject_id, started_at_unix_time);
@@ -0,0 +1,21 @@
+-- Add migration script here
+-- Public links to a fixed report of sleep states, for people without an account, like a doctor.
+-- Like feed tokens, the token in the URL is the only credential.
+CREATE TABLE IF NOT EXISTS share_link (
+    id INTEGER NOT NULL PRIMARY KEY,
+    token TEXT NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES user(id),
+    subject_id INTEGER NOT NULL REFERENCES subject(id) ON DELETE CASCADE,
+    -- The report always covers the sleep dates from_date to to_date, as YYYY-MM-DD
+    from_date TEXT NOT NULL,
+    to_date TEXT NOT NULL,
+    -- Comma-separated names of the fields of the sleep states that are left out, like in sleep_share
+    hidden_fields TEXT NOT NULL DEFAULT '',
+    created_at_unix_time INTEGER NOT NULL,
+    expires_at_unix_time INTEGER NOT NULL,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    -- NULL until the report is opened for the first time
+    last_accessed_at_unix_time INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS share_link_by_user ON share_link(user_id);
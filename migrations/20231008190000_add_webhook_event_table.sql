@@ -0,0 +1,86 @@
+-- Add migration script here
+-- Changes to sleep states that webhook deliveries still have to be queued for.
+-- Rows are written by triggers in the same transaction as the change, so that none are lost
+-- if the server stops before the webhook worker gets to them; the worker deletes them once queued.
+CREATE TABLE IF NOT EXISTS webhook_event (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+    sleep_state_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL CHECK (event_type IN ('sleep_started', 'sleep_ended', 'sleep_updated', 'sleep_deleted'))
+);
+
+-- Only users with an active subscription get events, so that the table stays empty for everybody else.
+-- Sleep states that are created while going on have started, and the others were logged afterwards.
+CREATE TRIGGER IF NOT EXISTS sleep_state_inserted_webhook_event AFTER INSERT ON sleep_state
+    WHEN EXISTS (SELECT 1 FROM webhook_subscription WHERE user_id = NEW.user_id AND active)
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type) VALUES (
+        NEW.user_id,
+        NEW.id,
+        CASE WHEN NEW.ended_at_unix_time IS NULL THEN 'sleep_started' ELSE 'sleep_updated' END
+    );
+END;
+
+-- Sleep states that are restored from the trash are treated like new ones,
+-- and changes to sleep states that stay in the trash are not sent at all.
+-- Columns added to `sleep_state` later must be added to this list, like for `sleep_state_updated`.
+CREATE TRIGGER IF NOT EXISTS sleep_state_updated_webhook_event AFTER UPDATE OF
+    started_at_unix_time, ended_at_unix_time, comment, kind, quality, sleep_latency_minutes,
+    awakenings, restedness, dream_recall, auto_closed, deleted_at_unix_time
+    ON sleep_state
+    WHEN EXISTS (SELECT 1 FROM webhook_subscription WHERE user_id = NEW.user_id AND active)
+        AND (OLD.deleted_at_unix_time IS NULL OR NEW.deleted_at_unix_time IS NULL)
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type) VALUES (
+        NEW.user_id,
+        NEW.id,
+        CASE
+            WHEN NEW.deleted_at_unix_time IS NOT NULL THEN 'sleep_deleted'
+            WHEN OLD.deleted_at_unix_time IS NOT NULL AND NEW.ended_at_unix_time IS NULL THEN 'sleep_started'
+            WHEN OLD.deleted_at_unix_time IS NOT NULL THEN 'sleep_updated'
+            WHEN OLD.ended_at_unix_time IS NULL AND NEW.ended_at_unix_time IS NOT NULL THEN 'sleep_ended'
+            ELSE 'sleep_updated'
+        END
+    );
+END;
+
+-- Interruptions and tags are part of a sleep state as receivers see it
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_inserted_webhook_event AFTER INSERT ON sleep_interruption
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type)
+        SELECT user_id, id, 'sleep_updated' FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+            AND EXISTS (SELECT 1 FROM webhook_subscription WHERE webhook_subscription.user_id = sleep_state.user_id AND active);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_updated_webhook_event AFTER UPDATE ON sleep_interruption
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type)
+        SELECT user_id, id, 'sleep_updated' FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+            AND EXISTS (SELECT 1 FROM webhook_subscription WHERE webhook_subscription.user_id = sleep_state.user_id AND active);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_interruption_deleted_webhook_event AFTER DELETE ON sleep_interruption
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type)
+        SELECT user_id, id, 'sleep_updated' FROM sleep_state
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL
+            AND EXISTS (SELECT 1 FROM webhook_subscription WHERE webhook_subscription.user_id = sleep_state.user_id AND active);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_tag_inserted_webhook_event AFTER INSERT ON sleep_state_tag
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type)
+        SELECT user_id, id, 'sleep_updated' FROM sleep_state
+        WHERE id = NEW.sleep_state_id AND deleted_at_unix_time IS NULL
+            AND EXISTS (SELECT 1 FROM webhook_subscription WHERE webhook_subscription.user_id = sleep_state.user_id AND active);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sleep_state_tag_deleted_webhook_event AFTER DELETE ON sleep_state_tag
+BEGIN
+    INSERT INTO webhook_event (user_id, sleep_state_id, event_type)
+        SELECT user_id, id, 'sleep_updated' FROM sleep_state
+        WHERE id = OLD.sleep_state_id AND deleted_at_unix_time IS NULL
+            AND EXISTS (SELECT 1 FROM webhook_subscription WHERE webhook_subscription.user_id = sleep_state.user_id AND active);
+END;
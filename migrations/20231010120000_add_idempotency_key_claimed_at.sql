@@ -0,0 +1,6 @@
+-- Add migration script here
+
+-- When the request that is being handled with a key started, so that a key whose request never got an answer,
+-- like when the server stopped while handling it, can be claimed again by a retry.
+-- Keys claimed before this column existed count as claimed long ago.
+ALTER TABLE idempotency_key ADD COLUMN claimed_at_unix_time INTEGER NOT NULL DEFAULT 0;
@@ -2,11 +2,25 @@ use axum::middleware::from_fn_with_state;
 use axum::{routing::get, Router};
 use sqlx::SqlitePool;
 
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::{
+    clock::{Clock, SimulatedClock, SystemClock},
+    outbound::OutboundPolicy,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
+
+    /// The time used by background jobs, and by the endpoints that report on them.
+    pub clock: Arc<dyn Clock>,
+
+    /// The changes to sleep states, for the clients that listen to them.
+    pub sleep_events: crate::v1::SleepEventBus,
+
+    /// Where requests to URLs chosen by users, like webhooks, may be sent.
+    pub outbound: OutboundPolicy,
 }
 
 #[tokio::main]
@@ -22,13 +36,54 @@ pub async fn main() {
         .await
         .expect("Failed to connect to database");
 
-    let app_state = AppState { db: conn };
+    // For trying out reminders and other time-dependent features,
+    // the clock can be started at any moment, like `2023-09-01T21:55:00Z`
+    let clock: Arc<dyn Clock> = match std::env::var("SIMULATED_CLOCK_START") {
+        Ok(start) => Arc::new(SimulatedClock::starting_at(
+            start
+                .parse()
+                .expect("SIMULATED_CLOCK_START is not a valid RFC 3339 time"),
+        )),
+        Err(_) => Arc::new(SystemClock),
+    };
+
+    let app_state = AppState {
+        db: conn,
+        clock,
+        sleep_events: crate::v1::SleepEventBus::new(),
+        // Webhooks can only be sent to local servers, like a stand-in for trying them out,
+        // if `ALLOW_LOCAL_WEBHOOKS` is `true`
+        outbound: OutboundPolicy::from_env(),
+    };
+
+    crate::v1::ReminderScheduler::new(
+        app_state.db.clone(),
+        app_state.clock.clone(),
+        app_state.outbound,
+    )
+    .spawn();
+    crate::v1::StaleSleepSweeper::new(
+        app_state.db.clone(),
+        app_state.clock.clone(),
+        app_state.sleep_events.clone(),
+    )
+    .spawn();
+    crate::v1::TrashPurger::new(app_state.db.clone(), app_state.clock.clone()).spawn();
+    crate::v1::IdempotencyKeyPurger::new(app_state.db.clone(), app_state.clock.clone()).spawn();
+    crate::v1::DataExportWorker::new(app_state.db.clone(), app_state.clock.clone()).spawn();
+    crate::v1::WebhookWorker::new(
+        app_state.db.clone(),
+        app_state.clock.clone(),
+        app_state.sleep_events.clone(),
+        app_state.outbound,
+    )
+    .spawn();
 
     // build our application with a route
     let app = Router::new()
         // `GET /` goes to `root`
         .route("/", get(root))
-        .nest("/v1", crate::v1::get_router())
+        .nest("/v1", crate::v1::get_router(&app_state))
         .with_state(app_state.clone())
         .route_layer(from_fn_with_state(
             app_state,
@@ -0,0 +1,79 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+
+use chrono::Duration;
+
+use crate::DateTimeUtc;
+
+/// Source of the current time for background jobs, so that they can be run against a simulated clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTimeUtc;
+}
+
+/// The real time of the system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTimeUtc {
+        DateTimeUtc::from(SystemTime::now())
+    }
+}
+
+/// A clock that is set to a chosen moment, and can be moved forward by hand.
+///
+/// This is useful for trying out time-dependent features, like reminders,
+/// without waiting for the right time of day.
+/// Clones share the same time, so a test can keep one to advance the clock that a job uses.
+#[derive(Debug, Clone)]
+pub struct SimulatedClock {
+    time: Arc<Mutex<SimulatedTime>>,
+}
+
+#[derive(Debug)]
+struct SimulatedTime {
+    at: DateTimeUtc,
+    /// Since when the clock has been running at the normal speed, or nothing if it stands still.
+    running_since: Option<Instant>,
+}
+
+impl SimulatedClock {
+    /// A clock that starts at a chosen moment, and then runs at the normal speed.
+    pub fn starting_at(start: DateTimeUtc) -> Self {
+        Self::new(start, Some(Instant::now()))
+    }
+
+    /// A clock that stands still at a chosen moment, until it is advanced.
+    #[allow(dead_code)]
+    // only stopped in tests, so that they do not depend on how fast they run
+    pub fn stopped_at(at: DateTimeUtc) -> Self {
+        Self::new(at, None)
+    }
+
+    fn new(at: DateTimeUtc, running_since: Option<Instant>) -> Self {
+        Self {
+            time: Arc::new(Mutex::new(SimulatedTime { at, running_since })),
+        }
+    }
+
+    /// Move the clock forward.
+    #[allow(dead_code)]
+    // only advanced by tests
+    pub fn advance(&self, by: Duration) {
+        let mut time = self.time.lock().expect("the clock is never poisoned");
+        time.at += by;
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTimeUtc {
+        let time = self.time.lock().expect("the clock is never poisoned");
+        let elapsed = time
+            .running_since
+            .and_then(|since| Duration::from_std(since.elapsed()).ok())
+            .unwrap_or_else(Duration::zero);
+        time.at + elapsed
+    }
+}
@@ -1,5 +1,9 @@
 mod api;
+mod clock;
+mod outbound;
 mod security;
+#[cfg(test)]
+mod testing;
 
 mod v1;
 
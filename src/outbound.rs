@@ -0,0 +1,196 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Resolve, Resolving},
+    redirect, Url,
+};
+
+/// Where the requests that the server sends to URLs chosen by users, like webhooks, may go.
+///
+/// Without care, a user could point a webhook at the server itself or at the network it runs in,
+/// and read the answers back from the delivery log.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutboundPolicy {
+    /// Also allow plain HTTP, and addresses on this machine or the local network.
+    /// This is only meant for trying things out against a stand-in server.
+    pub allow_local: bool,
+}
+
+impl OutboundPolicy {
+    /// The policy set by the `ALLOW_LOCAL_WEBHOOKS` environment variable, which is strict unless it is `true`.
+    pub fn from_env() -> Self {
+        Self {
+            allow_local: std::env::var("ALLOW_LOCAL_WEBHOOKS").is_ok_and(|value| value == "true"),
+        }
+    }
+
+    /// Check that requests may be sent to the URL, as far as can be told without resolving its host name.
+    ///
+    /// This is done both when the URL is stored and right before sending to it.
+    /// The addresses of host names are checked by the client from [`Self::client`] when it connects,
+    /// since they can change at any time.
+    pub fn check_url(&self, url: &str) -> Result<Url, String> {
+        let parsed = Url::parse(url).map_err(|_| format!("{url:?} is not a valid URL"))?;
+        match parsed.scheme() {
+            "https" => {}
+            "http" if self.allow_local => {}
+            _ => return Err(format!("the URL must be an HTTPS URL, not {url:?}")),
+        }
+        if self.allow_local {
+            return Ok(parsed);
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        // IPv6 addresses are written in brackets in URLs
+        let is_allowed = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(ip) => is_public(ip),
+            Err(_) => {
+                let domain = host.trim_end_matches('.').to_ascii_lowercase();
+                !domain.is_empty() && domain != "localhost" && !domain.ends_with(".localhost")
+            }
+        };
+        if !is_allowed {
+            return Err(format!(
+                "the URL must not point at a local or private address, like {url:?} does"
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// A client for sending requests to URLs chosen by users.
+    ///
+    /// It refuses to connect to host names that resolve to addresses that are not allowed,
+    /// and it does not follow redirects, since those could lead anywhere.
+    pub fn client(&self, timeout: Duration) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(redirect::Policy::none());
+        if !self.allow_local {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        builder.build().expect("TLS backend is compiled in")
+    }
+}
+
+/// Resolves host names with the system resolver, leaving out the addresses that are not public.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!(
+                    "{} has no public address, and local or private addresses are not allowed",
+                    name.as_str()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Box<dyn Iterator<Item = SocketAddr> + Send>)
+        })
+    }
+}
+
+/// Whether requests may go to the address: not to this machine, the local network,
+/// or link-local addresses like those of cloud metadata services.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    // 0.0.0.0/8 means "this network", and 100.64.0.0/10 is shared by carrier-grade NATs
+    let is_this_network = first == 0;
+    let is_shared = first == 100 && (64..128).contains(&second);
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || is_this_network
+        || is_shared)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+    // fc00::/7 are unique local addresses, and fe80::/10 are link-local
+    let is_unique_local = first_segment & 0xfe00 == 0xfc00;
+    let is_link_local = first_segment & 0xffc0 == 0xfe80;
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || is_unique_local
+        || is_link_local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRICT: OutboundPolicy = OutboundPolicy { allow_local: false };
+    const LOCAL: OutboundPolicy = OutboundPolicy { allow_local: true };
+
+    #[test]
+    fn allows_public_https_urls() {
+        assert!(STRICT.check_url("https://example.org/hook").is_ok());
+        assert!(STRICT.check_url("https://93.184.216.34/hook").is_ok());
+        assert!(STRICT.check_url("https://[2606:2800:220:1::]/hook").is_ok());
+    }
+
+    #[test]
+    fn rejects_local_and_private_addresses() {
+        for url in [
+            "http://example.org/hook",
+            "https://localhost/hook",
+            "https://api.localhost./hook",
+            "https://127.0.0.1/hook",
+            // The same address, written as a single number
+            "https://2130706433/hook",
+            "https://10.0.0.1/hook",
+            "https://192.168.1.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.64.0.1/hook",
+            "https://0.0.0.0/hook",
+            "https://[::1]/hook",
+            "https://[::ffff:127.0.0.1]/hook",
+            "https://[fd00::1]/hook",
+            "https://[fe80::1]/hook",
+            "ftp://example.org/hook",
+            "not a URL",
+        ] {
+            assert!(STRICT.check_url(url).is_err(), "{url} is allowed");
+        }
+    }
+
+    #[test]
+    fn local_servers_can_be_allowed() {
+        assert!(LOCAL.check_url("http://localhost:8080/hook").is_ok());
+        assert!(LOCAL.check_url("http://127.0.0.1:8080/hook").is_ok());
+        assert!(LOCAL.check_url("ftp://127.0.0.1/hook").is_err());
+    }
+
+    #[tokio::test]
+    async fn client_does_not_connect_to_local_host_names() {
+        let err = STRICT
+            .client(Duration::from_secs(1))
+            .get("https://localhost/")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_connect(), "{err}");
+    }
+}
@@ -0,0 +1,45 @@
+//! Helpers for tests that run against a database.
+
+use sqlx::{query, sqlite::SqlitePoolOptions, SqlitePool};
+
+/// A new in-memory database with all the migrations applied.
+pub async fn test_db() -> SqlitePool {
+    // Every connection to `sqlite::memory:` opens a database of its own,
+    // so the pool must keep a single connection open for as long as the test runs
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("in-memory database can be opened");
+    sqlx::migrate!()
+        .run(&db)
+        .await
+        .expect("migrations apply to an empty database");
+    db
+}
+
+/// Add a user, along with the subject that stands for themselves, which has the same ID.
+pub async fn insert_user(db: &SqlitePool, id: i64) {
+    let username = format!("user{id}");
+    let email = format!("user{id}@example.org");
+    query!(
+        "INSERT INTO user (id, username, email, password_hash) VALUES (?,?,?,'')",
+        id,
+        username,
+        email,
+    )
+    .execute(db)
+    .await
+    .expect("user can be inserted");
+    query!(
+        r#"INSERT INTO subject (id, user_id, name, is_self, created_at_unix_time)
+            VALUES (?,?,'Me',1,0)"#,
+        id,
+        id,
+    )
+    .execute(db)
+    .await
+    .expect("subject can be inserted");
+}
@@ -1,17 +1,43 @@
+mod account;
 mod auth;
+mod body;
 mod error;
+mod events;
+mod goals;
+mod idempotency;
+mod notifications;
+mod settings;
+mod sharing;
 mod sleep;
+mod subjects;
+mod tags;
+mod webhooks;
+pub use account::DataExportWorker;
 pub use error::*;
+pub use idempotency::IdempotencyKeyPurger;
+pub use notifications::ReminderScheduler;
+pub use sleep::{SleepEventBus, StaleSleepSweeper, TrashPurger};
+pub use webhooks::WebhookWorker;
 
 use axum::{routing::get, Router};
 
 use crate::AppState;
 
-pub fn get_router() -> Router<AppState> {
+/// The state is needed by the middleware of some of the routers.
+pub fn get_router(app_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/", get(root))
-        .nest("/auth", crate::v1::auth::get_router())
-        .nest("/sleep", crate::v1::sleep::get_router())
+        .nest("/account", crate::v1::account::get_router())
+        .nest("/auth", crate::v1::auth::get_router(app_state))
+        .nest("/events", crate::v1::events::get_router())
+        .nest("/goals", crate::v1::goals::get_router())
+        .nest("/notifications", crate::v1::notifications::get_router())
+        .nest("/settings", crate::v1::settings::get_router())
+        .nest("/sharing", crate::v1::sharing::get_router())
+        .nest("/sleep", crate::v1::sleep::get_router(app_state))
+        .nest("/subjects", crate::v1::subjects::get_router())
+        .nest("/tags", crate::v1::tags::get_router())
+        .nest("/webhooks", crate::v1::webhooks::get_router())
 }
 
 async fn root() -> &'static str {
@@ -0,0 +1,29 @@
+mod archive;
+mod data_export;
+mod export_worker;
+
+pub use export_worker::DataExportWorker;
+
+use axum::{routing::get, Router};
+
+use crate::AppState;
+
+use self::data_export::{download_export, get_export, list_exports, request_export};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/export", get(list_exports).post(request_export))
+        .route("/export/:id", get(get_export))
+        .route("/export/:id/download", get(download_export))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Account API\n",
+        "POST /export -- ask for a ZIP archive of everything stored about you, with a JSON file for each kind of data; it is built in the background and you get an email when it is ready, or 409 if one is already being built\n",
+        "GET /export -- your data exports, newest first\n",
+        "GET /export/<id> -- the status of a data export: pending, ready, failed or expired\n",
+        "GET /export/<id>/download -- download the archive of a data export, for 7 days after it is built (409 if it is not ready, 410 if it expired)\n",
+    )
+}
@@ -0,0 +1,137 @@
+use std::io::{Cursor, Write};
+
+use api_types::{v1::DateTimeUtc, Snowflake};
+use chrono::{Datelike, Timelike};
+use serde_json::Value;
+use sqlx::{query_scalar, SqliteConnection, SqlitePool};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// Columns that are left out of the archive: secrets that would let someone log in as the user,
+/// and the earlier archives themselves.
+const OMITTED_COLUMNS: &[(&str, &str)] = &[
+    ("user", "password_hash"),
+    ("user_token", "token"),
+    ("feed_token", "token"),
+    ("share_link", "token"),
+    ("registration", "password_hash"),
+    ("registration", "confirm_token"),
+    ("webhook_subscription", "secret"),
+    ("data_export", "archive"),
+];
+
+const README: &str = "\
+This archive contains everything Oyasumi.app stores about your account, as of the time it was built.
+
+Each file holds the rows of one table of our database that belong to you, as a list of JSON objects.
+Times whose names end with unix_time are in seconds since 1970-01-01 00:00 UTC.
+Passwords, login tokens and feed tokens are left out, since they could be used to log in as you.
+The secrets of your webhooks are left out as well, since they could be used to forge webhook payloads.
+The tokens of your public report links are left out too, since anyone who has them can see the reports.
+";
+
+/// Build a ZIP file with a JSON file for every table that has rows belonging to the user.
+///
+/// The tables are found by looking at the database itself, so that new tables are never forgotten:
+/// a table belongs to users if it has a `user_id` column, or a `sleep_state_id` column for the
+/// tables that hang off a sleep state.
+pub async fn build_archive(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    now: DateTimeUtc,
+) -> anyhow::Result<Vec<u8>> {
+    // Read everything in one transaction, so that the files agree with each other
+    let mut tx = db.begin().await?;
+    let tables: Vec<String> = query_scalar(
+        r#"SELECT name FROM sqlite_master
+            WHERE type='table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE '_sqlx_%'
+            ORDER BY name"#,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let modified = zip::DateTime::from_date_and_time(
+        now.year().clamp(1980, 2107) as u16,
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+    )
+    .unwrap_or_default();
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(modified);
+
+    zip.start_file("README.txt", options)?;
+    zip.write_all(README.as_bytes())?;
+    for table in tables {
+        let Some(rows) = table_rows(&mut tx, &table, user_id).await? else {
+            continue;
+        };
+        zip.start_file(format!("{table}.json"), options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&rows)?)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// The user's rows of a table as JSON objects, or nothing if the table does not belong to users.
+async fn table_rows(
+    conn: &mut SqliteConnection,
+    table: &str,
+    user_id: Snowflake,
+) -> Result<Option<Vec<Value>>, sqlx::Error> {
+    let columns: Vec<String> = query_scalar("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(&mut *conn)
+        .await?;
+    let has_column = |name: &str| columns.iter().any(|column| column == name);
+    let condition = if table == "user" {
+        "id=?1"
+    } else if has_column("grantee_user_id") {
+        // Sharing grants belong to both the user who gave them and the user who received them
+        "user_id=?1 OR grantee_user_id=?1"
+    } else if has_column("user_id") {
+        "user_id=?1"
+    } else if has_column("sleep_state_id") {
+        "sleep_state_id IN (SELECT id FROM sleep_state WHERE user_id=?1)"
+    } else if has_column("subscription_id") {
+        "subscription_id IN (SELECT id FROM webhook_subscription WHERE user_id=?1)"
+    } else if table == "registration" {
+        // Registrations are deleted when they are confirmed, but another one may be pending
+        "email=(SELECT email FROM user WHERE id=?1)"
+    } else {
+        return Ok(None);
+    };
+
+    // JSON cannot hold binary values, so they are written in hexadecimal
+    let fields: Vec<String> = columns
+        .iter()
+        .filter(|column| !OMITTED_COLUMNS.contains(&(table, column.as_str())))
+        .map(|column| {
+            let quoted = quote_identifier(column);
+            format!(
+                "'{}', CASE WHEN typeof({quoted})='blob' THEN hex({quoted}) ELSE {quoted} END",
+                column.replace('\'', "''")
+            )
+        })
+        .collect();
+    let sql = format!(
+        "SELECT json_object({}) FROM {} WHERE {condition} ORDER BY rowid",
+        fields.join(", "),
+        quote_identifier(table),
+    );
+    let rows: Vec<String> = query_scalar(&sql)
+        .bind(user_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(Some(
+        rows.iter()
+            .map(|row| serde_json::from_str(row).expect("SQLite always returns valid JSON"))
+            .collect(),
+    ))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
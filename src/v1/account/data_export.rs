@@ -0,0 +1,168 @@
+use api_types::{
+    v1::{DataExport, DataExportStatus},
+    Snowflake,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    Json,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+/// A row of the `data_export` table, without the archive itself.
+#[derive(Debug, Clone)]
+pub struct DataExportRow {
+    pub id: i64,
+    pub status: String,
+    pub requested_at_unix_time: i64,
+    pub completed_at_unix_time: Option<i64>,
+    pub expires_at_unix_time: Option<i64>,
+    pub size_bytes: Option<i64>,
+}
+
+impl DataExportRow {
+    pub fn into_api(self) -> DataExport {
+        DataExport {
+            id: self.id.into(),
+            // The database has a CHECK constraint on this column, so parsing cannot fail
+            status: self.status.parse().unwrap_or(DataExportStatus::Failed),
+            requested_at: datetime_utc_from_timestamp(self.requested_at_unix_time),
+            completed_at: self.completed_at_unix_time.map(datetime_utc_from_timestamp),
+            expires_at: self.expires_at_unix_time.map(datetime_utc_from_timestamp),
+            size_bytes: self.size_bytes.map(|size| size as u64),
+        }
+    }
+}
+
+/// Ask for an archive of everything stored about the user.
+/// It is built in the background, and the user gets an email when it can be downloaded.
+pub async fn request_export(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Result<(StatusCode, Json<DataExport>), (StatusCode, String)>> {
+    let pending = query!(
+        "SELECT id FROM data_export WHERE user_id=? AND status='pending'",
+        conn_user.id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    if let Some(pending) = pending {
+        return Ok(Err((
+            StatusCode::CONFLICT,
+            format!("export {} is already being built", pending.id),
+        )));
+    }
+
+    let id = Snowflake::new().await;
+    let requested = app_state.clock.now().timestamp();
+    query!(
+        "INSERT INTO data_export (id, user_id, status, requested_at_unix_time) VALUES (?,?,'pending',?)",
+        id,
+        conn_user.id,
+        requested
+    )
+    .execute(&app_state.db)
+    .await?;
+    let export = find_export(&app_state.db, conn_user.id, id).await?;
+    Ok(Ok((StatusCode::ACCEPTED, Json(export.into_api()))))
+}
+
+/// The user's data exports, newest first.
+pub async fn list_exports(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<DataExport>>> {
+    let rows = query_as!(
+        DataExportRow,
+        r#"SELECT id, status, requested_at_unix_time, completed_at_unix_time, expires_at_unix_time, size_bytes
+            FROM data_export WHERE user_id=?
+            ORDER BY requested_at_unix_time DESC"#,
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(DataExportRow::into_api).collect(),
+    ))
+}
+
+pub async fn get_export(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<DataExport>> {
+    let export = find_export(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(export.into_api()))
+}
+
+/// Download the ZIP file of an export, or 409 if it is not ready, or 410 if it expired.
+pub async fn download_export(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Result<(HeaderMap, Bytes), (StatusCode, String)>> {
+    let export = find_export(&app_state.db, conn_user.id, id)
+        .await?
+        .into_api();
+    let now = app_state.clock.now();
+    let expired = export.status == DataExportStatus::Expired
+        || export.expires_at.is_some_and(|expires| expires <= now);
+    if expired {
+        return Ok(Err((
+            StatusCode::GONE,
+            "the archive expired, so request a new export".to_string(),
+        )));
+    }
+    if export.status != DataExportStatus::Ready {
+        return Ok(Err((
+            StatusCode::CONFLICT,
+            format!("the export is {}, not ready", export.status),
+        )));
+    }
+
+    let row = query!(
+        "SELECT archive FROM data_export WHERE id=? AND user_id=?",
+        id,
+        conn_user.id
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+    let archive = row.archive.ok_or(ApiError::NotFound)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"oyasumi-export-{id}.zip\""))
+            .expect("the file name is always a valid header value"),
+    );
+    Ok(Ok((headers, Bytes::from(archive))))
+}
+
+/// Find a data export by ID, making sure that it belongs to the user.
+async fn find_export(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<DataExportRow, ApiError> {
+    query_as!(
+        DataExportRow,
+        r#"SELECT id, status, requested_at_unix_time, completed_at_unix_time, expires_at_unix_time, size_bytes
+            FROM data_export WHERE id=? AND user_id=?"#,
+        id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
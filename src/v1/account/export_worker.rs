@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use api_types::Snowflake;
+use chrono::Duration;
+use sqlx::{query, SqlitePool};
+
+use crate::clock::Clock;
+
+use super::archive::build_archive;
+
+/// How often to look for exports to build, and for archives to delete.
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long an archive can be downloaded after it is built, in days.
+pub const DOWNLOAD_DAYS: i64 = 7;
+
+/// Builds the data exports that users asked for, and deletes the archives that expired.
+pub struct DataExportWorker {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+}
+
+impl DataExportWorker {
+    pub fn new(db: SqlitePool, clock: Arc<dyn Clock>) -> Self {
+        Self { db, clock }
+    }
+
+    /// Check for work every few seconds, in the background.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.tick().await {
+                    tracing::error!("Failed to process data exports: {err}");
+                }
+            }
+        });
+    }
+
+    /// Delete the expired archives, then build every pending export, oldest first.
+    pub async fn tick(&self) -> Result<(), sqlx::Error> {
+        let now = self.clock.now().timestamp();
+        let result = query!(
+            r#"UPDATE data_export SET status='expired', archive=NULL
+                WHERE status='ready' AND expires_at_unix_time<=?"#,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() > 0 {
+            tracing::info!("Deleted {} expired data exports", result.rows_affected());
+        }
+
+        let pending = query!(
+            r#"SELECT data_export.id, data_export.user_id, user.email
+                FROM data_export JOIN user ON user.id=data_export.user_id
+                WHERE status='pending'
+                ORDER BY requested_at_unix_time"#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for row in pending {
+            self.build(row.id.into(), row.user_id.into(), &row.email)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn build(
+        &self,
+        id: Snowflake,
+        user_id: Snowflake,
+        email: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = self.clock.now();
+        let completed = now.timestamp();
+        let archive = match build_archive(&self.db, user_id, now).await {
+            Ok(archive) => archive,
+            Err(err) => {
+                tracing::error!("Failed to build data export {id}: {err}");
+                query!(
+                    "UPDATE data_export SET status='failed', completed_at_unix_time=? WHERE id=?",
+                    completed,
+                    id
+                )
+                .execute(&self.db)
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let expires = now + Duration::days(DOWNLOAD_DAYS);
+        let expires_timestamp = expires.timestamp();
+        let size = archive.len() as i64;
+        query!(
+            r#"UPDATE data_export
+                SET status='ready', archive=?, size_bytes=?, completed_at_unix_time=?, expires_at_unix_time=?
+                WHERE id=?"#,
+            archive,
+            size,
+            completed,
+            expires_timestamp,
+            id
+        )
+        .execute(&self.db)
+        .await?;
+        tracing::info!("Built data export {id} of {size} bytes");
+
+        // The archive can be downloaded anyway, so failing to send the email is not fatal
+        let download_path = format!("/v1/account/export/{id}/download");
+        let expires = expires.format("%Y-%m-%d %H:%M UTC").to_string();
+        let sent = match email.parse() {
+            Ok(address) => {
+                let message = mail::templates::data_export::make_data_export_ready_email(
+                    address,
+                    &download_path,
+                    &expires,
+                );
+                mail::delivery::send_message(message)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+            Err(err) => Err(format!("invalid email address: {err}")),
+        };
+        if let Err(err) = sent {
+            tracing::warn!("Failed to send the email about data export {id}: {err}");
+        }
+        Ok(())
+    }
+}
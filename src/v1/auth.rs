@@ -9,18 +9,21 @@ mod tokens;
 mod check;
 use check::check;
 
-use crate::AppState;
+use crate::{v1::idempotency::idempotent, AppState};
 use axum::{
-    routing::{get, post},
+    middleware::from_fn_with_state,
+    routing::{delete, get, post},
     Router,
 };
 
-pub fn get_router() -> Router<AppState> {
+pub fn get_router(app_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
         .route(
             "/registration",
-            post(make_registration).get(get_registration_info),
+            post(make_registration)
+                .route_layer(from_fn_with_state(app_state.clone(), idempotent))
+                .get(get_registration_info),
         )
         .route("/registration/:id", get(get_registration))
         .route("/registration/:id/confirm", post(confirm_registration))
@@ -42,4 +45,9 @@ pub fn get_router() -> Router<AppState> {
             "/token/list",
             get(tokens::get_user_tokens).delete(tokens::delete_user_tokens),
         )
+        .route(
+            "/token/feed",
+            get(tokens::get_feed_tokens).post(tokens::create_feed_token),
+        )
+        .route("/token/feed/:id", delete(tokens::delete_feed_token))
 }
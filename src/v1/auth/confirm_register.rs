@@ -98,6 +98,16 @@ pub async fn confirm_registration(
             created_by_ip,
             expires
         ).execute(&mut tx).await?;
+        // Every user has a subject that stands for themselves, with the same ID
+        let created = now.timestamp();
+        query!(
+            "INSERT INTO subject (id, user_id, name, is_self, created_at_unix_time) VALUES (?,?,'Me',1,?)",
+            pending_registration.id,
+            pending_registration.id,
+            created,
+        )
+        .execute(&mut tx)
+        .await?;
         tx.commit().await?;
         new_token
     };
@@ -1,7 +1,9 @@
 mod delete;
+mod feed;
 mod get;
 mod list;
 
 pub use delete::*;
+pub use feed::*;
 pub use get::*;
 pub use list::*;
@@ -0,0 +1,86 @@
+use api_types::v1::FeedToken;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use crypto::token::generate_token;
+use sqlx::query;
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser, Snowflake,
+};
+
+/// Create a token for the calendar feed.
+/// The token itself is only returned here, so it cannot be read back later.
+pub async fn create_feed_token(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<(StatusCode, Json<FeedToken>)> {
+    const TOKEN_LENGTH: u16 = 32;
+    let token = generate_token(TOKEN_LENGTH);
+    let id = Snowflake::new().await;
+    let created = app_state.clock.now();
+    let created_timestamp = created.timestamp();
+    query!(
+        "INSERT INTO feed_token (id, token, user_id, created_at_unix_time) VALUES (?,?,?,?)",
+        id,
+        token,
+        conn_user.id,
+        created_timestamp,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(FeedToken {
+            id,
+            token: Some(token),
+            created,
+            last_used: None,
+        }),
+    ))
+}
+
+pub async fn get_feed_tokens(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<FeedToken>>> {
+    let tokens = query!(
+        "SELECT * FROM feed_token WHERE user_id=? ORDER BY created_at_unix_time",
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?
+    .into_iter()
+    .map(|row| FeedToken {
+        id: row.id.into(),
+        token: None,
+        created: datetime_utc_from_timestamp(row.created_at_unix_time),
+        last_used: row.last_used_at_unix_time.map(datetime_utc_from_timestamp),
+    })
+    .collect();
+    Ok(Json(tokens))
+}
+
+/// Revoke a feed token, so that calendars using it cannot fetch the feed anymore.
+pub async fn delete_feed_token(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let result = query!(
+        "DELETE FROM feed_token WHERE id=? AND user_id=?",
+        id,
+        conn_user.id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,16 @@
+use axum::body::Bytes;
+use serde::de::DeserializeOwned;
+
+use super::ApiError;
+
+/// Parse a JSON request body that may be left out entirely.
+///
+/// An empty body gives the default value, while an invalid one is a bad request
+/// (unlike with `Option<Json<T>>`, which silently ignores invalid bodies).
+pub fn parse_optional_json<T: DeserializeOwned + Default>(body: &Bytes) -> Result<T, ApiError> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body)
+        .map_err(|err| ApiError::BadRequest(format!("invalid request body: {err}")))
+}
@@ -15,6 +15,7 @@ impl axum::response::IntoResponse for ResponseError {
                 ApiError::DatabaseErr(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 ApiError::NotFound => StatusCode::NOT_FOUND,
                 ApiError::Forbidden => StatusCode::FORBIDDEN,
+                ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
                 ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             }
         } else {
@@ -43,6 +44,9 @@ pub enum ApiError {
 
     #[error("you do not have access to this entity")]
     Forbidden,
+
+    #[error("invalid request: {0}")]
+    BadRequest(String),
 }
 
 impl From<sqlx::Error> for ApiError {
@@ -53,7 +57,16 @@ impl From<sqlx::Error> for ApiError {
 
 impl From<sqlx::Error> for ResponseError {
     fn from(err: sqlx::Error) -> Self {
-        err.into()
+        ApiError::from(err).into()
+    }
+}
+
+/// Whether the error is caused by violating a UNIQUE constraint,
+/// which usually means that the entity already exists.
+pub fn is_unique_violation(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => err.message().contains("UNIQUE constraint failed"),
+        _ => false,
     }
 }
 
@@ -0,0 +1,185 @@
+mod create;
+mod delete;
+mod get;
+mod list;
+mod types;
+mod update;
+
+use api_types::{
+    v1::{Event, EventKind, EventType},
+    Snowflake,
+};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{datetime_utc_from_timestamp, v1::ApiError, AppState};
+
+use self::{
+    create::{create_event, start_now},
+    delete::{delete_by_id, delete_current},
+    get::{get_by_id, get_current},
+    list::list_events,
+    types::{create_type, delete_type, get_type, list_types, put_type},
+    update::{put_by_id, set_current_end, set_current_start},
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_events))
+        .route("/stream", get(crate::v1::sleep::stream_sleep_events))
+        .route("/new", post(create_event))
+        .route("/:id", get(get_by_id).delete(delete_by_id).put(put_by_id))
+        .route("/types/list", get(list_types))
+        .route("/types/new", post(create_type))
+        .route(
+            "/types/:type_id",
+            get(get_type).put(put_type).delete(delete_type),
+        )
+        .route("/types/:type_id/new", post(start_now))
+        .route(
+            "/types/:type_id/@current",
+            get(get_current)
+                .post(set_current_end)
+                .put(set_current_start)
+                .delete(delete_current),
+        )
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Event API\n",
+        "GET /list -- list of all events you have (filter with ?event_type_id=<id>&from=<time>&to=<time>)\n",
+        "GET /stream -- Server-Sent Events named sleep_state, pushed whenever one of your sleep states is created, updated or deleted on any device, with the sleep state as JSON (\"Lagged\" if some were missed, so fetch your sleep states again)\n",
+        "POST /new -- create an event with the given times (409 if it has no end and one of its type is already going on)\n",
+        "GET /<id> -- get event by ID\n",
+        "PUT /<id> -- change event by ID (ID in body must match the entry's data; 409 if it would have no end and another one of its type is going on)\n",
+        "DELETE /<id> -- delete event by ID, or 404\n",
+        "GET /types/list -- list of all event types you have defined\n",
+        "POST /types/new -- create an event type, or 409 if you already have one with that name\n",
+        "GET /types/<type id> -- get event type by ID\n",
+        "PUT /types/<type id> -- change event type by ID (ID in body must match the entry's data; the kind cannot be changed)\n",
+        "DELETE /types/<type id> -- delete event type by ID, along with all of its events, or 404\n",
+        "POST /types/<type id>/new -- record an instant event now, or start an interval event now (409 if one is already going on)\n",
+        "GET /types/<type id>/@current -- the interval event of this type that is not completed, or 404\n",
+        "POST /types/<type id>/@current -- modify the current event, so that its end time is now\n",
+        "PUT /types/<type id>/@current -- modify the current event, so that its start time is now\n",
+        "DELETE /types/<type id>/@current -- delete the current event\n",
+    )
+}
+
+/// A row of the `event_type` table.
+#[derive(Debug, Clone)]
+pub struct EventTypeRow {
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    pub unit: Option<String>,
+}
+
+impl EventTypeRow {
+    pub fn kind(&self) -> EventKind {
+        // The database has a CHECK constraint on this column, so parsing cannot fail
+        self.kind.parse().unwrap_or(EventKind::Instant)
+    }
+
+    pub fn into_api(self) -> EventType {
+        EventType {
+            id: self.id.into(),
+            kind: self.kind(),
+            name: self.name,
+            unit: self.unit,
+        }
+    }
+}
+
+/// A row of the `event` table.
+#[derive(Debug, Clone)]
+pub struct EventRow {
+    pub id: i64,
+    pub event_type_id: i64,
+    pub started_at_unix_time: i64,
+    pub ended_at_unix_time: Option<i64>,
+    pub value: Option<f64>,
+    pub comment: Option<String>,
+}
+
+impl EventRow {
+    pub fn into_api(self) -> Event {
+        Event {
+            id: self.id.into(),
+            event_type_id: self.event_type_id.into(),
+            start: datetime_utc_from_timestamp(self.started_at_unix_time),
+            end: self.ended_at_unix_time.map(datetime_utc_from_timestamp),
+            value: self.value,
+            comment: self.comment,
+        }
+    }
+}
+
+/// Find an event type by ID, making sure that it belongs to the user.
+async fn find_type(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    type_id: Snowflake,
+) -> Result<EventTypeRow, ApiError> {
+    query_as!(
+        EventTypeRow,
+        "SELECT id, name, kind, unit FROM event_type WHERE user_id=? AND id=?",
+        user_id,
+        type_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// Find an interval event type, because only those can have current events.
+async fn find_interval_type(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    type_id: Snowflake,
+) -> Result<EventTypeRow, ApiError> {
+    let event_type = find_type(db, user_id, type_id).await?;
+    if event_type.kind() != EventKind::Interval {
+        return Err(ApiError::BadRequest(format!(
+            "{:?} is an instant event type, so its events cannot be current",
+            event_type.name
+        )));
+    }
+    Ok(event_type)
+}
+
+/// Whether an event of this type other than `id` is going on, because only one can be at a time.
+async fn other_event_going_on(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    type_id: i64,
+    id: Snowflake,
+) -> Result<bool, sqlx::Error> {
+    let row = query!(
+        "SELECT id FROM event WHERE user_id=? AND event_type_id=? AND ended_at_unix_time IS NULL AND id!=?",
+        user_id,
+        type_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row.is_some())
+}
+
+/// Check that the times of an event make sense for its type.
+fn validate_times(event_type: &EventTypeRow, start: i64, end: Option<i64>) -> Result<(), ApiError> {
+    match (event_type.kind(), end) {
+        (EventKind::Instant, Some(_)) => Err(ApiError::BadRequest(
+            "instant events cannot have an end".to_string(),
+        )),
+        (EventKind::Interval, Some(end)) if end < start => Err(ApiError::BadRequest(
+            "event cannot end before it starts".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
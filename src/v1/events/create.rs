@@ -0,0 +1,112 @@
+use api_types::{
+    v1::{Event, EventKind, NewEvent, NewEventNow},
+    Snowflake,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{body::parse_optional_json, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_type, other_event_going_on, validate_times};
+
+pub async fn create_event(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_event): Json<NewEvent>,
+) -> ResultResponse<Result<(StatusCode, Json<Event>), StatusCode>> {
+    let event_type = find_type(&app_state.db, conn_user.id, new_event.event_type_id).await?;
+    let start = new_event.start.timestamp();
+    let end = new_event.end.map(|end| end.timestamp());
+    validate_times(&event_type, start, end)?;
+
+    let id = Snowflake::new().await;
+    if event_type.kind() == EventKind::Interval
+        && end.is_none()
+        && other_event_going_on(&app_state.db, conn_user.id, event_type.id, id).await?
+    {
+        return Ok(Err(StatusCode::CONFLICT));
+    }
+    query!(
+        r#"INSERT INTO event
+            (id, user_id, event_type_id, started_at_unix_time, ended_at_unix_time, value, comment)
+            VALUES (?,?,?,?,?,?,?)"#,
+        id,
+        conn_user.id,
+        event_type.id,
+        start,
+        end,
+        new_event.value,
+        new_event.comment,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(Event {
+            id,
+            event_type_id: new_event.event_type_id,
+            start: new_event.start,
+            end: new_event.end,
+            value: new_event.value,
+            comment: new_event.comment,
+        }),
+    )))
+}
+
+/// Record an instant event now, or start an interval event now.
+///
+/// For interval events, this returns 409 if an event of this type is already going on.
+pub async fn start_now(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+    body: Bytes,
+) -> ResultResponse<Result<(StatusCode, Json<Event>), StatusCode>> {
+    let new_event: NewEventNow = parse_optional_json(&body)?;
+    let event_type = find_type(&app_state.db, conn_user.id, type_id).await?;
+
+    let id = Snowflake::new().await;
+    if event_type.kind() == EventKind::Interval
+        && other_event_going_on(&app_state.db, conn_user.id, event_type.id, id).await?
+    {
+        return Ok(Err(StatusCode::CONFLICT));
+    }
+
+    let now = app_state.clock.now().timestamp();
+    query!(
+        r#"INSERT INTO event
+            (id, user_id, event_type_id, started_at_unix_time, ended_at_unix_time, value, comment)
+            VALUES (?,?,?,?,?,?,?)"#,
+        id,
+        conn_user.id,
+        event_type.id,
+        now,
+        Option::<i64>::None,
+        new_event.value,
+        new_event.comment,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(Event {
+            id,
+            event_type_id: type_id,
+            start: datetime_utc_from_timestamp(now),
+            end: None,
+            value: new_event.value,
+            comment: new_event.comment,
+        }),
+    )))
+}
@@ -0,0 +1,52 @@
+use api_types::Snowflake;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::find_interval_type;
+
+pub async fn delete_by_id(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let row = query!(
+        "DELETE FROM event WHERE user_id=? AND id=? RETURNING event.id",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn delete_current(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let event_type = find_interval_type(&app_state.db, conn_user.id, type_id).await?;
+    let row = query!(
+        "DELETE FROM event WHERE user_id=? AND event_type_id=? AND ended_at_unix_time IS NULL RETURNING event.id",
+        conn_user.id,
+        event_type.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
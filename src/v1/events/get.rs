@@ -0,0 +1,56 @@
+use api_types::{v1::Event, Snowflake};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_interval_type, EventRow};
+
+pub async fn get_by_id(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Event>> {
+    let row = query_as!(
+        EventRow,
+        r#"SELECT id, event_type_id, started_at_unix_time, ended_at_unix_time, value, comment
+            FROM event WHERE user_id=? AND id=?"#,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn get_current(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<Json<Event>> {
+    let event_type = find_interval_type(&app_state.db, conn_user.id, type_id).await?;
+    let row = query_as!(
+        EventRow,
+        r#"SELECT id, event_type_id, started_at_unix_time, ended_at_unix_time, value, comment
+            FROM event WHERE user_id=? AND event_type_id=? AND ended_at_unix_time IS NULL"#,
+        conn_user.id,
+        event_type.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
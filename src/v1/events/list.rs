@@ -0,0 +1,37 @@
+use api_types::v1::{Event, EventListQuery};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::EventRow;
+
+pub async fn list_events(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(filter): Query<EventListQuery>,
+) -> ResultResponse<Json<Vec<Event>>> {
+    let event_type_id = filter.event_type_id.map(i64::from);
+    let from = filter.from.map(|from| from.timestamp()).unwrap_or(i64::MIN);
+    let to = filter.to.map(|to| to.timestamp()).unwrap_or(i64::MAX);
+    let rows = query_as!(
+        EventRow,
+        r#"SELECT id, event_type_id, started_at_unix_time, ended_at_unix_time, value, comment
+            FROM event
+            WHERE user_id=? AND (? IS NULL OR event_type_id=?)
+                AND started_at_unix_time>=? AND started_at_unix_time<?
+            ORDER BY started_at_unix_time"#,
+        conn_user.id,
+        event_type_id,
+        event_type_id,
+        from,
+        to,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(EventRow::into_api).collect()))
+}
@@ -0,0 +1,147 @@
+use api_types::{
+    v1::{EventType, NewEventType},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as};
+
+use crate::{
+    v1::{is_unique_violation, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_type, EventTypeRow};
+
+const MAX_NAME_LENGTH: usize = 64;
+
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "event type name cannot be empty".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "event type name cannot be longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
+
+pub async fn list_types(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<EventType>>> {
+    let rows = query_as!(
+        EventTypeRow,
+        "SELECT id, name, kind, unit FROM event_type WHERE user_id=? ORDER BY name",
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(EventTypeRow::into_api).collect()))
+}
+
+pub async fn create_type(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_type): Json<NewEventType>,
+) -> ResultResponse<Result<(StatusCode, Json<EventType>), StatusCode>> {
+    validate_name(&new_type.name)?;
+
+    let id = Snowflake::new().await;
+    let kind = new_type.kind.to_string();
+    let result = query!(
+        "INSERT INTO event_type (id, user_id, name, kind, unit) VALUES (?,?,?,?,?)",
+        id,
+        conn_user.id,
+        new_type.name,
+        kind,
+        new_type.unit,
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Ok(Err(StatusCode::CONFLICT)),
+        Err(err) => return Err(err)?,
+    }
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(EventType {
+            id,
+            name: new_type.name,
+            kind: new_type.kind,
+            unit: new_type.unit,
+        }),
+    )))
+}
+
+pub async fn get_type(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<Json<EventType>> {
+    let event_type = find_type(&app_state.db, conn_user.id, type_id).await?;
+    Ok(Json(event_type.into_api()))
+}
+
+pub async fn put_type(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+    Json(new_type): Json<EventType>,
+) -> ResultResponse<StatusCode> {
+    if new_type.id != type_id {
+        return Ok(StatusCode::CONFLICT);
+    }
+    validate_name(&new_type.name)?;
+    let event_type = find_type(&app_state.db, conn_user.id, type_id).await?;
+    if event_type.kind() != new_type.kind {
+        // The existing events would not make sense anymore
+        return Err(ApiError::BadRequest(
+            "the kind of an event type cannot be changed".to_string(),
+        ))?;
+    }
+
+    let result = query!(
+        "UPDATE event_type SET name=?, unit=? WHERE user_id=? AND id=?",
+        new_type.name,
+        new_type.unit,
+        conn_user.id,
+        type_id
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(err) if is_unique_violation(&err) => Ok(StatusCode::CONFLICT),
+        Err(err) => Err(err)?,
+    }
+}
+
+pub async fn delete_type(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    // The events of this type are deleted by the foreign key cascade
+    let row = query!(
+        "DELETE FROM event_type WHERE user_id=? AND id=? RETURNING id",
+        conn_user.id,
+        type_id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
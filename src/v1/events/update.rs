@@ -0,0 +1,110 @@
+use api_types::{
+    v1::{Event, EventKind},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::{find_interval_type, find_type, other_event_going_on, validate_times};
+
+pub async fn put_by_id(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+    Json(new_event): Json<Event>,
+) -> ResultResponse<StatusCode> {
+    if new_event.id != id {
+        return Ok(StatusCode::CONFLICT);
+    }
+    let event_type = find_type(&app_state.db, conn_user.id, new_event.event_type_id).await?;
+    let start = new_event.start.timestamp();
+    let end = new_event.end.map(|i| i.timestamp());
+    validate_times(&event_type, start, end)?;
+    if event_type.kind() == EventKind::Interval
+        && end.is_none()
+        && other_event_going_on(&app_state.db, conn_user.id, event_type.id, id).await?
+    {
+        return Ok(StatusCode::CONFLICT);
+    }
+
+    let row = query!(
+        r#"
+            UPDATE event SET
+                event_type_id=?,
+                started_at_unix_time=?,
+                ended_at_unix_time=?,
+                value=?,
+                comment=?
+            WHERE user_id=? AND id=?
+            RETURNING event.id"#,
+        event_type.id,
+        start,
+        end,
+        new_event.value,
+        new_event.comment,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Ok(StatusCode::NOT_FOUND),
+    }
+}
+
+/// End the current event of this type now.
+pub async fn set_current_end(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let event_type = find_interval_type(&app_state.db, conn_user.id, type_id).await?;
+    let now = app_state.clock.now().timestamp();
+    let row = query!(
+        r#"UPDATE event
+        SET ended_at_unix_time=?
+        WHERE user_id=? AND event_type_id=? AND ended_at_unix_time IS NULL
+        RETURNING event.id"#,
+        now,
+        conn_user.id,
+        event_type.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    match row {
+        Some(_row) => Ok(StatusCode::OK),
+        None => Ok(StatusCode::NOT_FOUND),
+    }
+}
+
+pub async fn set_current_start(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(type_id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let event_type = find_interval_type(&app_state.db, conn_user.id, type_id).await?;
+    let now = app_state.clock.now().timestamp();
+    let row = query!(
+        r#"UPDATE event
+        SET started_at_unix_time=?
+        WHERE user_id=? AND event_type_id=? AND ended_at_unix_time IS NULL
+        RETURNING event.id"#,
+        now,
+        conn_user.id,
+        event_type.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Ok(StatusCode::NOT_FOUND),
+    }
+}
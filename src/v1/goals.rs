@@ -0,0 +1,149 @@
+mod adherence;
+mod create;
+mod delete;
+mod get;
+mod list;
+
+use api_types::{v1::SleepGoal, Snowflake};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use sqlx::{query_as, SqlitePool};
+
+use crate::{
+    v1::{settings::SleepContext, ApiError, ResultResponse},
+    AppState,
+};
+
+use self::{
+    adherence::get_adherence,
+    create::create_goal,
+    delete::delete_goal,
+    get::{get_current_goal, get_goal},
+    list::list_goals,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_goals))
+        .route("/new", post(create_goal))
+        .route("/adherence", get(get_adherence))
+        .route("/@current", get(get_current_goal))
+        .route("/:id", get(get_goal).delete(delete_goal))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Sleep goal API\n",
+        "GET /list -- history of your goals, ordered by the date they take effect\n",
+        "POST /new -- add a goal that applies from its effective date (the current night or later) until the next one, or 409 if you already have a goal taking effect on that date\n",
+        "GET /adherence?from=YYYY-MM-DD&to=YYYY-MM-DD -- how each night went compared to the goal that applied to it, with the current and longest streaks\n",
+        "GET /@current -- the goal that applies to the current night, or 404\n",
+        "GET /<id> -- get goal by ID\n",
+        "DELETE /<id> -- delete a goal that has not taken effect yet by ID, so that the previous goal applies instead, or 404\n",
+    )
+}
+
+/// A row of the `sleep_goal` table.
+#[derive(Debug, Clone)]
+pub struct SleepGoalRow {
+    pub id: i64,
+    pub effective_from: String,
+    pub target_bedtime_minute: Option<i64>,
+    pub target_wake_time_minute: Option<i64>,
+    pub min_duration_minutes: Option<i64>,
+    pub tolerance_minutes: i64,
+}
+
+impl SleepGoalRow {
+    pub fn into_api(self) -> SleepGoal {
+        // The database has CHECK constraints on these columns, and the dates are written by us,
+        // so the conversions cannot fail
+        SleepGoal {
+            id: self.id.into(),
+            effective_from: self.effective_from.parse().unwrap_or(NaiveDate::MIN),
+            target_bedtime: self.target_bedtime_minute.map(time_from_minute),
+            target_wake_time: self.target_wake_time_minute.map(time_from_minute),
+            min_duration_minutes: self.min_duration_minutes.map(|minutes| minutes as u32),
+            tolerance_minutes: self.tolerance_minutes as u32,
+        }
+    }
+}
+
+/// Load all the goals of a user, ordered by the date they take effect.
+pub async fn load_goals(
+    db: &SqlitePool,
+    user_id: Snowflake,
+) -> Result<Vec<SleepGoal>, sqlx::Error> {
+    let rows = query_as!(
+        SleepGoalRow,
+        r#"SELECT id, effective_from, target_bedtime_minute, target_wake_time_minute,
+            min_duration_minutes, tolerance_minutes
+            FROM sleep_goal WHERE user_id=? ORDER BY effective_from"#,
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(SleepGoalRow::into_api).collect())
+}
+
+/// The goal that applies to the given night: the last one that took effect by then.
+///
+/// `goals` must be ordered by the date they take effect, like [`load_goals`] returns them.
+pub fn goal_for_night(goals: &[SleepGoal], sleep_date: NaiveDate) -> Option<&SleepGoal> {
+    goals
+        .iter()
+        .rev()
+        .find(|goal| goal.effective_from <= sleep_date)
+}
+
+/// The sleep date of the current night, in the user's local time.
+///
+/// Goals can only be added or deleted from this night on, so that the adherence of past nights never changes.
+async fn current_night(app_state: &AppState, user_id: Snowflake) -> ResultResponse<NaiveDate> {
+    let context = SleepContext::load(&app_state.db, user_id).await?;
+    Ok(context.local_day.sleep_date(app_state.clock.now()))
+}
+
+/// The number of minutes since midnight, dropping any seconds.
+fn minute_of_day(time: NaiveTime) -> i64 {
+    (time.hour() * 60 + time.minute()) as i64
+}
+
+fn time_from_minute(minute: i64) -> NaiveTime {
+    NaiveTime::from_hms_opt((minute / 60) as u32, (minute % 60) as u32, 0).unwrap_or(NaiveTime::MIN)
+}
+
+/// The longest tolerance: with this, any time of day is within the tolerance of any target.
+const MAX_TOLERANCE_MINUTES: u32 = 12 * 60;
+
+const MAX_DURATION_MINUTES: u32 = 24 * 60;
+
+/// Check that a goal sets at least one target, and that the numbers make sense.
+fn validate_goal(
+    target_bedtime: Option<NaiveTime>,
+    target_wake_time: Option<NaiveTime>,
+    min_duration_minutes: Option<u32>,
+    tolerance_minutes: u32,
+) -> Result<(), ApiError> {
+    if target_bedtime.is_none() && target_wake_time.is_none() && min_duration_minutes.is_none() {
+        return Err(ApiError::BadRequest(
+            "a goal must set a target bedtime, a target wake time or a minimum duration"
+                .to_string(),
+        ));
+    }
+    if min_duration_minutes.is_some_and(|minutes| minutes == 0 || minutes > MAX_DURATION_MINUTES) {
+        return Err(ApiError::BadRequest(format!(
+            "minimum duration must be between 1 and {MAX_DURATION_MINUTES} minutes"
+        )));
+    }
+    if tolerance_minutes > MAX_TOLERANCE_MINUTES {
+        return Err(ApiError::BadRequest(format!(
+            "tolerance cannot be more than {MAX_TOLERANCE_MINUTES} minutes"
+        )));
+    }
+    Ok(())
+}
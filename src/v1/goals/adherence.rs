@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use api_types::v1::{
+    DateTimeUtc, SleepGoal, SleepGoalAdherence, SleepGoalAdherenceQuery, SleepGoalNight, SleepKind,
+    SleepState,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use sqlx::query_as;
+
+use crate::{
+    v1::{
+        settings::{LocalDay, SleepContext},
+        sleep::row::{load_states, SleepStateRow},
+        subjects::resolve_subject,
+        ApiError, ResultResponse,
+    },
+    AppState, RequireUser,
+};
+
+use super::{goal_for_night, load_goals, minute_of_day};
+
+/// The longest range that adherence can be requested for, in days.
+const MAX_RANGE_DAYS: i64 = 3660;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+pub async fn get_adherence(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(query): Query<SleepGoalAdherenceQuery>,
+) -> ResultResponse<Json<SleepGoalAdherence>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let local_day = &context.local_day;
+    let today = local_day.sleep_date(app_state.clock.now());
+
+    let goals = load_goals(&app_state.db, conn_user.id).await?;
+    let Some(first_goal) = goals.first() else {
+        return Ok(Json(SleepGoalAdherence {
+            nights: vec![],
+            current_streak: 0,
+            longest_streak: 0,
+        }));
+    };
+
+    // Nights that have not happened yet can neither be met nor missed
+    let from = query.from.unwrap_or(first_goal.effective_from);
+    let to = query.to.unwrap_or(today).min(today);
+    if query.from.is_some() && query.to.is_some() && from > to {
+        return Err(ApiError::BadRequest(
+            "`from` must not be after `to`".to_string(),
+        ))?;
+    }
+    if to - from > Duration::days(MAX_RANGE_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "cannot compute adherence for more than {MAX_RANGE_DAYS} days at once"
+        )))?;
+    }
+
+    let lower = local_day.lower_bound_utc(from).timestamp();
+    let upper = local_day.upper_bound_utc(to).timestamp();
+    // The goals are the user's own, so only their own sleep counts
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, None).await?;
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND started_at_unix_time>=? AND started_at_unix_time<?
+                AND ended_at_unix_time IS NOT NULL AND deleted_at_unix_time IS NULL
+            ORDER BY started_at_unix_time"#,
+        subject_id,
+        lower,
+        upper,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    let states = load_states(&app_state.db, &context, rows).await?;
+
+    // The main sleep of each night is the longest one, so that a short false start does not count
+    let mut main_sleeps: HashMap<NaiveDate, SleepState> = HashMap::new();
+    for state in states {
+        if state.kind == SleepKind::Nap {
+            continue;
+        }
+        let sleep_date = local_day.sleep_date(state.start);
+        match main_sleeps.get(&sleep_date) {
+            Some(longest) if longest.net_sleep_seconds >= state.net_sleep_seconds => {}
+            _ => {
+                main_sleeps.insert(sleep_date, state);
+            }
+        }
+    }
+
+    let mut nights = vec![];
+    let mut day = from;
+    while day <= to {
+        if let Some(goal) = goal_for_night(&goals, day) {
+            nights.push(evaluate_night(day, goal, main_sleeps.get(&day), local_day));
+        }
+        day += Duration::days(1);
+    }
+
+    let mut longest_streak = 0;
+    let mut streak = 0;
+    for night in &nights {
+        streak = if night.met { streak + 1 } else { 0 };
+        longest_streak = longest_streak.max(streak);
+    }
+    // Tonight may simply not be over yet
+    let unfinished_tonight = nights
+        .last()
+        .is_some_and(|night| night.sleep_date == today && night.sleep_state_id.is_none());
+    let current_streak = nights
+        .iter()
+        .rev()
+        .skip(unfinished_tonight as usize)
+        .take_while(|night| night.met)
+        .count() as u32;
+
+    Ok(Json(SleepGoalAdherence {
+        nights,
+        current_streak,
+        longest_streak,
+    }))
+}
+
+/// Compare the main sleep of a night to the goal that applies to it.
+fn evaluate_night(
+    sleep_date: NaiveDate,
+    goal: &SleepGoal,
+    sleep: Option<&SleepState>,
+    local_day: &LocalDay,
+) -> SleepGoalNight {
+    let tolerance = goal.tolerance_minutes as i64;
+    let time_met = |target: Option<NaiveTime>, actual: Option<DateTimeUtc>| {
+        let target = target?;
+        let actual = actual.map(|actual| local_day.local_time(actual).time());
+        Some(actual.is_some_and(|actual| {
+            // Compare on a 24-hour clock, so that 23:50 is close to a target of 00:10
+            let difference =
+                (minute_of_day(actual) - minute_of_day(target)).rem_euclid(MINUTES_PER_DAY);
+            difference.min(MINUTES_PER_DAY - difference) <= tolerance
+        }))
+    };
+
+    let bedtime_met = time_met(goal.target_bedtime, sleep.map(|sleep| sleep.start));
+    let wake_time_met = time_met(goal.target_wake_time, sleep.and_then(|sleep| sleep.end));
+    let duration_met = goal.min_duration_minutes.map(|minutes| {
+        sleep
+            .and_then(|sleep| sleep.net_sleep_seconds)
+            .is_some_and(|seconds| seconds >= minutes as i64 * 60)
+    });
+
+    SleepGoalNight {
+        sleep_date,
+        goal_id: goal.id,
+        sleep_state_id: sleep.map(|sleep| sleep.id),
+        bedtime_met,
+        wake_time_met,
+        duration_met,
+        met: [bedtime_met, wake_time_met, duration_met]
+            .into_iter()
+            .flatten()
+            .all(|met| met),
+    }
+}
@@ -0,0 +1,68 @@
+use api_types::{
+    v1::{NewSleepGoal, SleepGoal},
+    Snowflake,
+};
+use axum::{extract::State, http::StatusCode, Json};
+use sqlx::query;
+
+use crate::{
+    v1::{is_unique_violation, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{current_night, minute_of_day, time_from_minute, validate_goal};
+
+pub async fn create_goal(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_goal): Json<NewSleepGoal>,
+) -> ResultResponse<Result<(StatusCode, Json<SleepGoal>), StatusCode>> {
+    validate_goal(
+        new_goal.target_bedtime,
+        new_goal.target_wake_time,
+        new_goal.min_duration_minutes,
+        new_goal.tolerance_minutes,
+    )?;
+    if new_goal.effective_from < current_night(&app_state, conn_user.id).await? {
+        return Err(ApiError::BadRequest(
+            "a goal cannot take effect before the current night".to_string(),
+        ))?;
+    }
+
+    let id = Snowflake::new().await;
+    let effective_from = new_goal.effective_from.to_string();
+    let target_bedtime_minute = new_goal.target_bedtime.map(minute_of_day);
+    let target_wake_time_minute = new_goal.target_wake_time.map(minute_of_day);
+    let result = query!(
+        r#"INSERT INTO sleep_goal
+            (id, user_id, effective_from, target_bedtime_minute, target_wake_time_minute,
+                min_duration_minutes, tolerance_minutes)
+            VALUES (?,?,?,?,?,?,?)"#,
+        id,
+        conn_user.id,
+        effective_from,
+        target_bedtime_minute,
+        target_wake_time_minute,
+        new_goal.min_duration_minutes,
+        new_goal.tolerance_minutes,
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Ok(Err(StatusCode::CONFLICT)),
+        Err(err) => return Err(err)?,
+    }
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(SleepGoal {
+            id,
+            effective_from: new_goal.effective_from,
+            target_bedtime: target_bedtime_minute.map(time_from_minute),
+            target_wake_time: target_wake_time_minute.map(time_from_minute),
+            min_duration_minutes: new_goal.min_duration_minutes,
+            tolerance_minutes: new_goal.tolerance_minutes,
+        }),
+    )))
+}
@@ -0,0 +1,43 @@
+use api_types::Snowflake;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::current_night;
+
+/// Delete a goal that has not taken effect yet.
+/// A goal that applied to a past night stays, so that the adherence of that night does not change.
+pub async fn delete_goal(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let today = current_night(&app_state, conn_user.id).await?.to_string();
+    let mut tx = app_state.db.begin().await?;
+    let goal = query!(
+        "SELECT effective_from FROM sleep_goal WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    // The dates are written as YYYY-MM-DD, so they compare like the dates themselves
+    if goal.effective_from <= today {
+        return Err(ApiError::BadRequest(
+            "a goal that has already taken effect cannot be deleted".to_string(),
+        ))?;
+    }
+    query!("DELETE FROM sleep_goal WHERE id=?", id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,62 @@
+use api_types::{v1::SleepGoal, Snowflake};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{
+    v1::{settings::SleepContext, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::SleepGoalRow;
+
+pub async fn get_goal(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<SleepGoal>> {
+    let row = query_as!(
+        SleepGoalRow,
+        r#"SELECT id, effective_from, target_bedtime_minute, target_wake_time_minute,
+            min_duration_minutes, tolerance_minutes
+            FROM sleep_goal WHERE user_id=? AND id=?"#,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn get_current_goal(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<SleepGoal>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let today = context
+        .local_day
+        .sleep_date(app_state.clock.now())
+        .to_string();
+    let row = query_as!(
+        SleepGoalRow,
+        r#"SELECT id, effective_from, target_bedtime_minute, target_wake_time_minute,
+            min_duration_minutes, tolerance_minutes
+            FROM sleep_goal WHERE user_id=? AND effective_from<=?
+            ORDER BY effective_from DESC LIMIT 1"#,
+        conn_user.id,
+        today
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
@@ -0,0 +1,24 @@
+use api_types::v1::SleepGoal;
+use axum::{extract::State, Json};
+use sqlx::query_as;
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::SleepGoalRow;
+
+pub async fn list_goals(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<SleepGoal>>> {
+    let rows = query_as!(
+        SleepGoalRow,
+        r#"SELECT id, effective_from, target_bedtime_minute, target_wake_time_minute,
+            min_duration_minutes, tolerance_minutes
+            FROM sleep_goal WHERE user_id=? ORDER BY effective_from"#,
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(SleepGoalRow::into_api).collect()))
+}
@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use api_types::Snowflake;
+use axum::{
+    body::{boxed, Body, Full, HttpBody},
+    extract::State,
+    http::{header, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_client_ip::ClientIp;
+use chrono::Duration;
+use crypto::digest::fingerprint;
+use http_body::{LengthLimitError, Limited};
+use sqlx::{query, SqlitePool};
+
+use crate::{
+    clock::Clock,
+    v1::{ApiError, ResultResponse},
+    AppState, LoginState,
+};
+
+/// How long the response to a request is kept for retries, in hours.
+const RETENTION_HOURS: i64 = 24;
+
+/// How long a key stays claimed by a request that has not been answered, in seconds.
+/// After this, the request is taken to have been lost, like when the server stopped while handling it,
+/// and a retry is handled as a new request.
+const CLAIM_LEASE_SECONDS: i64 = 60;
+
+/// How often to delete the responses whose retention period is over.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// The longest idempotency key that is accepted.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// The largest request body that is read to fingerprint it.
+/// This is large enough for imports, and anything larger cannot be sent with an idempotency key.
+const MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// The largest response body that is stored for retries.
+/// Larger responses are returned as they are, without keeping the key.
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+/// Set on responses that were stored, instead of made for the request.
+const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Middleware that answers retries of a request with the response to the first try.
+///
+/// This applies to requests that change something and have an `Idempotency-Key` header.
+/// The key is scoped to the user, or to the client's IP address for requests without a user,
+/// and must not be reused for a different request:
+/// that returns 422, and retrying while the first try is still being handled returns 409,
+/// for as long as the lease of the first try on the key lasts.
+/// Responses with a server error are not stored, so that the request can be retried for real.
+pub async fn idempotent(
+    State(app_state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> ResultResponse<Response> {
+    if matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    ) {
+        return Ok(next.run(req).await);
+    }
+    let Some(key) = req.headers().get("Idempotency-Key") else {
+        return Ok(next.run(req).await);
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return Err(ApiError::BadRequest(format!(
+                "Idempotency-Key must be between 1 and {MAX_KEY_LENGTH} visible ASCII characters"
+            )))?
+        }
+    };
+    // Requests without a user all share the user ID 0,
+    // so their keys are scoped to the client to keep others from replaying their responses
+    let (user_id, key) = match req.extensions().get::<LoginState>() {
+        Some(LoginState::ValidToken((user, _token))) => (user.id, key),
+        _ => (Snowflake::from(0), format!("{ip} {key}")),
+    };
+
+    // The body has to be read to fingerprint it, and put back for the handler
+    let (parts, body) = req.into_parts();
+    let body = match hyper::body::to_bytes(Limited::new(body, MAX_REQUEST_BYTES)).await {
+        Ok(body) => body,
+        Err(err) if err.downcast_ref::<LengthLimitError>().is_some() => {
+            return Ok((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "requests with an Idempotency-Key can be at most {MAX_REQUEST_BYTES} bytes"
+                ),
+            )
+                .into_response());
+        }
+        Err(err) => Err(ApiError::BadRequest(format!(
+            "could not read the body: {err}"
+        )))?,
+    };
+    let request_fingerprint = fingerprint(&[
+        parts.method.as_str().as_bytes(),
+        parts.uri.to_string().as_bytes(),
+        &body,
+    ]);
+    let req = Request::from_parts(parts, Body::from(body));
+
+    let now = app_state.clock.now().timestamp();
+    let lease_cutoff = now - CLAIM_LEASE_SECONDS;
+    // A key whose request is still unanswered after the lease is claimed again,
+    // but only by the same request, so that a different one still gets a 422
+    let claimed = query!(
+        r#"INSERT INTO idempotency_key
+            (user_id, key, fingerprint, created_at_unix_time, claimed_at_unix_time)
+            VALUES (?1,?2,?3,?4,?4)
+            ON CONFLICT (user_id, key) DO UPDATE SET claimed_at_unix_time=excluded.claimed_at_unix_time
+            WHERE response_status IS NULL AND claimed_at_unix_time<=?5
+                AND fingerprint=excluded.fingerprint"#,
+        user_id,
+        key,
+        request_fingerprint,
+        now,
+        lease_cutoff,
+    )
+    .execute(&app_state.db)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return replay(&app_state.db, user_id, &key, &request_fingerprint).await;
+    }
+
+    // Finish handling the request even if the client goes away, so that the retry finds the response
+    let response = match tokio::spawn(next.run(req)).await {
+        Ok(response) => response,
+        Err(err) => {
+            forget(&app_state.db, user_id, &key).await?;
+            return Err(ApiError::UnexpectedError(format!(
+                "the request could not be handled: {err}"
+            )))?;
+        }
+    };
+    if response.status().is_server_error() {
+        forget(&app_state.db, user_id, &key).await?;
+        return Ok(response);
+    }
+    // Handlers that change something answer with small bodies of a known size,
+    // so this only lets through the odd response that could not be kept in memory
+    let is_storable = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_RESPONSE_BYTES);
+    if !is_storable {
+        tracing::warn!("Response for Idempotency-Key {key:?} is too large to store for retries");
+        forget(&app_state.db, user_id, &key).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(Limited::new(body, MAX_RESPONSE_BYTES as usize))
+        .await
+        .map_err(|err| {
+            ApiError::UnexpectedError(format!("could not read the response body: {err}"))
+        })?;
+    let status = parts.status.as_u16();
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok());
+    let stored_body = body.as_ref();
+    query!(
+        r#"UPDATE idempotency_key
+            SET response_status=?, response_content_type=?, response_body=?
+            WHERE user_id=? AND key=?"#,
+        status,
+        content_type,
+        stored_body,
+        user_id,
+        key,
+    )
+    .execute(&app_state.db)
+    .await?;
+    Ok(Response::from_parts(parts, boxed(Full::from(body))))
+}
+
+/// Answer a retry with the stored response, if it is a retry of the same request.
+async fn replay(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    key: &str,
+    request_fingerprint: &str,
+) -> ResultResponse<Response> {
+    let stored = query!(
+        r#"SELECT fingerprint, response_status, response_content_type, response_body
+            FROM idempotency_key WHERE user_id=? AND key=?"#,
+        user_id,
+        key,
+    )
+    .fetch_one(db)
+    .await?;
+    if stored.fingerprint != request_fingerprint {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "this Idempotency-Key was already used for a different request",
+        )
+            .into_response());
+    }
+    let Some(status) = stored.response_status else {
+        return Ok((
+            StatusCode::CONFLICT,
+            "a request with this Idempotency-Key is still being handled",
+        )
+            .into_response());
+    };
+
+    let mut response = Response::new(boxed(Full::from(stored.response_body.unwrap_or_default())));
+    *response.status_mut() = StatusCode::from_u16(status as u16).map_err(|err| {
+        ApiError::UnexpectedError(format!("stored an invalid status code: {err}"))
+    })?;
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .response_content_type
+        .and_then(|content_type| HeaderValue::from_str(&content_type).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+/// Delete a key whose request failed, so that it can be tried again.
+async fn forget(db: &SqlitePool, user_id: Snowflake, key: &str) -> Result<(), sqlx::Error> {
+    query!(
+        "DELETE FROM idempotency_key WHERE user_id=? AND key=?",
+        user_id,
+        key
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Deletes the stored responses once they are too old to be retried.
+pub struct IdempotencyKeyPurger {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+}
+
+impl IdempotencyKeyPurger {
+    pub fn new(db: SqlitePool, clock: Arc<dyn Clock>) -> Self {
+        Self { db, clock }
+    }
+
+    /// Purge every hour, in the background.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.purge().await {
+                    tracing::error!("Failed to purge idempotency keys: {err}");
+                }
+            }
+        });
+    }
+
+    /// Delete the keys whose retention period is over at the current time of the clock.
+    pub async fn purge(&self) -> Result<(), sqlx::Error> {
+        let cutoff = (self.clock.now() - Duration::hours(RETENTION_HOURS)).timestamp();
+        query!(
+            "DELETE FROM idempotency_key WHERE created_at_unix_time<=?",
+            cutoff
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
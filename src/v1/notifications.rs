@@ -0,0 +1,102 @@
+mod channel;
+mod inbox;
+mod reminders;
+mod schedule;
+mod scheduler;
+
+pub use scheduler::ReminderScheduler;
+
+use api_types::v1::{Notification, NotificationKind, ReminderChannel, ReminderSettings};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::{datetime_utc_from_timestamp, outbound::OutboundPolicy, v1::ApiError, AppState};
+
+use self::{
+    inbox::{delete_notification, list_notifications, mark_read},
+    reminders::{get_next_reminder, get_reminder_settings, put_reminder_settings},
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_notifications))
+        .route("/:id", axum::routing::delete(delete_notification))
+        .route("/:id/read", post(mark_read))
+        .route(
+            "/reminders",
+            get(get_reminder_settings).put(put_reminder_settings),
+        )
+        .route("/reminders/next", get(get_next_reminder))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Notification API\n",
+        "GET /list -- the notifications in your inbox, newest first (filter with ?unread_only=true)\n",
+        "DELETE /<id> -- delete notification by ID, or 404\n",
+        "POST /<id>/read -- mark notification as read, or 404\n",
+        "GET /reminders -- your bedtime reminder settings\n",
+        "PUT /reminders -- change your bedtime reminder settings (missing fields are reset to their defaults)\n",
+        "GET /reminders/next -- when the next bedtime reminder will be sent, based on the target bedtime of your goals\n",
+    )
+}
+
+/// A row of the `notification` table.
+#[derive(Debug, Clone)]
+pub struct NotificationRow {
+    pub id: i64,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub created_at_unix_time: i64,
+    pub read_at_unix_time: Option<i64>,
+}
+
+impl NotificationRow {
+    pub fn into_api(self) -> Notification {
+        Notification {
+            id: self.id.into(),
+            // The database has a CHECK constraint on this column, so parsing cannot fail
+            kind: self
+                .kind
+                .parse()
+                .unwrap_or(NotificationKind::BedtimeReminder),
+            title: self.title,
+            body: self.body,
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            read_at: self.read_at_unix_time.map(datetime_utc_from_timestamp),
+        }
+    }
+}
+
+/// The longest time before the target bedtime that a reminder can be sent.
+const MAX_MINUTES_BEFORE: u32 = 12 * 60;
+
+/// Check that the reminder settings can actually be used.
+fn validate_reminder_settings(
+    settings: &ReminderSettings,
+    outbound: &OutboundPolicy,
+) -> Result<(), ApiError> {
+    if settings.minutes_before > MAX_MINUTES_BEFORE {
+        return Err(ApiError::BadRequest(format!(
+            "reminders cannot be sent more than {MAX_MINUTES_BEFORE} minutes before bedtime"
+        )));
+    }
+    match &settings.webhook_url {
+        Some(url) => {
+            outbound
+                .check_url(url)
+                .map_err(|err| ApiError::BadRequest(format!("invalid webhook URL: {err}")))?;
+        }
+        None if settings.channel == ReminderChannel::Webhook => {
+            return Err(ApiError::BadRequest(
+                "a webhook URL is needed to send reminders by webhook".to_string(),
+            ));
+        }
+        None => {}
+    }
+    Ok(())
+}
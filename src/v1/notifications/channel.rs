@@ -0,0 +1,119 @@
+use api_types::{v1::Notification, Snowflake};
+use async_trait::async_trait;
+use sqlx::{query, SqlitePool};
+
+use crate::outbound::OutboundPolicy;
+
+/// Where a notification is delivered to.
+#[derive(Debug, Clone)]
+pub struct Recipient {
+    pub user_id: Snowflake,
+    pub email: String,
+    pub webhook_url: Option<String>,
+}
+
+/// A way of delivering notifications to users.
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    async fn deliver(
+        &self,
+        recipient: &Recipient,
+        notification: &Notification,
+    ) -> anyhow::Result<()>;
+}
+
+/// Sends notifications by email, to the address of the account.
+pub struct EmailChannel;
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    async fn deliver(
+        &self,
+        recipient: &Recipient,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let message = mail::templates::notification::make_notification_email(
+            recipient.email.parse()?,
+            &notification.title,
+            &notification.body,
+        );
+        mail::delivery::send_message(message).await?;
+        Ok(())
+    }
+}
+
+/// POSTs notifications as JSON to the user's webhook URL.
+pub struct WebhookChannel {
+    client: reqwest::Client,
+    outbound: OutboundPolicy,
+}
+
+impl WebhookChannel {
+    pub fn new(outbound: OutboundPolicy) -> Self {
+        Self {
+            client: outbound.client(std::time::Duration::from_secs(10)),
+            outbound,
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    async fn deliver(
+        &self,
+        recipient: &Recipient,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let url = recipient
+            .webhook_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("no webhook URL is set"))?;
+        // The URL was checked when it was set, but the policy may have become stricter since
+        let url = self.outbound.check_url(url).map_err(anyhow::Error::msg)?;
+        self.client
+            .post(url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Stores notifications in the user's inbox, for the client to poll.
+pub struct InboxChannel {
+    db: SqlitePool,
+}
+
+impl InboxChannel {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for InboxChannel {
+    async fn deliver(
+        &self,
+        recipient: &Recipient,
+        notification: &Notification,
+    ) -> anyhow::Result<()> {
+        let kind = notification.kind.to_string();
+        let created_at = notification.created_at.timestamp();
+        query!(
+            r#"INSERT INTO notification
+                (id, user_id, kind, title, body, created_at_unix_time, read_at_unix_time)
+                VALUES (?,?,?,?,?,?,?)"#,
+            notification.id,
+            recipient.user_id,
+            kind,
+            notification.title,
+            notification.body,
+            created_at,
+            Option::<i64>::None,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use api_types::{
+    v1::{Notification, NotificationListQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as};
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::NotificationRow;
+
+pub async fn list_notifications(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(filter): Query<NotificationListQuery>,
+) -> ResultResponse<Json<Vec<Notification>>> {
+    let rows = query_as!(
+        NotificationRow,
+        r#"SELECT id, kind, title, body, created_at_unix_time, read_at_unix_time
+            FROM notification
+            WHERE user_id=? AND (NOT ? OR read_at_unix_time IS NULL)
+            ORDER BY created_at_unix_time DESC"#,
+        conn_user.id,
+        filter.unread_only,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(
+        rows.into_iter().map(NotificationRow::into_api).collect(),
+    ))
+}
+
+pub async fn mark_read(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let now = app_state.clock.now().timestamp();
+    // Marking a notification as read again keeps the time it was first read
+    let row = query!(
+        r#"UPDATE notification
+            SET read_at_unix_time=COALESCE(read_at_unix_time, ?)
+            WHERE user_id=? AND id=?
+            RETURNING id"#,
+        now,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn delete_notification(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let row = query!(
+        "DELETE FROM notification WHERE user_id=? AND id=? RETURNING id",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
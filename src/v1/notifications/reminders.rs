@@ -0,0 +1,101 @@
+use api_types::{
+    v1::{NextReminder, ReminderChannel, ReminderSettings},
+    Snowflake,
+};
+use axum::{extract::State, Json};
+use sqlx::{query, SqlitePool};
+
+use crate::{
+    v1::{goals::load_goals, settings::SleepContext, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{schedule::next_reminder, validate_reminder_settings};
+
+/// Load the reminder settings of the given user.
+/// If the user has never changed them, the defaults are returned.
+async fn load_reminder_settings(
+    db: &SqlitePool,
+    user_id: Snowflake,
+) -> Result<ReminderSettings, sqlx::Error> {
+    let row = query!("SELECT * FROM reminder_settings WHERE user_id=?", user_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(match row {
+        Some(row) => ReminderSettings {
+            enabled: row.enabled != 0,
+            minutes_before: row.minutes_before as u32,
+            // The database has a CHECK constraint on this column, so parsing cannot fail
+            channel: row.channel.parse().unwrap_or(ReminderChannel::Inbox),
+            webhook_url: row.webhook_url,
+        },
+        None => ReminderSettings::default(),
+    })
+}
+
+pub async fn get_reminder_settings(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<ReminderSettings>> {
+    Ok(Json(
+        load_reminder_settings(&app_state.db, conn_user.id).await?,
+    ))
+}
+
+pub async fn put_reminder_settings(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_settings): Json<ReminderSettings>,
+) -> ResultResponse<Json<ReminderSettings>> {
+    validate_reminder_settings(&new_settings, &app_state.outbound)?;
+
+    let channel = new_settings.channel.to_string();
+    // Reminders that were already due are not sent again after changing the settings,
+    // so the time of the last handled reminder is kept
+    query!(
+        r#"INSERT INTO reminder_settings
+            (user_id, enabled, minutes_before, channel, webhook_url)
+            VALUES (?,?,?,?,?)
+            ON CONFLICT (user_id) DO UPDATE SET
+                enabled=excluded.enabled,
+                minutes_before=excluded.minutes_before,
+                channel=excluded.channel,
+                webhook_url=excluded.webhook_url"#,
+        conn_user.id,
+        new_settings.enabled,
+        new_settings.minutes_before,
+        channel,
+        new_settings.webhook_url,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok(Json(new_settings))
+}
+
+pub async fn get_next_reminder(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<NextReminder>> {
+    let settings = load_reminder_settings(&app_state.db, conn_user.id).await?;
+    if !settings.enabled {
+        return Ok(Json(NextReminder {
+            at: None,
+            bedtime: None,
+        }));
+    }
+
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let goals = load_goals(&app_state.db, conn_user.id).await?;
+    let reminder = next_reminder(
+        &goals,
+        &context.local_day,
+        settings.minutes_before,
+        app_state.clock.now(),
+    );
+    Ok(Json(NextReminder {
+        at: reminder.map(|reminder| reminder.at),
+        bedtime: reminder.map(|reminder| reminder.bedtime),
+    }))
+}
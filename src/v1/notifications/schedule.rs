@@ -0,0 +1,188 @@
+use api_types::v1::{DateTimeUtc, SleepGoal};
+use chrono::{Duration, Timelike};
+
+use crate::v1::{goals::goal_for_night, settings::LocalDay};
+
+/// How late a reminder may still be sent, if the server was down or busy at the time.
+const GRACE_PERIOD_MINUTES: i64 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reminder {
+    /// When the reminder is sent.
+    pub at: DateTimeUtc,
+
+    /// The target bedtime that it reminds of.
+    pub bedtime: DateTimeUtc,
+}
+
+/// The reminders for the nights around the given moment, in chronological order.
+///
+/// Each night gets a reminder if the goal that applies to it has a target bedtime.
+fn reminders_around(
+    goals: &[SleepGoal],
+    local_day: &LocalDay,
+    minutes_before: u32,
+    around: DateTimeUtc,
+) -> Vec<Reminder> {
+    let tonight = local_day.sleep_date(around);
+    let mut reminders: Vec<Reminder> = (-1..=2)
+        .filter_map(|offset| {
+            let night = tonight + Duration::days(offset);
+            let target = goal_for_night(goals, night)?.target_bedtime?;
+            // A bedtime after midnight is on the next calendar day
+            let date = if target.hour() < local_day.day_boundary_hour {
+                night + Duration::days(1)
+            } else {
+                night
+            };
+            let bedtime = local_day.instant_at(date.and_time(target));
+            Some(Reminder {
+                at: bedtime - Duration::minutes(minutes_before as i64),
+                bedtime,
+            })
+        })
+        .collect();
+    reminders.sort_by_key(|reminder| reminder.at);
+    reminders
+}
+
+/// The first reminder after `now`.
+pub fn next_reminder(
+    goals: &[SleepGoal],
+    local_day: &LocalDay,
+    minutes_before: u32,
+    now: DateTimeUtc,
+) -> Option<Reminder> {
+    reminders_around(goals, local_day, minutes_before, now)
+        .into_iter()
+        .find(|reminder| reminder.at > now)
+}
+
+/// The reminder that should be sent at `now`, if any.
+///
+/// This is the latest reminder that is not in the future, unless it was already handled
+/// or it is too late to send it.
+pub fn due_reminder(
+    goals: &[SleepGoal],
+    local_day: &LocalDay,
+    minutes_before: u32,
+    now: DateTimeUtc,
+    last_handled: Option<DateTimeUtc>,
+) -> Option<Reminder> {
+    reminders_around(goals, local_day, minutes_before, now)
+        .into_iter()
+        .rev()
+        .find(|reminder| reminder.at <= now)
+        .filter(|reminder| {
+            now - reminder.at <= Duration::minutes(GRACE_PERIOD_MINUTES)
+                && last_handled.is_none_or(|last_handled| reminder.at > last_handled)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use api_types::Snowflake;
+    use chrono::{NaiveDate, NaiveTime};
+    use chrono_tz::Tz;
+
+    use super::*;
+
+    fn goal(effective_from: &str, target_bedtime: &str) -> SleepGoal {
+        SleepGoal {
+            id: Snowflake::from(1),
+            effective_from: effective_from.parse::<NaiveDate>().unwrap(),
+            target_bedtime: Some(target_bedtime.parse::<NaiveTime>().unwrap()),
+            target_wake_time: None,
+            min_duration_minutes: None,
+            tolerance_minutes: 0,
+        }
+    }
+
+    fn local_day(tz: Tz) -> LocalDay {
+        LocalDay {
+            tz,
+            day_boundary_hour: 12,
+        }
+    }
+
+    fn utc(time: &str) -> DateTimeUtc {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn follows_the_wall_clock_across_dst() {
+        let goals = [goal("2023-03-01", "23:00:00")];
+        let local_day = local_day(chrono_tz::Europe::Berlin);
+
+        // Before the clocks go forward in the night to 2023-03-26, Berlin is at UTC+1
+        let before = next_reminder(&goals, &local_day, 30, utc("2023-03-25T12:00:00Z"));
+        assert_eq!(
+            before,
+            Some(Reminder {
+                at: utc("2023-03-25T21:30:00Z"),
+                bedtime: utc("2023-03-25T22:00:00Z"),
+            })
+        );
+
+        // The next night, 23:00 is at UTC+2
+        let after = next_reminder(&goals, &local_day, 30, utc("2023-03-25T21:30:00Z"));
+        assert_eq!(
+            after,
+            Some(Reminder {
+                at: utc("2023-03-26T20:30:00Z"),
+                bedtime: utc("2023-03-26T21:00:00Z"),
+            })
+        );
+    }
+
+    #[test]
+    fn bedtime_after_midnight_is_on_the_next_day() {
+        let goals = [goal("2023-01-01", "00:30:00")];
+        // Auckland is at UTC+13 in January, so its day starts while it is still the day before in UTC
+        let local_day = local_day(chrono_tz::Pacific::Auckland);
+
+        let reminder = next_reminder(&goals, &local_day, 30, utc("2023-01-10T10:00:00Z"));
+        assert_eq!(
+            reminder,
+            Some(Reminder {
+                at: utc("2023-01-10T11:00:00Z"),
+                bedtime: utc("2023-01-10T11:30:00Z"),
+            })
+        );
+    }
+
+    #[test]
+    fn no_reminder_before_the_first_goal() {
+        let goals = [goal("2023-09-10", "23:00:00")];
+        let local_day = local_day(chrono_tz::UTC);
+
+        let reminder = next_reminder(&goals, &local_day, 30, utc("2023-09-01T12:00:00Z"));
+        assert_eq!(reminder, None);
+    }
+
+    #[test]
+    fn due_reminder_is_sent_once_within_the_grace_period() {
+        let goals = [goal("2023-09-01", "23:00:00")];
+        let local_day = local_day(chrono_tz::America::New_York);
+        // 22:30 in New York, during daylight saving time
+        let at = utc("2023-09-02T02:30:00Z");
+
+        let before = due_reminder(&goals, &local_day, 30, at - Duration::minutes(1), None);
+        assert_eq!(before, None);
+
+        let due = due_reminder(&goals, &local_day, 30, at + Duration::minutes(5), None);
+        assert_eq!(due.map(|reminder| reminder.at), Some(at));
+
+        let handled = due_reminder(&goals, &local_day, 30, at + Duration::minutes(5), Some(at));
+        assert_eq!(handled, None);
+
+        let too_late = due_reminder(
+            &goals,
+            &local_day,
+            30,
+            at + Duration::minutes(GRACE_PERIOD_MINUTES + 1),
+            None,
+        );
+        assert_eq!(too_late, None);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{collections::HashMap, sync::Arc};
+
+use api_types::{
+    v1::{DateTimeUtc, Notification, NotificationKind, ReminderChannel},
+    Snowflake,
+};
+use sqlx::{query, SqlitePool};
+
+use crate::{
+    clock::Clock,
+    datetime_utc_from_timestamp,
+    outbound::OutboundPolicy,
+    v1::{
+        goals::load_goals,
+        settings::{load_user_settings, SleepContext},
+    },
+};
+
+use super::{
+    channel::{EmailChannel, InboxChannel, NotificationChannel, Recipient, WebhookChannel},
+    schedule::{due_reminder, Reminder},
+};
+
+/// How often to check for due reminders.
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Sends the bedtime reminders of all users when they are due.
+pub struct ReminderScheduler {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+    channels: HashMap<ReminderChannel, Box<dyn NotificationChannel>>,
+}
+
+impl ReminderScheduler {
+    pub fn new(db: SqlitePool, clock: Arc<dyn Clock>, outbound: OutboundPolicy) -> Self {
+        let mut channels: HashMap<ReminderChannel, Box<dyn NotificationChannel>> = HashMap::new();
+        channels.insert(ReminderChannel::Email, Box::new(EmailChannel));
+        channels.insert(
+            ReminderChannel::Webhook,
+            Box::new(WebhookChannel::new(outbound)),
+        );
+        channels.insert(
+            ReminderChannel::Inbox,
+            Box::new(InboxChannel::new(db.clone())),
+        );
+        Self {
+            db,
+            clock,
+            channels,
+        }
+    }
+
+    /// Check for due reminders every minute, in the background.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.tick().await {
+                    tracing::error!("Failed to check for due reminders: {err}");
+                }
+            }
+        });
+    }
+
+    /// Send all the reminders that are due at the current time of the clock.
+    pub async fn tick(&self) -> Result<(), sqlx::Error> {
+        let now = self.clock.now();
+        let rows = query!(
+            r#"SELECT reminder_settings.*, user.email
+                FROM reminder_settings JOIN user ON user.id=reminder_settings.user_id
+                WHERE enabled"#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        for row in rows {
+            // The database has a CHECK constraint on this column, so parsing cannot fail
+            let channel = row.channel.parse().unwrap_or(ReminderChannel::Inbox);
+            let recipient = Recipient {
+                user_id: row.user_id.into(),
+                email: row.email,
+                webhook_url: row.webhook_url,
+            };
+            let last_handled = row
+                .last_reminder_at_unix_time
+                .map(datetime_utc_from_timestamp);
+            let result = self
+                .remind_user(
+                    &recipient,
+                    channel,
+                    row.minutes_before as u32,
+                    last_handled,
+                    now,
+                )
+                .await;
+            // A failed reminder is retried on the next tick, until it is too late to send
+            if let Err(err) = result {
+                tracing::warn!(
+                    "Failed to send bedtime reminder to user {}: {err}",
+                    row.user_id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn remind_user(
+        &self,
+        recipient: &Recipient,
+        channel: ReminderChannel,
+        minutes_before: u32,
+        last_handled: Option<DateTimeUtc>,
+        now: DateTimeUtc,
+    ) -> anyhow::Result<()> {
+        let settings = load_user_settings(&self.db, recipient.user_id).await?;
+        let context = SleepContext::from_settings(settings)?;
+        let goals = load_goals(&self.db, recipient.user_id).await?;
+        let Some(reminder) = due_reminder(
+            &goals,
+            &context.local_day,
+            minutes_before,
+            now,
+            last_handled,
+        ) else {
+            return Ok(());
+        };
+
+        // Nobody needs to be told to go to bed while they are asleep.
+        // Only their own sleep counts, not that of the other subjects they track.
+        let open_sleep = query!(
+            r#"SELECT id FROM sleep_state
+                WHERE subject_id=(SELECT id FROM subject WHERE user_id=? AND is_self)
+                    AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#,
+            recipient.user_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        if open_sleep.is_none() {
+            let notification = make_reminder(&reminder, &context, now).await;
+            self.channels[&channel]
+                .deliver(recipient, &notification)
+                .await?;
+        }
+
+        let reminder_at = reminder.at.timestamp();
+        query!(
+            "UPDATE reminder_settings SET last_reminder_at_unix_time=? WHERE user_id=?",
+            reminder_at,
+            recipient.user_id
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
+
+async fn make_reminder(
+    reminder: &Reminder,
+    context: &SleepContext,
+    now: DateTimeUtc,
+) -> Notification {
+    let bedtime = context
+        .local_day
+        .local_time(reminder.bedtime)
+        .format("%H:%M");
+    Notification {
+        id: Snowflake::new().await,
+        kind: NotificationKind::BedtimeReminder,
+        title: "Time to wind down".to_string(),
+        body: format!("Your target bedtime is {bedtime}. Start getting ready for bed."),
+        created_at: now,
+        read_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        clock::SimulatedClock,
+        testing::{insert_user, test_db},
+    };
+
+    use super::*;
+
+    const USER_ID: i64 = 1;
+
+    /// A user whose goal is to go to bed at 23:00 in New York, with reminders in the inbox 30 minutes before.
+    async fn set_up(db: &SqlitePool) {
+        insert_user(db, USER_ID).await;
+        query!(
+            "INSERT INTO user_settings (user_id, timezone) VALUES (?, 'America/New_York')",
+            USER_ID
+        )
+        .execute(db)
+        .await
+        .unwrap();
+        query!(
+            r#"INSERT INTO sleep_goal (id, user_id, effective_from, target_bedtime_minute, tolerance_minutes)
+                VALUES (1, ?, '2023-09-01', 1380, 0)"#,
+            USER_ID
+        )
+        .execute(db)
+        .await
+        .unwrap();
+        query!(
+            r#"INSERT INTO reminder_settings (user_id, enabled, minutes_before, channel)
+                VALUES (?, 1, 30, 'inbox')"#,
+            USER_ID
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    async fn notification_count(db: &SqlitePool) -> i32 {
+        query!(
+            "SELECT COUNT(*) AS count FROM notification WHERE user_id=?",
+            USER_ID
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+        .count
+    }
+
+    async fn last_reminder_at(db: &SqlitePool) -> Option<i64> {
+        query!(
+            "SELECT last_reminder_at_unix_time FROM reminder_settings WHERE user_id=?",
+            USER_ID
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+        .last_reminder_at_unix_time
+    }
+
+    /// 22:30 on 2023-09-01 in New York, during daylight saving time.
+    fn reminder_at() -> DateTimeUtc {
+        "2023-09-02T02:30:00Z".parse().unwrap()
+    }
+
+    fn scheduler(db: &SqlitePool, clock: &SimulatedClock) -> ReminderScheduler {
+        ReminderScheduler::new(
+            db.clone(),
+            Arc::new(clock.clone()),
+            OutboundPolicy::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn sends_a_due_reminder_once() {
+        let db = test_db().await;
+        set_up(&db).await;
+        let clock = SimulatedClock::stopped_at(reminder_at() - Duration::minutes(5));
+        let scheduler = scheduler(&db, &clock);
+
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 0);
+
+        clock.advance(Duration::minutes(5));
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 1);
+        assert_eq!(last_reminder_at(&db).await, Some(reminder_at().timestamp()));
+
+        // Later ticks in the grace period do not send it again
+        clock.advance(Duration::minutes(1));
+        scheduler.tick().await.unwrap();
+        clock.advance(Duration::minutes(10));
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 1);
+
+        // The next night gets its own reminder
+        clock.advance(Duration::days(1) - Duration::minutes(11));
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 2);
+    }
+
+    #[tokio::test]
+    async fn skips_the_reminder_while_asleep() {
+        let db = test_db().await;
+        set_up(&db).await;
+        let started_at = (reminder_at() - Duration::hours(1)).timestamp();
+        query!(
+            r#"INSERT INTO sleep_state (id, user_id, subject_id, started_at_unix_time)
+                VALUES (1, ?, ?, ?)"#,
+            USER_ID,
+            USER_ID,
+            started_at,
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let clock = SimulatedClock::stopped_at(reminder_at());
+        let scheduler = scheduler(&db, &clock);
+
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 0);
+        // The reminder counts as handled, so it is not sent after waking up in the grace period
+        assert_eq!(last_reminder_at(&db).await, Some(reminder_at().timestamp()));
+
+        let ended_at = (reminder_at() + Duration::minutes(5)).timestamp();
+        query!(
+            "UPDATE sleep_state SET ended_at_unix_time=? WHERE id=1",
+            ended_at
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        clock.advance(Duration::minutes(10));
+        scheduler.tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn only_the_own_sleep_counts() {
+        let db = test_db().await;
+        set_up(&db).await;
+        query!(
+            r#"INSERT INTO subject (id, user_id, name, is_self, created_at_unix_time)
+                VALUES (2, ?, 'Baby', 0, 0)"#,
+            USER_ID
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let started_at = (reminder_at() - Duration::hours(1)).timestamp();
+        query!(
+            r#"INSERT INTO sleep_state (id, user_id, subject_id, started_at_unix_time)
+                VALUES (1, ?, 2, ?)"#,
+            USER_ID,
+            started_at,
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let clock = SimulatedClock::stopped_at(reminder_at());
+
+        scheduler(&db, &clock).tick().await.unwrap();
+        assert_eq!(notification_count(&db).await, 1);
+    }
+}
@@ -0,0 +1,20 @@
+mod context;
+mod get;
+mod kind_rules;
+mod local_day;
+mod update;
+
+pub use context::SleepContext;
+pub use get::load_user_settings;
+pub use kind_rules::KindRules;
+pub use local_day::LocalDay;
+
+use axum::{routing::get, Router};
+
+use crate::AppState;
+
+use self::{get::get_settings, update::put_settings};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new().route("/", get(get_settings).put(put_settings))
+}
@@ -0,0 +1,29 @@
+use api_types::{v1::UserSettings, Snowflake};
+use sqlx::SqlitePool;
+
+use crate::v1::{ApiError, ResultResponse};
+
+use super::{load_user_settings, KindRules, LocalDay};
+
+/// A user's settings, validated and prepared for interpreting their sleep data.
+#[derive(Debug, Clone)]
+pub struct SleepContext {
+    pub settings: UserSettings,
+    pub local_day: LocalDay,
+    pub kind_rules: KindRules,
+}
+
+impl SleepContext {
+    pub fn from_settings(settings: UserSettings) -> Result<Self, ApiError> {
+        Ok(Self {
+            local_day: LocalDay::from_settings(&settings)?,
+            kind_rules: KindRules::from_settings(&settings)?,
+            settings,
+        })
+    }
+
+    pub async fn load(db: &SqlitePool, user_id: Snowflake) -> ResultResponse<Self> {
+        let settings = load_user_settings(db, user_id).await?;
+        Ok(Self::from_settings(settings)?)
+    }
+}
@@ -0,0 +1,36 @@
+use api_types::{v1::UserSettings, Snowflake};
+use axum::{extract::State, Json};
+use sqlx::{query, SqlitePool};
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+/// Load the settings of the given user.
+/// If the user has never changed their settings, the defaults are returned.
+pub async fn load_user_settings(
+    db: &SqlitePool,
+    user_id: Snowflake,
+) -> Result<UserSettings, sqlx::Error> {
+    let row = query!("SELECT * FROM user_settings WHERE user_id=?", user_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(match row {
+        Some(row) => UserSettings {
+            timezone: row.timezone,
+            day_boundary_hour: row.day_boundary_hour as u8,
+            sleep_goal_minutes: row.sleep_goal_minutes as u32,
+            nap_max_minutes: row.nap_max_minutes as u32,
+            night_start_hour: row.night_start_hour as u8,
+            night_end_hour: row.night_end_hour as u8,
+            max_sleep_minutes: row.max_sleep_minutes as u32,
+        },
+        None => UserSettings::default(),
+    })
+}
+
+pub async fn get_settings(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<UserSettings>> {
+    Ok(Json(load_user_settings(&app_state.db, conn_user.id).await?))
+}
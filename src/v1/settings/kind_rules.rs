@@ -0,0 +1,59 @@
+use api_types::v1::{SleepKind, UserSettings};
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+use crate::v1::ApiError;
+
+/// The thresholds used for telling naps apart from main sleeps.
+#[derive(Debug, Clone, Copy)]
+pub struct KindRules {
+    pub nap_max: Duration,
+    pub night_start_hour: u32,
+    pub night_end_hour: u32,
+}
+
+impl KindRules {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ApiError> {
+        for (name, hour) in [
+            ("night start hour", settings.night_start_hour),
+            ("night end hour", settings.night_end_hour),
+        ] {
+            if hour > 23 {
+                return Err(ApiError::BadRequest(format!(
+                    "{name} must be between 0 and 23, not {hour}"
+                )));
+            }
+        }
+        Ok(Self {
+            nap_max: Duration::minutes(settings.nap_max_minutes as i64),
+            night_start_hour: settings.night_start_hour as u32,
+            night_end_hour: settings.night_end_hour as u32,
+        })
+    }
+
+    /// Whether the given local hour is part of the night.
+    /// The night may wrap around midnight; if it starts and ends at the same hour, it is empty.
+    pub fn is_night(&self, hour: u32) -> bool {
+        if self.night_start_hour <= self.night_end_hour {
+            (self.night_start_hour..self.night_end_hour).contains(&hour)
+        } else {
+            hour >= self.night_start_hour || hour < self.night_end_hour
+        }
+    }
+
+    /// Infer the kind of a sleep from when it started (in local time) and how long it lasted.
+    ///
+    /// Sleeps starting at night are main sleeps.
+    /// Other sleeps are naps if they are short enough, and main sleeps otherwise
+    /// (so that the day sleep of a night shift worker is still their main sleep).
+    /// If a sleep outside of the night is not over yet, its kind is unknown.
+    pub fn infer(&self, local_start: NaiveDateTime, duration: Option<Duration>) -> SleepKind {
+        if self.is_night(local_start.hour()) {
+            return SleepKind::Main;
+        }
+        match duration {
+            Some(duration) if duration <= self.nap_max => SleepKind::Nap,
+            Some(_) => SleepKind::Main,
+            None => SleepKind::Unknown,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use api_types::v1::{DateTimeUtc, UserSettings};
+use chrono::{Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+use crate::v1::ApiError;
+
+/// A user's view of local time: their timezone, and the hour at which their day starts.
+///
+/// Timezone data comes from the tz database bundled with `chrono-tz`,
+/// so DST transitions are handled without any network access.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalDay {
+    pub tz: Tz,
+    pub day_boundary_hour: u32,
+}
+
+impl LocalDay {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ApiError> {
+        let tz: Tz = settings.timezone.parse().map_err(|_| {
+            ApiError::BadRequest(format!("unknown timezone {:?}", settings.timezone))
+        })?;
+        if settings.day_boundary_hour > 23 {
+            return Err(ApiError::BadRequest(format!(
+                "day boundary hour must be between 0 and 23, not {}",
+                settings.day_boundary_hour
+            )));
+        }
+        Ok(Self {
+            tz,
+            day_boundary_hour: settings.day_boundary_hour as u32,
+        })
+    }
+
+    /// Convert a UTC instant to the user's wall clock time.
+    pub fn local_time(&self, instant: DateTimeUtc) -> NaiveDateTime {
+        instant.with_timezone(&self.tz).naive_local()
+    }
+
+    /// The local night that a sleep starting at the given instant is attributed to.
+    ///
+    /// This works on the wall clock time, so a night is attributed correctly
+    /// even if it crosses a DST transition.
+    pub fn sleep_date(&self, start: DateTimeUtc) -> NaiveDate {
+        let local = self.local_time(start);
+        if local.hour() < self.day_boundary_hour {
+            local.date() - Duration::days(1)
+        } else {
+            local.date()
+        }
+    }
+
+    /// The instant at which the given sleep date starts, that is, the day boundary hour on that date.
+    pub fn day_start_utc(&self, date: NaiveDate) -> DateTimeUtc {
+        self.instant_at(
+            date.and_hms_opt(self.day_boundary_hour, 0, 0)
+                .expect("day boundary hour is validated to be a valid hour"),
+        )
+    }
+
+    /// The instant at which the user's wall clock shows the given time.
+    ///
+    /// If that wall clock time is skipped by a DST transition, it is moved forward past the gap,
+    /// so the day boundary hour becomes the moment the clocks resume.
+    /// If it happens twice, this is the first occurrence.
+    pub fn instant_at(&self, mut local: NaiveDateTime) -> DateTimeUtc {
+        loop {
+            match self.tz.from_local_datetime(&local) {
+                LocalResult::Single(instant) | LocalResult::Ambiguous(instant, _) => {
+                    return instant.with_timezone(&Utc)
+                }
+                // Transitions always happen on a multiple of 15 minutes
+                LocalResult::None => local += Duration::minutes(15),
+            }
+        }
+    }
+
+    /// A UTC instant that is guaranteed to be no later than the start of the given sleep date.
+    ///
+    /// Useful for narrowing down database queries before filtering precisely with [`Self::sleep_date`].
+    pub fn lower_bound_utc(&self, date: NaiveDate) -> DateTimeUtc {
+        // Timezone offsets never exceed a day, so a two-day margin always suffices.
+        Utc.from_utc_datetime(&(date - Duration::days(2)).and_time(NaiveTime::MIN))
+    }
+
+    /// A UTC instant that is guaranteed to be after the end of the given sleep date.
+    pub fn upper_bound_utc(&self, date: NaiveDate) -> DateTimeUtc {
+        Utc.from_utc_datetime(&(date + Duration::days(3)).and_time(NaiveTime::MIN))
+    }
+}
@@ -0,0 +1,63 @@
+use api_types::v1::UserSettings;
+use axum::{extract::State, Json};
+use sqlx::query;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::SleepContext;
+
+pub async fn put_settings(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_settings): Json<UserSettings>,
+) -> ResultResponse<Json<UserSettings>> {
+    // Make sure that the settings can actually be used before saving them
+    SleepContext::from_settings(new_settings.clone())?;
+
+    if new_settings.sleep_goal_minutes > 24 * 60 {
+        return Err(ApiError::BadRequest(
+            "sleep goal cannot be longer than a day".to_string(),
+        ))?;
+    }
+
+    if new_settings.max_sleep_minutes < 60 || new_settings.max_sleep_minutes > 48 * 60 {
+        return Err(ApiError::BadRequest(
+            "maximum sleep length must be between 1 and 48 hours".to_string(),
+        ))?;
+    }
+
+    let day_boundary_hour = new_settings.day_boundary_hour as i64;
+    let sleep_goal_minutes = new_settings.sleep_goal_minutes as i64;
+    let nap_max_minutes = new_settings.nap_max_minutes as i64;
+    let night_start_hour = new_settings.night_start_hour as i64;
+    let night_end_hour = new_settings.night_end_hour as i64;
+    let max_sleep_minutes = new_settings.max_sleep_minutes as i64;
+    query!(
+        r#"INSERT INTO user_settings
+            (user_id, timezone, day_boundary_hour, sleep_goal_minutes, nap_max_minutes, night_start_hour, night_end_hour, max_sleep_minutes)
+            VALUES (?,?,?,?,?,?,?,?)
+            ON CONFLICT (user_id) DO UPDATE SET
+                timezone=excluded.timezone,
+                day_boundary_hour=excluded.day_boundary_hour,
+                sleep_goal_minutes=excluded.sleep_goal_minutes,
+                nap_max_minutes=excluded.nap_max_minutes,
+                night_start_hour=excluded.night_start_hour,
+                night_end_hour=excluded.night_end_hour,
+                max_sleep_minutes=excluded.max_sleep_minutes"#,
+        conn_user.id,
+        new_settings.timezone,
+        day_boundary_hour,
+        sleep_goal_minutes,
+        nap_max_minutes,
+        night_start_hour,
+        night_end_hour,
+        max_sleep_minutes,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok(Json(new_settings))
+}
@@ -0,0 +1,142 @@
+pub(super) mod access;
+mod granted;
+pub(super) mod links;
+mod received;
+
+use api_types::v1::{ShareAccess, SharedField, SleepShare, SleepShareSettings};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use chrono::NaiveDate;
+
+use crate::{datetime_utc_from_timestamp, v1::ApiError, AppState};
+
+use self::{
+    granted::{create_share, delete_share, get_share, list_shares, put_share},
+    links::{create_link, delete_link, get_link, list_links},
+    received::{accept_received, delete_received, get_received, list_received},
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/granted/list", get(list_shares))
+        .route("/granted/new", post(create_share))
+        .route(
+            "/granted/:id",
+            get(get_share).put(put_share).delete(delete_share),
+        )
+        .route("/received/list", get(list_received))
+        .route("/received/:id", get(get_received).delete(delete_received))
+        .route("/received/:id/accept", post(accept_received))
+        .route("/links/list", get(list_links))
+        .route("/links/new", post(create_link))
+        .route("/links/:id", get(get_link).delete(delete_link))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Sharing API\n",
+        "Sleep states can be shared with partners, caregivers or clinicians: invite their email address, and once they accept, they can pass ?user_id=<your id> to GET /v1/sleep/list, /v1/sleep/list/summary and /v1/sleep/stats (403 without a grant)\n",
+        "A read_write grant also lets them change your sleep states with PUT /v1/sleep/<id>?user_id=<your id>; a read grant can be limited to a range of sleep dates, and can hide the comment, check_in or tags of the sleep states\n",
+        "GET /granted/list -- the grants you gave, including pending invitations\n",
+        "POST /granted/new -- invite an email address to see your sleep states, or 409 if it is already invited\n",
+        "GET /granted/<id> -- get grant by ID\n",
+        "PUT /granted/<id> -- change what a grant allows (the email address cannot be changed)\n",
+        "DELETE /granted/<id> -- revoke a grant, or withdraw an invitation\n",
+        "GET /received/list -- the grants you received, including invitations to your email address that you have not accepted yet\n",
+        "GET /received/<id> -- get received grant by ID\n",
+        "POST /received/<id>/accept -- accept an invitation\n",
+        "DELETE /received/<id> -- decline an invitation, or give up a grant you no longer need\n",
+        "Public links show a fixed report of your sleep states to anyone who has them, without an account, like a doctor: GET /v1/sleep/report/<token> for JSON, or /v1/sleep/report/<token>/csv for CSV\n",
+        "GET /links/list -- the public links you created, with how often they were opened, including expired ones\n",
+        "POST /links/new -- create a public link to the sleep dates from_date to to_date, which expires at expires_at (at most 90 days from now), optionally hiding the comment, check_in or tags of the sleep states; the token is only returned here\n",
+        "GET /links/<id> -- get public link by ID\n",
+        "DELETE /links/<id> -- revoke a public link\n",
+    )
+}
+
+/// A row of the `sleep_share` table.
+#[derive(Debug, Clone)]
+pub struct ShareRow {
+    pub id: i64,
+    pub invited_email: String,
+    pub grantee_user_id: Option<i64>,
+    pub access: String,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub hidden_fields: String,
+    pub created_at_unix_time: i64,
+    pub accepted_at_unix_time: Option<i64>,
+}
+
+impl ShareRow {
+    pub fn into_api(self) -> SleepShare {
+        SleepShare {
+            id: self.id.into(),
+            grantee_id: self.grantee_user_id.map(Into::into),
+            settings: parse_settings(
+                &self.access,
+                self.from_date.as_deref(),
+                self.to_date.as_deref(),
+                &self.hidden_fields,
+            ),
+            email: self.invited_email,
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            accepted_at: self.accepted_at_unix_time.map(datetime_utc_from_timestamp),
+        }
+    }
+}
+
+/// The settings of a grant from the columns of its row.
+fn parse_settings(
+    access: &str,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    hidden_fields: &str,
+) -> SleepShareSettings {
+    // The database has a CHECK constraint on the access, and the dates are written by us,
+    // so the conversions cannot fail
+    SleepShareSettings {
+        access: access.parse().unwrap_or(ShareAccess::Read),
+        from_date: from_date.and_then(|date| date.parse::<NaiveDate>().ok()),
+        to_date: to_date.and_then(|date| date.parse::<NaiveDate>().ok()),
+        hidden_fields: parse_hidden_fields(hidden_fields),
+    }
+}
+
+/// The hidden fields as stored in the database, like `comment,check_in`.
+fn format_hidden_fields(fields: &[SharedField]) -> String {
+    let names: Vec<String> = fields.iter().map(ToString::to_string).collect();
+    names.join(",")
+}
+
+fn parse_hidden_fields(fields: &str) -> Vec<SharedField> {
+    fields
+        .split(',')
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// Check that the settings of a grant make sense.
+///
+/// A grantee who can change sleep states has to see all of them, or they would overwrite what they cannot see.
+fn validate_settings(settings: &SleepShareSettings) -> Result<(), ApiError> {
+    if let (Some(from), Some(to)) = (settings.from_date, settings.to_date) {
+        if from > to {
+            return Err(ApiError::BadRequest(
+                "`from_date` must not be after `to_date`".to_string(),
+            ));
+        }
+    }
+    let is_limited = settings.from_date.is_some()
+        || settings.to_date.is_some()
+        || !settings.hidden_fields.is_empty();
+    if settings.access == ShareAccess::ReadWrite && is_limited {
+        return Err(ApiError::BadRequest(
+            "a read_write grant cannot be limited to some sleep dates or hide fields".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use api_types::{
+    v1::{ShareAccess, SharedField, SleepCheckIn, SleepState, SleepStateListQuery, TagFilter},
+    Snowflake,
+};
+use chrono::NaiveDate;
+use sqlx::{query, SqlitePool};
+
+use crate::v1::ApiError;
+
+use super::parse_settings;
+
+/// Whose sleep states a request is about, and what of them the connected user may see.
+#[derive(Debug, Clone)]
+pub struct SleepAccess {
+    pub owner_id: Snowflake,
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+    hidden_fields: Vec<SharedField>,
+}
+
+impl SleepAccess {
+    /// Access to the owner's sleep states with these sleep dates, without the hidden fields.
+    pub fn limited(
+        owner_id: Snowflake,
+        from_date: Option<NaiveDate>,
+        to_date: Option<NaiveDate>,
+        hidden_fields: Vec<SharedField>,
+    ) -> Self {
+        Self {
+            owner_id,
+            from_date,
+            to_date,
+            hidden_fields,
+        }
+    }
+
+    /// Whether a sleep state with this sleep date can be seen.
+    pub fn includes(&self, date: NaiveDate) -> bool {
+        self.from_date.is_none_or(|from| date >= from) && self.to_date.is_none_or(|to| date <= to)
+    }
+
+    fn hides(&self, field: SharedField) -> bool {
+        self.hidden_fields.contains(&field)
+    }
+
+    /// Make sure that a filter by tags does not reveal tags that are hidden.
+    pub fn check_tag_filter(&self, filter: &TagFilter<'_>) -> Result<(), ApiError> {
+        let filters_tags = filter.any.is_some() || filter.all.is_some() || filter.none.is_some();
+        if filters_tags && self.hides(SharedField::Tags) {
+            return Err(ApiError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Narrow a filter down to the sleep dates that can be seen,
+    /// and make sure that it does not reveal anything that is hidden.
+    pub fn restrict_filter(&self, filter: &mut SleepStateListQuery) -> Result<(), ApiError> {
+        self.check_tag_filter(&filter.tag_filter())?;
+        let filters_check_in = filter.min_quality.is_some()
+            || filter.max_quality.is_some()
+            || filter.min_restedness.is_some()
+            || filter.max_restedness.is_some()
+            || filter.dream_recall.is_some();
+        if filters_check_in && self.hides(SharedField::CheckIn) {
+            return Err(ApiError::Forbidden);
+        }
+
+        filter.from_date = match (filter.from_date, self.from_date) {
+            (Some(asked), Some(allowed)) => Some(asked.max(allowed)),
+            (asked, allowed) => asked.or(allowed),
+        };
+        filter.to_date = match (filter.to_date, self.to_date) {
+            (Some(asked), Some(allowed)) => Some(asked.min(allowed)),
+            (asked, allowed) => asked.or(allowed),
+        };
+        Ok(())
+    }
+
+    /// Leave out the fields of a sleep state that are hidden.
+    pub fn redact(&self, mut state: SleepState) -> SleepState {
+        if self.hides(SharedField::Comment) {
+            state.comment = None;
+        }
+        if self.hides(SharedField::CheckIn) {
+            state.check_in = SleepCheckIn::default();
+        }
+        if self.hides(SharedField::Tags) {
+            state.tags = vec![];
+        }
+        state
+    }
+}
+
+/// Find out whose sleep states a request is about: the user's own by default,
+/// or those of `requested_user_id` if they shared them with the user.
+///
+/// Fails with [`ApiError::Forbidden`] if there is no accepted grant that allows `needed`.
+pub async fn resolve_access(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    requested_user_id: Option<Snowflake>,
+    needed: ShareAccess,
+) -> Result<SleepAccess, ApiError> {
+    let owner_id = match requested_user_id {
+        Some(owner_id) if owner_id != user_id => owner_id,
+        _ => {
+            return Ok(SleepAccess {
+                owner_id: user_id,
+                from_date: None,
+                to_date: None,
+                hidden_fields: vec![],
+            })
+        }
+    };
+
+    let grant = query!(
+        r#"SELECT access, from_date, to_date, hidden_fields FROM sleep_share
+            WHERE user_id=? AND grantee_user_id=?"#,
+        owner_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::Forbidden)?;
+    let settings = parse_settings(
+        &grant.access,
+        grant.from_date.as_deref(),
+        grant.to_date.as_deref(),
+        &grant.hidden_fields,
+    );
+    if needed == ShareAccess::ReadWrite && settings.access != ShareAccess::ReadWrite {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(SleepAccess::limited(
+        owner_id,
+        settings.from_date,
+        settings.to_date,
+        settings.hidden_fields,
+    ))
+}
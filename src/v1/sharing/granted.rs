@@ -0,0 +1,178 @@
+use api_types::{
+    v1::{SleepShare, SleepShareInvitation, SleepShareSettings},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    v1::{is_unique_violation, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{format_hidden_fields, validate_settings, ShareRow};
+
+/// Find a grant by ID, making sure that it was given by the user.
+async fn find_share(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<ShareRow, ApiError> {
+    query_as!(
+        ShareRow,
+        r#"SELECT id, invited_email, grantee_user_id, access, from_date, to_date, hidden_fields,
+            created_at_unix_time, accepted_at_unix_time
+            FROM sleep_share WHERE user_id=? AND id=?"#,
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+pub async fn list_shares(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<SleepShare>>> {
+    let rows = query_as!(
+        ShareRow,
+        r#"SELECT id, invited_email, grantee_user_id, access, from_date, to_date, hidden_fields,
+            created_at_unix_time, accepted_at_unix_time
+            FROM sleep_share WHERE user_id=? ORDER BY created_at_unix_time"#,
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(rows.into_iter().map(ShareRow::into_api).collect()))
+}
+
+/// Invite an email address to see the user's sleep states, and tell its owner by email.
+pub async fn create_share(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(invitation): Json<SleepShareInvitation>,
+) -> ResultResponse<Result<(StatusCode, Json<SleepShare>), StatusCode>> {
+    let settings = invitation.settings;
+    validate_settings(&settings)?;
+    let email = invitation.email.to_string();
+    if email.eq_ignore_ascii_case(&conn_user.email) {
+        return Err(ApiError::BadRequest(
+            "you cannot share your sleep states with yourself".to_string(),
+        ))?;
+    }
+
+    let id = Snowflake::new().await;
+    let access = settings.access.to_string();
+    let from_date = settings.from_date.map(|date| date.to_string());
+    let to_date = settings.to_date.map(|date| date.to_string());
+    let hidden_fields = format_hidden_fields(&settings.hidden_fields);
+    let created = app_state.clock.now();
+    let created_timestamp = created.timestamp();
+    let result = query!(
+        r#"INSERT INTO sleep_share
+            (id, user_id, invited_email, access, from_date, to_date, hidden_fields, created_at_unix_time)
+            VALUES (?,?,?,?,?,?,?,?)"#,
+        id,
+        conn_user.id,
+        email,
+        access,
+        from_date,
+        to_date,
+        hidden_fields,
+        created_timestamp,
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Ok(Err(StatusCode::CONFLICT)),
+        Err(err) => return Err(err)?,
+    }
+
+    // The invitation is listed for the invitee anyway, so failing to send the email is not fatal
+    let message = mail::templates::sharing::make_sleep_share_invitation_email(
+        invitation.email,
+        &conn_user.username,
+        &format!("/v1/sharing/received/{id}/accept"),
+    );
+    if let Err(err) = mail::delivery::send_message(message).await {
+        tracing::warn!("Failed to send the invitation email for sleep share {id}: {err}");
+    }
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(SleepShare {
+            id,
+            email,
+            grantee_id: None,
+            settings,
+            created_at: created,
+            accepted_at: None,
+        }),
+    )))
+}
+
+pub async fn get_share(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<SleepShare>> {
+    let row = find_share(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Change what a grant allows. This takes effect right away, also for a grant that was accepted.
+pub async fn put_share(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+    Json(settings): Json<SleepShareSettings>,
+) -> ResultResponse<Json<SleepShare>> {
+    validate_settings(&settings)?;
+
+    let access = settings.access.to_string();
+    let from_date = settings.from_date.map(|date| date.to_string());
+    let to_date = settings.to_date.map(|date| date.to_string());
+    let hidden_fields = format_hidden_fields(&settings.hidden_fields);
+    let result = query!(
+        r#"UPDATE sleep_share SET access=?, from_date=?, to_date=?, hidden_fields=?
+            WHERE user_id=? AND id=?"#,
+        access,
+        from_date,
+        to_date,
+        hidden_fields,
+        conn_user.id,
+        id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    let row = find_share(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Revoke a grant, or withdraw an invitation that was not accepted yet.
+pub async fn delete_share(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let result = query!(
+        "DELETE FROM sleep_share WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
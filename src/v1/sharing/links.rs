@@ -0,0 +1,189 @@
+use api_types::{
+    v1::{NewShareLink, ShareLink},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, NaiveDate};
+use crypto::token::generate_token;
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{format_hidden_fields, parse_hidden_fields};
+
+/// A link cannot be made to work for longer than this, so that forgotten links do not stay open forever.
+const MAX_EXPIRY_DAYS: i64 = 90;
+
+/// A row of the `share_link` table, without the token, which is only returned when the link is created.
+#[derive(Debug, Clone)]
+pub struct LinkRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub subject_id: i64,
+    pub from_date: String,
+    pub to_date: String,
+    pub hidden_fields: String,
+    pub created_at_unix_time: i64,
+    pub expires_at_unix_time: i64,
+    pub access_count: i64,
+    pub last_accessed_at_unix_time: Option<i64>,
+}
+
+impl LinkRow {
+    pub fn into_api(self) -> ShareLink {
+        ShareLink {
+            id: self.id.into(),
+            token: None,
+            subject_id: self.subject_id.into(),
+            // The dates are written by us, so they can always be read back
+            from_date: self.from_date.parse().unwrap_or(NaiveDate::MIN),
+            to_date: self.to_date.parse().unwrap_or(NaiveDate::MAX),
+            hidden_fields: parse_hidden_fields(&self.hidden_fields),
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            expires_at: datetime_utc_from_timestamp(self.expires_at_unix_time),
+            access_count: self.access_count as u32,
+            last_accessed_at: self
+                .last_accessed_at_unix_time
+                .map(datetime_utc_from_timestamp),
+        }
+    }
+}
+
+/// Find a link by ID, making sure that it was created by the user.
+async fn find_link(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<LinkRow, ApiError> {
+    query_as!(
+        LinkRow,
+        r#"SELECT id, user_id, subject_id, from_date, to_date, hidden_fields, created_at_unix_time,
+            expires_at_unix_time, access_count, last_accessed_at_unix_time
+            FROM share_link WHERE user_id=? AND id=?"#,
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// The user's links, including the ones that expired, so that their access counts can still be seen.
+pub async fn list_links(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<ShareLink>>> {
+    let rows = query_as!(
+        LinkRow,
+        r#"SELECT id, user_id, subject_id, from_date, to_date, hidden_fields, created_at_unix_time,
+            expires_at_unix_time, access_count, last_accessed_at_unix_time
+            FROM share_link WHERE user_id=? ORDER BY created_at_unix_time"#,
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(rows.into_iter().map(LinkRow::into_api).collect()))
+}
+
+/// Create a public link to a report of the user's sleep states.
+/// The token itself is only returned here, so it cannot be read back later.
+pub async fn create_link(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_link): Json<NewShareLink>,
+) -> ResultResponse<(StatusCode, Json<ShareLink>)> {
+    if new_link.from_date > new_link.to_date {
+        return Err(ApiError::BadRequest(
+            "`from_date` must not be after `to_date`".to_string(),
+        ))?;
+    }
+    let created = app_state.clock.now();
+    if new_link.expires_at <= created {
+        return Err(ApiError::BadRequest(
+            "`expires_at` must be in the future".to_string(),
+        ))?;
+    }
+    if new_link.expires_at - created > Duration::days(MAX_EXPIRY_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "`expires_at` must be at most {MAX_EXPIRY_DAYS} days from now"
+        )))?;
+    }
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, new_link.subject_id).await?;
+
+    const TOKEN_LENGTH: u16 = 32;
+    let token = generate_token(TOKEN_LENGTH);
+    let id = Snowflake::new().await;
+    let from_date = new_link.from_date.to_string();
+    let to_date = new_link.to_date.to_string();
+    let hidden_fields = format_hidden_fields(&new_link.hidden_fields);
+    let created_timestamp = created.timestamp();
+    let expires_timestamp = new_link.expires_at.timestamp();
+    query!(
+        r#"INSERT INTO share_link
+            (id, token, user_id, subject_id, from_date, to_date, hidden_fields, created_at_unix_time, expires_at_unix_time)
+            VALUES (?,?,?,?,?,?,?,?,?)"#,
+        id,
+        token,
+        conn_user.id,
+        subject_id,
+        from_date,
+        to_date,
+        hidden_fields,
+        created_timestamp,
+        expires_timestamp,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ShareLink {
+            id,
+            token: Some(token),
+            subject_id,
+            from_date: new_link.from_date,
+            to_date: new_link.to_date,
+            hidden_fields: new_link.hidden_fields,
+            created_at: created,
+            expires_at: datetime_utc_from_timestamp(expires_timestamp),
+            access_count: 0,
+            last_accessed_at: None,
+        }),
+    ))
+}
+
+pub async fn get_link(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<ShareLink>> {
+    let row = find_link(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Revoke a link, so that the report cannot be opened with it anymore.
+pub async fn delete_link(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let result = query!(
+        "DELETE FROM share_link WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
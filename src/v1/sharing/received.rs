@@ -0,0 +1,153 @@
+use api_types::{v1::ReceivedSleepShare, Snowflake};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::parse_settings;
+
+// A grant is received by a user once they accepted it, and before that by the user who has the email address
+// it was sent to. The queries below spell this out, since the query macros cannot share SQL.
+
+/// A grant joined with the name of the user who gave it.
+struct ReceivedShareRow {
+    id: i64,
+    user_id: i64,
+    owner_username: String,
+    access: String,
+    from_date: Option<String>,
+    to_date: Option<String>,
+    hidden_fields: String,
+    created_at_unix_time: i64,
+    accepted_at_unix_time: Option<i64>,
+}
+
+impl ReceivedShareRow {
+    fn into_api(self) -> ReceivedSleepShare {
+        ReceivedSleepShare {
+            id: self.id.into(),
+            owner_id: self.user_id.into(),
+            owner_username: self.owner_username,
+            settings: parse_settings(
+                &self.access,
+                self.from_date.as_deref(),
+                self.to_date.as_deref(),
+                &self.hidden_fields,
+            ),
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            accepted_at: self.accepted_at_unix_time.map(datetime_utc_from_timestamp),
+        }
+    }
+}
+
+/// Find a grant by ID, making sure that it was given to the user.
+async fn find_received(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<ReceivedShareRow, ApiError> {
+    query_as!(
+        ReceivedShareRow,
+        r#"SELECT sleep_share.id, sleep_share.user_id, user.username AS owner_username,
+                access, from_date, to_date, hidden_fields, created_at_unix_time, accepted_at_unix_time
+            FROM sleep_share INNER JOIN user ON user.id = sleep_share.user_id
+            WHERE sleep_share.id=? AND (grantee_user_id=? OR (grantee_user_id IS NULL
+                AND invited_email=(SELECT email FROM user WHERE id=?)))"#,
+        id,
+        user_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+pub async fn list_received(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<ReceivedSleepShare>>> {
+    let rows = query_as!(
+        ReceivedShareRow,
+        r#"SELECT sleep_share.id, sleep_share.user_id, user.username AS owner_username,
+                access, from_date, to_date, hidden_fields, created_at_unix_time, accepted_at_unix_time
+            FROM sleep_share INNER JOIN user ON user.id = sleep_share.user_id
+            WHERE grantee_user_id=? OR (grantee_user_id IS NULL
+                AND invited_email=(SELECT email FROM user WHERE id=?))
+            ORDER BY created_at_unix_time"#,
+        conn_user.id,
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(ReceivedShareRow::into_api).collect(),
+    ))
+}
+
+pub async fn get_received(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<ReceivedSleepShare>> {
+    let row = find_received(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Accept an invitation, so that the grant is tied to the user even if their email address changes.
+///
+/// Accepting a grant again does nothing.
+pub async fn accept_received(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<ReceivedSleepShare>> {
+    let now = app_state.clock.now().timestamp();
+    let result = query!(
+        r#"UPDATE sleep_share
+            SET grantee_user_id=?, accepted_at_unix_time=COALESCE(accepted_at_unix_time, ?)
+            WHERE id=? AND (grantee_user_id=? OR (grantee_user_id IS NULL
+                AND invited_email=(SELECT email FROM user WHERE id=?)))"#,
+        conn_user.id,
+        now,
+        id,
+        conn_user.id,
+        conn_user.id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    let row = find_received(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Decline an invitation, or give up a grant. The owner can invite the user again afterwards.
+pub async fn delete_received(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    let result = query!(
+        r#"DELETE FROM sleep_share
+            WHERE id=? AND (grantee_user_id=? OR (grantee_user_id IS NULL
+                AND invited_email=(SELECT email FROM user WHERE id=?)))"#,
+        id,
+        conn_user.id,
+        conn_user.id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,28 +1,82 @@
+mod analysis;
+mod auto_close;
+mod batch;
+mod bus;
+mod check_in;
 mod create;
 mod delete;
+mod export;
+mod feed;
 mod get;
+mod history;
+mod import;
+mod interruptions;
 mod list;
+mod report;
+pub(super) mod row;
+mod stats;
+mod stream;
+mod summary;
+mod sync;
+mod tags;
+mod trash;
 mod update;
 
+pub use auto_close::StaleSleepSweeper;
+pub use bus::{SleepChange, SleepEventBus};
+pub use stream::stream_sleep_events;
+pub use trash::TrashPurger;
+
 use axum::{
-    routing::{get, post},
+    middleware::from_fn_with_state,
+    routing::{get, post, put},
     Router,
 };
 
-use crate::AppState;
+use crate::{v1::idempotency::idempotent, AppState};
 
 use self::{
+    analysis::analyze,
+    batch::apply_batch,
     create::create_now,
     delete::{delete_by_id, delete_current},
+    export::{export_csv, export_ndjson},
+    feed::get_feed,
     get::{get_by_id, get_current},
+    history::{get_history, revert_to_revision},
+    import::import_sleep,
+    interruptions::{
+        create_interruption, delete_current_interruption, delete_interruption,
+        end_current_interruption, get_current_interruption, get_interruption,
+        list_current_interruptions, list_interruptions, put_interruption, wake_up_now,
+    },
     list::list_states,
+    report::{get_report, get_report_csv},
+    stats::get_stats,
+    summary::summarize_states,
+    sync::{get_changes, upload_changes},
+    tags::{attach_tag, detach_tag, list_sleep_tags},
+    trash::{list_trash, restore_by_id},
     update::{put_by_id, set_current_end, set_current_start},
 };
 
-pub fn get_router() -> Router<AppState> {
+pub fn get_router(app_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/", get(root))
         .route("/list", get(list_states))
+        .route("/list/summary", get(summarize_states))
+        .route("/stats", get(get_stats))
+        .route("/analysis", get(analyze))
+        .route("/batch", post(apply_batch))
+        .route("/export/csv", get(export_csv))
+        .route("/export/ndjson", get(export_ndjson))
+        .route("/feed/:token", get(get_feed))
+        .route("/report/:token", get(get_report))
+        .route("/report/:token/csv", get(get_report_csv))
+        .route("/changes", get(get_changes).post(upload_changes))
+        .route("/import", post(import_sleep))
+        .route("/trash", get(list_trash))
+        .route("/trash/:id/restore", post(restore_by_id))
         .route("/:id", get(get_by_id).delete(delete_by_id).put(put_by_id))
         .route("/new", post(create_now))
         .route(
@@ -32,19 +86,73 @@ pub fn get_router() -> Router<AppState> {
                 .put(set_current_start)
                 .delete(delete_current),
         )
+        .route(
+            "/:id/interruptions",
+            get(list_interruptions).post(create_interruption),
+        )
+        .route(
+            "/:id/interruptions/:interruption_id",
+            get(get_interruption)
+                .put(put_interruption)
+                .delete(delete_interruption),
+        )
+        .route("/:id/history", get(get_history))
+        .route("/:id/history/:revision_id/revert", post(revert_to_revision))
+        .route("/:id/tags", get(list_sleep_tags))
+        .route("/:id/tags/:tag_id", put(attach_tag).delete(detach_tag))
+        .route("/@current/interruptions", get(list_current_interruptions))
+        .route("/@current/interruptions/new", post(wake_up_now))
+        .route(
+            "/@current/interruptions/@current",
+            get(get_current_interruption)
+                .post(end_current_interruption)
+                .delete(delete_current_interruption),
+        )
+        // Retried requests that change something get the response to the first try
+        .route_layer(from_fn_with_state(app_state.clone(), idempotent))
 }
 
 async fn root() -> &'static str {
     concat!(
         "Sleep state API\n",
-        "GET /list -- list of all sleep states you have\n",
+        "Sleep states belong to a subject (see /v1/subjects): every request works on your own by default, add ?subject_id=<id> for another one; there can be one current sleep state per subject\n",
+        "Requests that change something may have an Idempotency-Key header: retrying with the same key within 24 hours returns the response to the first try instead of doing it again (422 if the key was used for a different request, 409 if the first try is still being handled)\n",
+        "GET /list -- list of all sleep states you have (filter with ?from_date=YYYY-MM-DD&to_date=YYYY-MM-DD&kind=main|nap|unknown&min_quality=1..5&max_quality=1..5&min_restedness=1..5&max_restedness=1..5&dream_recall=none|vague|vivid&tags_any=<ids>&tags_all=<ids>&tags_none=<ids>, where <ids> are comma-separated tag IDs; add &user_id=<id> for the sleep states another user shared with you)\n",
+        "GET /list/summary -- averages of the check-ins of the sleep states matching the same filters as /list\n",
+        "GET /stats?from=YYYY-MM-DD&to=YYYY-MM-DD&granularity=day|week|month&kind=main|nap|unknown&tags_any=<ids>&tags_all=<ids>&tags_none=<ids>&user_id=<id> -- statistics of your completed sleeps, or of another user who shared theirs with you\n",
+        "GET /analysis?from=YYYY-MM-DD&to=YYYY-MM-DD&tag_id=<id>|event_type_id=<id>&window_hours=<hours>&kind=main|nap|unknown -- compare your completed sleeps with and without a tag, or after an event within the window (6 hours by default)\n",
+        "POST /batch -- apply a list of create, update and delete operations in one transaction, either all or nothing (\"mode\": \"all_or_nothing\", the default) or skipping the ones that fail (\"mode\": \"best_effort\"), with a result for each operation\n",
+        "GET /export/csv?timezone=<timezone>&columns=<columns> -- download all your sleep states as CSV, with times in the timezone (your own by default) and the comma-separated columns in order (all of id,sleep_date,start,end,kind,time_in_bed_seconds,net_sleep_seconds,quality,sleep_latency_minutes,awakenings,restedness,dream_recall,interruptions,tags,comment,auto_closed by default)\n",
+        "GET /export/ndjson -- download all your sleep states as newline-delimited JSON, each with its interruptions\n",
+        "GET /feed/<feed token>?from_date=YYYY-MM-DD&to_date=YYYY-MM-DD -- your sleep states as an iCalendar feed to subscribe to in a calendar app, from 90 days ago by default; this needs no login, only a feed token from /v1/auth/token/feed\n",
+        "GET /report/<token> -- the report of a public link from /v1/sharing/links, as JSON (or as CSV with /report/<token>/csv); this needs no login, and 410 once the link has expired\n",
+        "POST /import?format=generic_csv|sleep_as_android|fitbit|apple_health&dry_run=true|false&timezone=<timezone> -- import the sleeps in the file in the body (at most 16 MiB, or 2 GiB for Apple Health exports), skipping the ones that overlap a sleep state you already have, and report what was imported (with ?dry_run=true, nothing is imported); generic CSV needs &start_column=<header>&end_column=<header>, and may have &comment_column=<header>&quality_column=<header>\n",
+        "GET /changes?since=<cursor>&limit=<count> -- the sleep states that changed since the cursor of a previous sync, oldest change first, with deleted ones as tombstones and a new cursor (without a cursor, everything is returned; 410 if the cursor is too old, so everything must be synced again)\n",
+        "POST /changes -- apply a batch of sleep state changes made offline, in order; each change is either applied, or reported as a conflict if the sleep state changed on the server since the client saw it, or rejected\n",
+        "GET /trash -- the sleep states you deleted, which are purged 30 days after deletion\n",
+        "POST /trash/<id>/restore -- take a sleep state out of the trash, or 409 if it is not completed and another sleep state is going on\n",
         "GET /<id> -- get sleep state by ID\n",
-        "POST /new - create a sleep state whose start time is now, or 409 if current sleep state already exists\n",
-        "PUT /<id> -- change sleep state by ID (ID in body must match the entry's data)\n",
-        "DELETE /<id> -- delete sleep state by ID, or 404\n",
+        "POST /new - create a sleep state whose start time is now, or 409 if the subject's current sleep state already exists (with ?replace_stale=true, a current sleep state longer than your maximum sleep length is ended automatically instead)\n",
+        "PUT /<id> -- change sleep state by ID (ID in body must match the entry's data; add ?user_id=<id> for a sleep state of another user who gave you read_write access)\n",
+        "DELETE /<id> -- move sleep state to the trash by ID, or 404\n",
         "GET /@current -- the sleep state that is not completed, or 404\n",
-        "POST /@current -- modify the current sleep state, so that its end time is now (and it is not the current sleep state anymore)\n",
+        "POST /@current -- modify the current sleep state, so that its end time is now (and it is not the current sleep state anymore); the body may contain a morning check-in\n",
         "PUT /@current -- modify the current sleep state, so that its start time is now\n",
-        "DELETE /@current -- delete the current sleep state\n",
+        "DELETE /@current -- move the current sleep state to the trash\n",
+        "GET /<id>/interruptions -- list of the times you woke up during a sleep state\n",
+        "POST /<id>/interruptions -- add an interruption to a sleep state, or 409 if it overlaps another one\n",
+        "GET /<id>/interruptions/<id> -- get interruption by ID\n",
+        "PUT /<id>/interruptions/<id> -- change interruption by ID (IDs in body must match the entry's data), or 409 if it would overlap another one\n",
+        "DELETE /<id>/interruptions/<id> -- delete interruption by ID, or 404\n",
+        "GET /<id>/history -- the previous values of a sleep state, recorded whenever it is changed, most recent first\n",
+        "POST /<id>/history/<revision id>/revert -- change a sleep state back to the values of a revision (the values before reverting are recorded too), or 409 if that would make two sleep states go on at once\n",
+        "GET /<id>/tags -- list of the tags attached to a sleep state\n",
+        "PUT /<id>/tags/<tag id> -- attach a tag to a sleep state\n",
+        "DELETE /<id>/tags/<tag id> -- remove a tag from a sleep state, or 404\n",
+        "GET /@current/interruptions -- list of the interruptions of the current sleep state\n",
+        "POST /@current/interruptions/new -- \"I'm awake\": start an interruption of the current sleep state now, or 409 if one is going on\n",
+        "GET /@current/interruptions/@current -- the interruption that is going on, or 404\n",
+        "POST /@current/interruptions/@current -- \"back to sleep\": end the interruption that is going on now\n",
+        "DELETE /@current/interruptions/@current -- delete the interruption that is going on\n",
     )
 }
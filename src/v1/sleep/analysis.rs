@@ -0,0 +1,239 @@
+mod effect;
+
+use api_types::v1::{EventKind, SleepAnalysis, SleepAnalysisGroup, SleepAnalysisQuery, SleepState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, NaiveTime, Timelike};
+use sqlx::{query, query_as};
+
+use crate::{
+    v1::{settings::SleepContext, subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use self::effect::compare;
+
+use super::{
+    row::{load_extras, SleepExtras, SleepStateRow},
+    stats::{
+        compute::{circular_mean_and_std_dev, mean_and_std_dev},
+        MAX_RANGE_DAYS,
+    },
+};
+
+/// The longest window before a sleep that events can be counted in, in hours.
+const MAX_WINDOW_HOURS: u32 = 7 * 24;
+
+/// A completed sleep, with the outcomes that are compared.
+struct AnalysisSample {
+    has_factor: bool,
+    net_sleep_seconds: i64,
+    quality: Option<u8>,
+    /// Local time of day when the sleep started
+    bedtime: NaiveTime,
+}
+
+/// Compare the sleeps that have a tag, or that follow an event, against the other sleeps.
+pub async fn analyze(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(query): Query<SleepAnalysisQuery>,
+) -> ResultResponse<Json<SleepAnalysis>> {
+    if query.from > query.to {
+        return Err(ApiError::BadRequest(
+            "`from` must not be after `to`".to_string(),
+        ))?;
+    }
+    if query.to - query.from > Duration::days(MAX_RANGE_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "cannot analyze more than {MAX_RANGE_DAYS} days at once"
+        )))?;
+    }
+    if query.window_hours > MAX_WINDOW_HOURS {
+        return Err(ApiError::BadRequest(format!(
+            "`window_hours` must be at most {MAX_WINDOW_HOURS}"
+        )))?;
+    }
+
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, query.subject_id).await?;
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let local_day = &context.local_day;
+    let window = Duration::hours(query.window_hours as i64);
+
+    let lower = local_day.lower_bound_utc(query.from).timestamp();
+    let upper = local_day.upper_bound_utc(query.to).timestamp();
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND started_at_unix_time>=? AND started_at_unix_time<?
+                AND ended_at_unix_time IS NOT NULL AND deleted_at_unix_time IS NULL
+            ORDER BY started_at_unix_time"#,
+        subject_id,
+        lower,
+        upper,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let extras = load_extras(&app_state.db, &ids).await?;
+    let no_extras = SleepExtras::default();
+    let states: Vec<SleepState> = rows
+        .into_iter()
+        .map(|row| {
+            let row_extras = extras.get(&row.id).unwrap_or(&no_extras);
+            row.into_api(&context, row_extras)
+        })
+        .filter(|state| {
+            query.kind.is_none_or(|kind| state.kind == kind)
+                && (query.from..=query.to).contains(&local_day.sleep_date(state.start))
+        })
+        .collect();
+
+    let has_factor: Box<dyn Fn(&SleepState) -> bool> = match (query.tag_id, query.event_type_id) {
+        (Some(tag_id), None) => {
+            query!(
+                "SELECT id FROM tag WHERE user_id=? AND id=?",
+                conn_user.id,
+                tag_id
+            )
+            .fetch_optional(&app_state.db)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+            Box::new(move |state| state.tags.iter().any(|tag| tag.id == tag_id))
+        }
+        (None, Some(event_type_id)) => {
+            let event_type = query!(
+                "SELECT kind FROM event_type WHERE user_id=? AND id=?",
+                conn_user.id,
+                event_type_id
+            )
+            .fetch_optional(&app_state.db)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+            let is_interval = event_type.kind.parse() == Ok(EventKind::Interval);
+
+            let events_lower = lower - window.num_seconds();
+            // Instant events have no end, so they are in the window by their start,
+            // while an interval event without an end is still going on
+            let events = query!(
+                r#"SELECT started_at_unix_time, ended_at_unix_time FROM event
+                    WHERE user_id=? AND event_type_id=? AND started_at_unix_time<?
+                        AND (ended_at_unix_time>=?
+                            OR (ended_at_unix_time IS NULL AND (? OR started_at_unix_time>=?)))"#,
+                conn_user.id,
+                event_type_id,
+                upper,
+                events_lower,
+                is_interval,
+                events_lower,
+            )
+            .fetch_all(&app_state.db)
+            .await?;
+            // An interval event that has not ended yet is still going on
+            let spans: Vec<(i64, i64)> = events
+                .into_iter()
+                .map(|event| {
+                    let start = event.started_at_unix_time;
+                    let end = match event.ended_at_unix_time {
+                        Some(end) => end,
+                        None if is_interval => i64::MAX,
+                        None => start,
+                    };
+                    (start, end)
+                })
+                .collect();
+            Box::new(move |state| {
+                let sleep_start = state.start.timestamp();
+                let window_start = (state.start - window).timestamp();
+                spans
+                    .iter()
+                    .any(|&(start, end)| start <= sleep_start && end >= window_start)
+            })
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "exactly one of `tag_id` and `event_type_id` must be given".to_string(),
+            ))?
+        }
+    };
+
+    let samples: Vec<AnalysisSample> = states
+        .iter()
+        .filter_map(|state| {
+            Some(AnalysisSample {
+                has_factor: has_factor(state),
+                net_sleep_seconds: state.net_sleep_seconds?,
+                quality: state.check_in.quality,
+                bedtime: local_day.local_time(state.start).time(),
+            })
+        })
+        .collect();
+    let (with_factor, without_factor): (Vec<&AnalysisSample>, Vec<&AnalysisSample>) =
+        samples.iter().partition(|sample| sample.has_factor);
+
+    let durations = |group: &[&AnalysisSample]| -> Vec<f64> {
+        group
+            .iter()
+            .map(|sample| sample.net_sleep_seconds as f64)
+            .collect()
+    };
+    let qualities = |group: &[&AnalysisSample]| -> Vec<f64> {
+        group
+            .iter()
+            .filter_map(|sample| sample.quality.map(f64::from))
+            .collect()
+    };
+    // Bedtimes are compared as offsets from the mean bedtime of all the sleeps,
+    // so that going to bed at 23:00 or at 01:00 is two hours apart rather than 22
+    let all_bedtimes: Vec<NaiveTime> = samples.iter().map(|sample| sample.bedtime).collect();
+    let (mean_bedtime, _) = circular_mean_and_std_dev(&all_bedtimes);
+    let bedtime_offsets = |group: &[&AnalysisSample]| -> Vec<f64> {
+        let Some(mean_bedtime) = mean_bedtime else {
+            return vec![];
+        };
+        group
+            .iter()
+            .map(|sample| {
+                let offset = sample.bedtime.num_seconds_from_midnight() as i64
+                    - mean_bedtime.num_seconds_from_midnight() as i64;
+                ((offset + 43200).rem_euclid(86400) - 43200) as f64
+            })
+            .collect()
+    };
+    let summarize = |group: &[&AnalysisSample]| -> SleepAnalysisGroup {
+        let (average_duration_seconds, duration_std_dev_seconds) =
+            mean_and_std_dev(&durations(group));
+        let group_qualities = qualities(group);
+        let (average_quality, quality_std_dev) = mean_and_std_dev(&group_qualities);
+        let bedtimes: Vec<NaiveTime> = group.iter().map(|sample| sample.bedtime).collect();
+        let (average_bedtime, bedtime_std_dev_seconds) = circular_mean_and_std_dev(&bedtimes);
+        SleepAnalysisGroup {
+            sleep_count: group.len() as u32,
+            average_duration_seconds,
+            duration_std_dev_seconds,
+            quality_count: group_qualities.len() as u32,
+            average_quality,
+            quality_std_dev,
+            average_bedtime,
+            bedtime_std_dev_seconds,
+        }
+    };
+
+    Ok(Json(SleepAnalysis {
+        window_hours: query.window_hours,
+        duration: compare(&durations(&with_factor), &durations(&without_factor)),
+        quality: compare(&qualities(&with_factor), &qualities(&without_factor)),
+        bedtime: compare(
+            &bedtime_offsets(&with_factor),
+            &bedtime_offsets(&without_factor),
+        ),
+        with_factor: summarize(&with_factor),
+        without_factor: summarize(&without_factor),
+    }))
+}
@@ -0,0 +1,67 @@
+use api_types::v1::SleepAnalysisEffect;
+
+use crate::v1::sleep::stats::compute::mean_and_std_dev;
+
+/// The two-sided 95% quantile of the standard normal distribution.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Compare the samples with the factor against those without it.
+///
+/// Returns nothing unless both groups have at least two samples,
+/// because otherwise there is no variance to estimate the uncertainty from.
+pub fn compare(with: &[f64], without: &[f64]) -> Option<SleepAnalysisEffect> {
+    let (Some(mean_with), Some(std_dev_with)) = mean_and_std_dev(with) else {
+        return None;
+    };
+    let (Some(mean_without), Some(std_dev_without)) = mean_and_std_dev(without) else {
+        return None;
+    };
+    let (n_with, n_without) = (with.len() as f64, without.len() as f64);
+    let difference = mean_with - mean_without;
+
+    // Welch's t-interval, which does not assume that both groups have the same variance
+    let variance_with = std_dev_with.powi(2) / n_with;
+    let variance_without = std_dev_without.powi(2) / n_without;
+    let standard_error = (variance_with + variance_without).sqrt();
+    let margin = if standard_error > 0.0 {
+        let degrees_of_freedom = (variance_with + variance_without).powi(2)
+            / (variance_with.powi(2) / (n_with - 1.0)
+                + variance_without.powi(2) / (n_without - 1.0));
+        t_quantile_95(degrees_of_freedom) * standard_error
+    } else {
+        0.0
+    };
+
+    let pooled_std_dev = (((n_with - 1.0) * std_dev_with.powi(2)
+        + (n_without - 1.0) * std_dev_without.powi(2))
+        / (n_with + n_without - 2.0))
+        .sqrt();
+    let effect_size = (pooled_std_dev > 0.0).then(|| {
+        let correction = 1.0 - 3.0 / (4.0 * (n_with + n_without) - 9.0);
+        difference / pooled_std_dev * correction
+    });
+
+    Some(SleepAnalysisEffect {
+        with_count: with.len() as u32,
+        without_count: without.len() as u32,
+        difference,
+        confidence_interval_low: difference - margin,
+        confidence_interval_high: difference + margin,
+        effect_size,
+    })
+}
+
+/// The two-sided 95% quantile of Student's t-distribution.
+///
+/// This uses the Cornish-Fisher expansion around the normal quantile,
+/// which is accurate to within 1% from two degrees of freedom upwards.
+/// Below that, it underestimates the quantile, but such tiny samples say little anyway.
+fn t_quantile_95(degrees_of_freedom: f64) -> f64 {
+    let df = degrees_of_freedom.max(1.0);
+    let z = Z_95;
+    let (z3, z5, z7, z9) = (z.powi(3), z.powi(5), z.powi(7), z.powi(9));
+    z + (z3 + z) / (4.0 * df)
+        + (5.0 * z5 + 16.0 * z3 + 3.0 * z) / (96.0 * df.powi(2))
+        + (3.0 * z7 + 19.0 * z5 + 17.0 * z3 - 15.0 * z) / (384.0 * df.powi(3))
+        + (79.0 * z9 + 776.0 * z7 + 1482.0 * z5 - 1920.0 * z3 - 945.0 * z) / (92160.0 * df.powi(4))
+}
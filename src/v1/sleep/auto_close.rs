@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use api_types::{
+    v1::{DateTimeUtc, SleepKind},
+    Snowflake,
+};
+use chrono::{Duration, NaiveTime};
+use sqlx::{query, query_as, Connection, SqliteConnection, SqlitePool};
+
+use crate::{
+    clock::Clock,
+    datetime_utc_from_timestamp,
+    v1::settings::{load_user_settings, SleepContext},
+};
+
+use super::{
+    bus::{SleepChange, SleepEventBus},
+    history::record_revision,
+    row::{SleepExtras, SleepStateRow},
+    stats::compute::circular_mean_and_std_dev,
+};
+
+/// How often to look for forgotten sleeps.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// How many recent main sleeps the usual wake time is computed from.
+const USUAL_WAKE_TIME_SAMPLES: usize = 14;
+
+/// How far back to look for recent main sleeps, in days.
+const USUAL_WAKE_TIME_DAYS: i64 = 60;
+
+/// The usual wake time is only trusted if there are at least this many sleeps to compute it from.
+const MIN_USUAL_WAKE_TIME_SAMPLES: usize = 3;
+
+/// Whether a sleep that is still going on has been going on for too long to be real.
+pub fn is_stale(context: &SleepContext, start: DateTimeUtc, now: DateTimeUtc) -> bool {
+    now - start > Duration::minutes(context.settings.max_sleep_minutes as i64)
+}
+
+/// Guess when a forgotten sleep actually ended.
+///
+/// This is the first time the subject usually wakes up after the start of the sleep,
+/// or the maximum sleep length after the start if that comes first,
+/// or if the subject does not have enough sleeps to tell when they usually wake up.
+pub async fn guess_end(
+    db: &SqlitePool,
+    context: &SleepContext,
+    subject_id: Snowflake,
+    start: DateTimeUtc,
+) -> Result<DateTimeUtc, sqlx::Error> {
+    let latest_end = start + Duration::minutes(context.settings.max_sleep_minutes as i64);
+
+    // Sleeps that were closed automatically are guesses themselves, so they are left out
+    let since = (start - Duration::days(USUAL_WAKE_TIME_DAYS)).timestamp();
+    let start_timestamp = start.timestamp();
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND ended_at_unix_time IS NOT NULL AND NOT auto_closed
+                AND deleted_at_unix_time IS NULL
+                AND started_at_unix_time>=? AND started_at_unix_time<?
+            ORDER BY started_at_unix_time DESC"#,
+        subject_id,
+        since,
+        start_timestamp,
+    )
+    .fetch_all(db)
+    .await?;
+    // The kind does not depend on the interruptions or tags
+    let no_extras = SleepExtras::default();
+    let wake_times: Vec<NaiveTime> = rows
+        .into_iter()
+        .map(|row| row.into_api(context, &no_extras))
+        .filter(|state| state.kind == SleepKind::Main)
+        .filter_map(|state| state.end)
+        .take(USUAL_WAKE_TIME_SAMPLES)
+        .map(|end| context.local_day.local_time(end).time())
+        .collect();
+    if wake_times.len() < MIN_USUAL_WAKE_TIME_SAMPLES {
+        return Ok(latest_end);
+    }
+    let (Some(usual_wake_time), _) = circular_mean_and_std_dev(&wake_times) else {
+        return Ok(latest_end);
+    };
+
+    let local_start = context.local_day.local_time(start);
+    let mut wake_up = context
+        .local_day
+        .instant_at(local_start.date().and_time(usual_wake_time));
+    if wake_up <= start {
+        wake_up = context
+            .local_day
+            .instant_at((local_start.date() + Duration::days(1)).and_time(usual_wake_time));
+    }
+    Ok(wake_up.min(latest_end))
+}
+
+/// End a forgotten sleep at the given time, flagging it as closed automatically.
+///
+/// The change is recorded in the history as made by the server, at `now`.
+/// Returns whether the sleep was still going on; if not, nothing is changed or recorded.
+pub async fn close_stale(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    id: Snowflake,
+    end: DateTimeUtc,
+    now: DateTimeUtc,
+) -> Result<bool, sqlx::Error> {
+    // The revision has to be taken before the update, so it is undone with a savepoint
+    // if the sleep turns out to be ended already
+    let mut savepoint = conn.begin().await?;
+    record_revision(&mut savepoint, user_id, id, None, now).await?;
+    let end = end.timestamp();
+    let row = query!(
+        r#"UPDATE sleep_state
+        SET ended_at_unix_time=?, auto_closed=1
+        WHERE id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL
+        RETURNING sleep_state.id"#,
+        end,
+        id,
+    )
+    .fetch_optional(&mut *savepoint)
+    .await?;
+    if row.is_none() {
+        savepoint.rollback().await?;
+        return Ok(false);
+    }
+    // The user cannot have stayed awake past the end of the sleep either
+    query!(
+        r#"UPDATE sleep_interruption
+        SET ended_at_unix_time=MAX(started_at_unix_time, ?)
+        WHERE sleep_state_id=? AND ended_at_unix_time IS NULL"#,
+        end,
+        id,
+    )
+    .execute(&mut *savepoint)
+    .await?;
+    savepoint.commit().await?;
+    Ok(true)
+}
+
+/// Ends the sleeps that users forgot to end, once they are longer than the user's maximum sleep length.
+pub struct StaleSleepSweeper {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+    sleep_events: SleepEventBus,
+}
+
+impl StaleSleepSweeper {
+    pub fn new(db: SqlitePool, clock: Arc<dyn Clock>, sleep_events: SleepEventBus) -> Self {
+        Self {
+            db,
+            clock,
+            sleep_events,
+        }
+    }
+
+    /// Sweep every few minutes, in the background.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.sweep().await {
+                    tracing::error!("Failed to sweep forgotten sleeps: {err}");
+                }
+            }
+        });
+    }
+
+    /// End all the sleeps that are stale at the current time of the clock.
+    pub async fn sweep(&self) -> Result<(), sqlx::Error> {
+        let now = self.clock.now();
+        let rows = query!(
+            r#"SELECT id, user_id, subject_id AS "subject_id!", started_at_unix_time FROM sleep_state
+                WHERE ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        for row in rows {
+            let start = datetime_utc_from_timestamp(row.started_at_unix_time);
+            if let Err(err) = self
+                .close_if_stale(
+                    row.id.into(),
+                    row.user_id.into(),
+                    row.subject_id.into(),
+                    start,
+                    now,
+                )
+                .await
+            {
+                tracing::warn!("Failed to end forgotten sleep {}: {err}", row.id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn close_if_stale(
+        &self,
+        id: Snowflake,
+        user_id: Snowflake,
+        subject_id: Snowflake,
+        start: DateTimeUtc,
+        now: DateTimeUtc,
+    ) -> anyhow::Result<()> {
+        let settings = load_user_settings(&self.db, user_id).await?;
+        let context = SleepContext::from_settings(settings)?;
+        if !is_stale(&context, start, now) {
+            return Ok(());
+        }
+        let end = guess_end(&self.db, &context, subject_id, start).await?;
+        let mut tx = self.db.begin().await?;
+        if close_stale(&mut tx, user_id, id, end, now).await? {
+            tx.commit().await?;
+            tracing::info!("Ended forgotten sleep {id} at {end}");
+            self.sleep_events.publish(user_id, SleepChange::Ended(id));
+        }
+        Ok(())
+    }
+}
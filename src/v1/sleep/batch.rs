@@ -0,0 +1,138 @@
+use api_types::{
+    v1::{
+        DateTimeUtc, SleepBatch, SleepBatchMode, SleepBatchOperation, SleepBatchOperationResult,
+        SleepBatchResult, SubjectQuery,
+    },
+    Snowflake,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{Connection, SqliteConnection};
+
+use crate::{
+    v1::{subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::SleepChange,
+    create::insert_sleep_state,
+    delete::move_to_trash,
+    update::{replace_sleep_state, validate_sleep_state, ChangeError},
+};
+
+/// The most operations that can be applied at once, in a batch or an upload of offline changes.
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Apply a list of operations on sleep states in a single transaction.
+///
+/// Each operation is validated like the endpoint for a single sleep state would,
+/// and fails with the status code that it would have returned.
+/// Sleep states are created for the subject given in the query.
+pub async fn apply_batch(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+    Json(batch): Json<SleepBatch>,
+) -> ResultResponse<Json<SleepBatchResult>> {
+    if batch.operations.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "cannot apply more than {MAX_BATCH_SIZE} operations at once"
+        )))?;
+    }
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let all_or_nothing = batch.mode == SleepBatchMode::AllOrNothing;
+    let now = app_state.clock.now();
+
+    let mut tx = app_state.db.begin().await?;
+    let mut results = vec![];
+    let mut changes = vec![];
+    let mut failed = false;
+    for operation in batch.operations {
+        if failed && all_or_nothing {
+            results.push(SleepBatchOperationResult::Skipped);
+            continue;
+        }
+        // A failed operation is undone on its own, so that the others can still be applied
+        let mut savepoint = tx.begin().await?;
+        let applied = apply(
+            &mut savepoint,
+            conn_user.id,
+            subject_id,
+            conn_token.id,
+            now,
+            operation,
+        )
+        .await?;
+        match applied {
+            Ok(change) => {
+                savepoint.commit().await?;
+                results.push(SleepBatchOperationResult::Ok { id: change.id() });
+                changes.push(change);
+            }
+            Err((code, error)) => {
+                savepoint.rollback().await?;
+                failed = true;
+                results.push(SleepBatchOperationResult::Failed {
+                    code: code.as_u16(),
+                    error,
+                });
+            }
+        }
+    }
+
+    let committed = !(failed && all_or_nothing);
+    if committed {
+        tx.commit().await?;
+        for change in changes {
+            app_state.sleep_events.publish(conn_user.id, change);
+        }
+    }
+    Ok(Json(SleepBatchResult { committed, results }))
+}
+
+/// Apply one operation, returning the change it made to a sleep state,
+/// or the status code and message that it failed with.
+async fn apply(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    subject_id: Snowflake,
+    token_id: Snowflake,
+    now: DateTimeUtc,
+    operation: SleepBatchOperation,
+) -> Result<Result<SleepChange, (StatusCode, String)>, sqlx::Error> {
+    match operation {
+        SleepBatchOperation::Create { sleep_state } => {
+            let values = match validate_sleep_state(&sleep_state) {
+                Ok(values) => values,
+                Err(err) => return Ok(Err((StatusCode::BAD_REQUEST, err.to_string()))),
+            };
+            let id = Snowflake::new().await;
+            if !insert_sleep_state(&mut *conn, user_id, subject_id, id, &values).await? {
+                return Ok(Err(ChangeError::AlreadyGoingOn.into_response()));
+            }
+            Ok(Ok(SleepChange::Created(id)))
+        }
+        SleepBatchOperation::Update { sleep_state } => {
+            let id = sleep_state.id;
+            let values = match validate_sleep_state(&sleep_state) {
+                Ok(values) => values,
+                Err(err) => return Ok(Err((StatusCode::BAD_REQUEST, err.to_string()))),
+            };
+            let replaced =
+                replace_sleep_state(&mut *conn, user_id, id, token_id, now, &values, None).await?;
+            Ok(replaced
+                .map(|()| SleepChange::Updated(id))
+                .map_err(ChangeError::into_response))
+        }
+        SleepBatchOperation::Delete { id } => {
+            if !move_to_trash(&mut *conn, user_id, id, now, None).await? {
+                return Ok(Err(ChangeError::NotFound.into_response()));
+            }
+            Ok(Ok(SleepChange::Deleted(id)))
+        }
+    }
+}
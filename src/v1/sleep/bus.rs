@@ -0,0 +1,54 @@
+use api_types::Snowflake;
+use tokio::sync::broadcast;
+
+/// How many changes a slow subscriber can fall behind before it misses some.
+const CAPACITY: usize = 1024;
+
+/// What happened to a sleep state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepChange {
+    Created(Snowflake),
+    Updated(Snowflake),
+    /// The sleep that was going on was ended, which is a kind of update that some listeners care about.
+    Ended(Snowflake),
+    Deleted(Snowflake),
+}
+
+impl SleepChange {
+    /// The sleep state that changed.
+    pub fn id(&self) -> Snowflake {
+        match self {
+            Self::Created(id) | Self::Updated(id) | Self::Ended(id) | Self::Deleted(id) => *id,
+        }
+    }
+}
+
+/// A change to a sleep state of a user.
+#[derive(Debug, Clone, Copy)]
+pub struct SleepEvent {
+    pub user_id: Snowflake,
+    pub change: SleepChange,
+}
+
+/// Carries the changes to sleep states from the handlers that make them to the open event streams.
+///
+/// Only the IDs are sent, so publishing is cheap even if nobody listens;
+/// the streams load the sleep states themselves.
+/// Changes must be published after they are committed, so that the streams see them.
+#[derive(Debug, Clone)]
+pub struct SleepEventBus(broadcast::Sender<SleepEvent>);
+
+impl SleepEventBus {
+    pub fn new() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+
+    pub fn publish(&self, user_id: Snowflake, change: SleepChange) {
+        // Sending only fails if no stream is open, and then nobody needs to know
+        let _ = self.0.send(SleepEvent { user_id, change });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SleepEvent> {
+        self.0.subscribe()
+    }
+}
@@ -0,0 +1,44 @@
+use api_types::v1::SleepCheckIn;
+
+use crate::v1::ApiError;
+
+/// The values of a check-in, as they are stored in the `sleep_state` table.
+pub struct CheckInColumns {
+    pub quality: Option<i64>,
+    pub sleep_latency_minutes: Option<i64>,
+    pub awakenings: Option<i64>,
+    pub restedness: Option<i64>,
+    pub dream_recall: Option<String>,
+}
+
+/// Check that the values of a check-in make sense, and convert them for storing.
+pub fn validate_check_in(check_in: &SleepCheckIn) -> Result<CheckInColumns, ApiError> {
+    for (name, rating) in [
+        ("quality", check_in.quality),
+        ("restedness", check_in.restedness),
+    ] {
+        if let Some(rating) = rating {
+            if !(1..=5).contains(&rating) {
+                return Err(ApiError::BadRequest(format!(
+                    "{name} must be between 1 and 5, not {rating}"
+                )));
+            }
+        }
+    }
+    if check_in
+        .sleep_latency_minutes
+        .is_some_and(|minutes| minutes > 24 * 60)
+    {
+        return Err(ApiError::BadRequest(
+            "time to fall asleep cannot be longer than a day".to_string(),
+        ));
+    }
+
+    Ok(CheckInColumns {
+        quality: check_in.quality.map(i64::from),
+        sleep_latency_minutes: check_in.sleep_latency_minutes.map(i64::from),
+        awakenings: check_in.awakenings.map(i64::from),
+        restedness: check_in.restedness.map(i64::from),
+        dream_recall: check_in.dream_recall.map(|recall| recall.to_string()),
+    })
+}
@@ -1,47 +1,172 @@
-use api_types::{v1::SleepState, Snowflake};
-use axum::{extract::State, http::StatusCode, Json};
-use sqlx::query;
+use api_types::{
+    v1::{NewSleepStateQuery, SleepState},
+    Snowflake,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, SqliteConnection};
 
-use crate::{v1::ResultResponse, AppState, RequireUser};
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{settings::SleepContext, subjects::resolve_subject, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    auto_close::{close_stale, guess_end, is_stale},
+    bus::SleepChange,
+    row::{SleepExtras, SleepStateRow},
+    update::SleepStateColumns,
+};
 
 pub async fn create_now(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
-) -> ResultResponse<Result<(StatusCode, Json<SleepState>), StatusCode>> {
-    // Check if there is a row with no end time.
-    // If there is, return a Conflict
+    Query(params): Query<NewSleepStateQuery>,
+) -> ResultResponse<Result<(StatusCode, Json<SleepState>), (StatusCode, String)>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, params.subject_id).await?;
+    let now = app_state.clock.now();
+
+    // Check if the subject has a row with no end time.
+    // If there is, return a Conflict, unless it was forgotten and the client asked to replace it
     let existing_row = query!(
-        "SELECT * FROM sleep_state WHERE user_id=? AND ended_at_unix_time IS NULL",
-        conn_user.id
+        "SELECT * FROM sleep_state WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL",
+        subject_id
     )
     .fetch_optional(&app_state.db)
     .await?;
-    if let Some(_row) = existing_row {
-        return Ok(Err(StatusCode::CONFLICT));
+    let mut stale = None;
+    if let Some(row) = existing_row {
+        let start = datetime_utc_from_timestamp(row.started_at_unix_time);
+        if !is_stale(&context, start, now) {
+            return Ok(Err((
+                StatusCode::CONFLICT,
+                "a sleep is already going on".to_string(),
+            )));
+        }
+        if !params.replace_stale {
+            return Ok(Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "the current sleep started at {} and is longer than your maximum sleep length; \
+                        POST /new?replace_stale=true to end it automatically and start a new one",
+                    start.to_rfc3339()
+                ),
+            )));
+        }
+        let end = guess_end(&app_state.db, &context, subject_id, start).await?;
+        stale = Some((Snowflake::from(row.id), end));
     }
 
     let id = Snowflake::new().await;
-    let now = id.timestamp().timestamp();
-    query!(
+    let started_at_unix_time = now.timestamp();
+    // Ending the forgotten sleep and starting the new one happen together or not at all
+    let mut tx = app_state.db.begin().await?;
+    let mut closed = None;
+    if let Some((stale_id, end)) = stale {
+        if close_stale(&mut tx, conn_user.id, stale_id, end, now).await? {
+            closed = Some(stale_id);
+        } else {
+            // The user or the sweep ended it in the meantime, which is just as good
+            tracing::debug!("Forgotten sleep {stale_id} was already ended");
+        }
+    }
+    let inserted = query!(
         r#"INSERT INTO sleep_state
-            (id, user_id, started_at_unix_time, ended_at_unix_time, comment)
-            VALUES (?,?,?,?,?)"#,
+            (id, user_id, subject_id, started_at_unix_time, ended_at_unix_time, comment)
+            SELECT ?,?,?,?,?,?
+            WHERE NOT EXISTS (
+                SELECT 1 FROM sleep_state
+                WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL
+            )"#,
         id,
         conn_user.id,
-        now,
+        subject_id,
+        started_at_unix_time,
         Option::<i64>::None,
         Option::<String>::None,
+        subject_id,
     )
-    .execute(&app_state.db)
+    .execute(&mut tx)
     .await?;
+    if inserted.rows_affected() == 0 {
+        return Ok(Err((
+            StatusCode::CONFLICT,
+            "a sleep is already going on".to_string(),
+        )));
+    }
+    tx.commit().await?;
+    if let Some(closed) = closed {
+        app_state
+            .sleep_events
+            .publish(conn_user.id, SleepChange::Ended(closed));
+    }
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Created(id));
 
+    let row = SleepStateRow {
+        id: id.into(),
+        started_at_unix_time,
+        ended_at_unix_time: None,
+        comment: None,
+        kind: None,
+        quality: None,
+        sleep_latency_minutes: None,
+        awakenings: None,
+        restedness: None,
+        dream_recall: None,
+        auto_closed: 0,
+        deleted_at_unix_time: None,
+        // Numbered by a trigger, and not part of the response
+        change_sequence: 0,
+        subject_id: Some(subject_id.into()),
+    };
     Ok(Ok((
         StatusCode::CREATED,
-        Json(SleepState {
-            id,
-            start: id.timestamp(),
-            end: None,
-            comment: None,
-        }),
+        Json(row.into_api(&context, &SleepExtras::default())),
     )))
 }
+
+/// Create a sleep state of the subject with the given values.
+///
+/// Returns false without creating it if it is not completed, and another sleep state of the subject is going on.
+pub async fn insert_sleep_state(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    subject_id: Snowflake,
+    id: Snowflake,
+    values: &SleepStateColumns,
+) -> Result<bool, sqlx::Error> {
+    let inserted = query!(
+        r#"INSERT INTO sleep_state
+            (id, user_id, subject_id, started_at_unix_time, ended_at_unix_time, comment, kind,
+                quality, sleep_latency_minutes, awakenings, restedness, dream_recall)
+            SELECT ?,?,?,?,?,?,?,?,?,?,?,?
+            WHERE ? IS NOT NULL OR NOT EXISTS (
+                SELECT 1 FROM sleep_state
+                WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL
+            )"#,
+        id,
+        user_id,
+        subject_id,
+        values.start,
+        values.end,
+        values.comment,
+        values.kind,
+        values.check_in.quality,
+        values.check_in.sleep_latency_minutes,
+        values.check_in.awakenings,
+        values.check_in.restedness,
+        values.check_in.dream_recall,
+        values.end,
+        subject_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(inserted.rows_affected() > 0)
+}
@@ -1,50 +1,90 @@
-use api_types::Snowflake;
+use api_types::{
+    v1::{DateTimeUtc, SubjectQuery},
+    Snowflake,
+};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
 };
-use sqlx::query;
+use sqlx::{query, SqliteConnection};
+
+use crate::{
+    v1::{subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
 
-use crate::{v1::ResultResponse, AppState, RequireUser};
+use super::bus::SleepChange;
 
+/// Move a sleep state to the trash.
+/// It can be restored until it is purged.
 pub async fn delete_by_id(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
     Path(id): Path<Snowflake>,
 ) -> ResultResponse<StatusCode> {
-    let row = query!(
-        "DELETE FROM sleep_state WHERE user_id=? AND id=? RETURNING sleep_state.id",
-        conn_user.id,
-        id
+    let mut conn = app_state.db.acquire().await?;
+    if !move_to_trash(&mut conn, conn_user.id, id, app_state.clock.now(), None).await? {
+        return Err(ApiError::NotFound)?;
+    }
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Deleted(id));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move one of the user's sleep states to the trash, as deleted at `now`.
+///
+/// If `base_sequence` is given, the sleep state is only moved if it has not changed since.
+/// Returns whether it was found outside the trash, unchanged.
+pub async fn move_to_trash(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    id: Snowflake,
+    now: DateTimeUtc,
+    base_sequence: Option<i64>,
+) -> Result<bool, sqlx::Error> {
+    let now = now.timestamp();
+    let result = query!(
+        r#"UPDATE sleep_state SET deleted_at_unix_time=?
+            WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL
+                AND (?4 IS NULL OR change_sequence=?4)"#,
+        now,
+        user_id,
+        id,
+        base_sequence,
     )
-    .fetch_optional(&app_state.db)
+    .execute(&mut *conn)
     .await?;
-
-    match row {
-        Some(row) => match row.id {
-            Some(_id) => Ok(StatusCode::NO_CONTENT),
-            None => Err(crate::v1::ApiError::NotFound)?,
-        },
-        None => Err(crate::v1::ApiError::NotFound)?,
-    }
+    Ok(result.rows_affected() > 0)
 }
 
 pub async fn delete_current(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
 ) -> ResultResponse<StatusCode> {
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let now = app_state.clock.now().timestamp();
     let row = query!(
-        "DELETE FROM sleep_state WHERE user_id=? AND ended_at_unix_time IS NULL RETURNING sleep_state.id",
-        conn_user.id,
+        r#"UPDATE sleep_state SET deleted_at_unix_time=?
+            WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL
+            RETURNING sleep_state.id"#,
+        now,
+        subject_id,
     )
     .fetch_optional(&app_state.db)
     .await?;
 
     match row {
         Some(row) => match row.id {
-            Some(_id) => Ok(StatusCode::NO_CONTENT),
-            None => Err(crate::v1::ApiError::NotFound)?,
+            Some(id) => {
+                app_state
+                    .sleep_events
+                    .publish(conn_user.id, SleepChange::Deleted(id.into()));
+                Ok(StatusCode::NO_CONTENT)
+            }
+            None => Err(ApiError::NotFound)?,
         },
-        None => Err(crate::v1::ApiError::NotFound)?,
+        None => Err(ApiError::NotFound)?,
     }
 }
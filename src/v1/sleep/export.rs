@@ -0,0 +1,278 @@
+use std::borrow::Cow;
+
+use api_types::{
+    v1::{
+        ExportedSleepState, SleepExportColumn, SleepExportQuery, SleepInterruption, SleepState,
+        SubjectQuery,
+    },
+    Snowflake,
+};
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::SecondsFormat;
+use chrono_tz::Tz;
+use futures_util::{stream, Stream, StreamExt};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    v1::{settings::SleepContext, subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    interruptions::InterruptionRow,
+    row::{load_extras, SleepStateRow},
+};
+
+/// How many sleep states are read from the database at once.
+const PAGE_SIZE: u32 = 500;
+
+/// All the sleep states of one of the user's subjects as CSV, oldest first.
+pub async fn export_csv(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(params): Query<SleepExportQuery>,
+) -> ResultResponse<impl IntoResponse> {
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, params.subject_id).await?;
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let tz = match &params.timezone {
+        Some(timezone) => timezone
+            .parse::<Tz>()
+            .map_err(|_| ApiError::BadRequest(format!("unknown timezone {timezone:?}")))?,
+        None => context.local_day.tz,
+    };
+    let columns = match params.columns {
+        Some(columns) if !columns.0.is_empty() => columns.0,
+        _ => SleepExportColumn::ALL.to_vec(),
+    };
+
+    let header_line = csv_line(columns.iter().map(|column| Cow::from(column.to_string())));
+    let pages = ExportPages::new(app_state.db, context, subject_id);
+    let lines = pages.into_stream(move |state, interruptions| {
+        csv_line(
+            columns
+                .iter()
+                .map(|column| csv_value(*column, state, interruptions, tz)),
+        )
+    });
+    let body = stream::once(async move { Ok(Bytes::from(header_line)) }).chain(lines);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"sleep.csv\"",
+            ),
+        ],
+        StreamBody::new(body),
+    ))
+}
+
+/// All the sleep states of one of the user's subjects as newline-delimited JSON, oldest first.
+pub async fn export_ndjson(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<impl IntoResponse> {
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let pages = ExportPages::new(app_state.db, context, subject_id);
+    let lines = pages.into_stream(|state, interruptions| {
+        let exported = ExportedSleepState {
+            sleep_state: state.clone(),
+            interruptions: interruptions.to_vec(),
+        };
+        let mut line =
+            serde_json::to_string(&exported).expect("a sleep state can always be serialized");
+        line.push('\n');
+        line
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"sleep.ndjson\"",
+            ),
+        ],
+        StreamBody::new(lines),
+    ))
+}
+
+/// Reads a subject's sleep states a page at a time, so that the whole history is never in memory at once.
+struct ExportPages {
+    db: SqlitePool,
+    context: SleepContext,
+    subject_id: Snowflake,
+    /// The start time and ID of the last sleep state that was read.
+    after: (i64, i64),
+    done: bool,
+}
+
+impl ExportPages {
+    fn new(db: SqlitePool, context: SleepContext, subject_id: Snowflake) -> Self {
+        Self {
+            db,
+            context,
+            subject_id,
+            after: (i64::MIN, i64::MIN),
+            done: false,
+        }
+    }
+
+    /// Read the next page of sleep states along with their interruptions,
+    /// or nothing if all of them have been read.
+    async fn next_page(
+        &mut self,
+    ) -> Result<Option<Vec<(SleepState, Vec<SleepInterruption>)>>, sqlx::Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let (after_start, after_id) = self.after;
+        let page = query!(
+            r#"SELECT id AS "id!", started_at_unix_time AS "started_at_unix_time!" FROM sleep_state
+                WHERE subject_id=? AND deleted_at_unix_time IS NULL
+                    AND (started_at_unix_time>? OR (started_at_unix_time=? AND id>?))
+                ORDER BY started_at_unix_time, id
+                LIMIT ?"#,
+            self.subject_id,
+            after_start,
+            after_start,
+            after_id,
+            PAGE_SIZE,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        if page.len() < PAGE_SIZE as usize {
+            self.done = true;
+        }
+        let Some(last) = page.last() else {
+            return Ok(None);
+        };
+        self.after = (last.started_at_unix_time, last.id);
+
+        // SQLite cannot bind arrays, so pass the IDs as a JSON array instead
+        let page_ids: Vec<i64> = page.iter().map(|row| row.id).collect();
+        let page_ids =
+            serde_json::to_string(&page_ids).expect("a list of integers is always valid JSON");
+        let rows = query_as!(
+            SleepStateRow,
+            r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+                sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+                deleted_at_unix_time, change_sequence, subject_id
+                FROM sleep_state
+                WHERE id IN (SELECT value FROM json_each(?))
+                ORDER BY started_at_unix_time, id"#,
+            page_ids,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+        let mut extras = load_extras(&self.db, &ids).await?;
+        Ok(Some(
+            rows.into_iter()
+                .map(|row| {
+                    let row_extras = extras.remove(&row.id).unwrap_or_default();
+                    let state = row.into_api(&self.context, &row_extras);
+                    let interruptions = row_extras
+                        .interruptions
+                        .into_iter()
+                        .map(InterruptionRow::into_api)
+                        .collect();
+                    (state, interruptions)
+                })
+                .collect(),
+        ))
+    }
+
+    /// Render each sleep state with `render`, one page per chunk of the body.
+    ///
+    /// If reading fails halfway, the body ends with an error, so that the client does not take it for all of the data.
+    fn into_stream<F>(self, render: F) -> impl Stream<Item = Result<Bytes, sqlx::Error>>
+    where
+        F: Fn(&SleepState, &[SleepInterruption]) -> String + Send + 'static,
+    {
+        stream::unfold((self, render), |(mut pages, render)| async move {
+            match pages.next_page().await {
+                Ok(Some(page)) => {
+                    let chunk: String = page
+                        .iter()
+                        .map(|(state, interruptions)| render(state, interruptions))
+                        .collect();
+                    Some((Ok(Bytes::from(chunk)), (pages, render)))
+                }
+                Ok(None) => None,
+                Err(err) => {
+                    tracing::error!("Failed to export sleep states: {err}");
+                    pages.done = true;
+                    Some((Err(err), (pages, render)))
+                }
+            }
+        })
+    }
+}
+
+/// The value of a column for a sleep state, as text.
+pub(super) fn csv_value(
+    column: SleepExportColumn,
+    state: &SleepState,
+    interruptions: &[SleepInterruption],
+    tz: Tz,
+) -> Cow<'static, str> {
+    fn optional<T: ToString>(value: Option<T>) -> Cow<'static, str> {
+        value.map_or(Cow::Borrowed(""), |value| Cow::Owned(value.to_string()))
+    }
+    let local_time = |instant: api_types::v1::DateTimeUtc| {
+        instant
+            .with_timezone(&tz)
+            .to_rfc3339_opts(SecondsFormat::Secs, false)
+    };
+    let check_in = &state.check_in;
+    match column {
+        SleepExportColumn::Id => state.id.to_string().into(),
+        SleepExportColumn::SleepDate => optional(state.sleep_date),
+        SleepExportColumn::Start => local_time(state.start).into(),
+        SleepExportColumn::End => optional(state.end.map(local_time)),
+        SleepExportColumn::Kind => state.kind.to_string().into(),
+        SleepExportColumn::TimeInBedSeconds => optional(state.time_in_bed_seconds),
+        SleepExportColumn::NetSleepSeconds => optional(state.net_sleep_seconds),
+        SleepExportColumn::Quality => optional(check_in.quality),
+        SleepExportColumn::SleepLatencyMinutes => optional(check_in.sleep_latency_minutes),
+        SleepExportColumn::Awakenings => optional(check_in.awakenings),
+        SleepExportColumn::Restedness => optional(check_in.restedness),
+        SleepExportColumn::DreamRecall => optional(check_in.dream_recall),
+        SleepExportColumn::Interruptions => interruptions.len().to_string().into(),
+        SleepExportColumn::Tags => state
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>()
+            .join(";")
+            .into(),
+        SleepExportColumn::Comment => optional(state.comment.as_ref()),
+        SleepExportColumn::AutoClosed => state.auto_closed.to_string().into(),
+    }
+}
+
+/// Join the fields of a CSV line, quoting the ones that need it as in RFC 4180.
+pub(super) fn csv_line<'a>(fields: impl Iterator<Item = Cow<'a, str>>) -> String {
+    let fields: Vec<Cow<str>> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+            } else {
+                field
+            }
+        })
+        .collect();
+    let mut line = fields.join(",");
+    line.push_str("\r\n");
+    line
+}
@@ -0,0 +1,159 @@
+use api_types::v1::{DateTimeUtc, SleepFeedQuery, SleepKind, SleepState, SleepStateListQuery};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::Duration;
+use sqlx::query;
+
+use crate::{
+    v1::{settings::SleepContext, ApiError, ResultResponse},
+    AppState,
+};
+
+use super::list::find_states;
+
+/// How many days back the feed goes, unless the query says otherwise.
+const DEFAULT_WINDOW_DAYS: i64 = 90;
+
+/// Lines of an iCalendar file are folded after this many octets.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// The user's sleep states as an iCalendar feed, for subscribing to in a calendar app.
+///
+/// There is no login here, since calendar apps cannot log in: the feed token in the URL is the only credential.
+/// The token may end with `.ics`, because some calendar apps only accept URLs that look like a file.
+pub async fn get_feed(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+    Query(params): Query<SleepFeedQuery>,
+) -> ResultResponse<impl IntoResponse> {
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    let now = app_state.clock.now();
+    let now_timestamp = now.timestamp();
+    let Some(row) = query!(
+        r#"UPDATE feed_token SET last_used_at_unix_time=? WHERE token=? RETURNING user_id AS "user_id!""#,
+        now_timestamp,
+        token,
+    )
+    .fetch_optional(&app_state.db)
+    .await?
+    else {
+        return Err(ApiError::NotFound)?;
+    };
+    let user_id = row.user_id.into();
+
+    let context = SleepContext::load(&app_state.db, user_id).await?;
+    let from_date = params
+        .from_date
+        .unwrap_or_else(|| context.local_day.sleep_date(now) - Duration::days(DEFAULT_WINDOW_DAYS));
+    let filter = SleepStateListQuery {
+        from_date: Some(from_date),
+        to_date: params.to_date,
+        subject_id: params.subject_id,
+        ..Default::default()
+    };
+    let states = find_states(&app_state.db, user_id, &filter).await?;
+
+    let mut calendar = String::new();
+    for (name, value) in [
+        ("BEGIN", "VCALENDAR"),
+        ("VERSION", "2.0"),
+        ("PRODID", "-//sleep-tracker//sleep feed//EN"),
+        ("CALSCALE", "GREGORIAN"),
+        ("METHOD", "PUBLISH"),
+        ("X-WR-CALNAME", "Sleep"),
+    ] {
+        calendar.push_str(&content_line(name, value));
+    }
+    for state in &states {
+        calendar.push_str(&event(state, now));
+    }
+    calendar.push_str(&content_line("END", "VCALENDAR"));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "inline; filename=\"sleep.ics\"",
+            ),
+        ],
+        calendar,
+    ))
+}
+
+/// A sleep state as a `VEVENT`.
+/// A sleep that is going on lasts until `now`, so that it shows up in the calendar already.
+fn event(state: &SleepState, now: DateTimeUtc) -> String {
+    let summary = match (state.end, state.kind) {
+        (None, _) => "Sleeping",
+        (Some(_), SleepKind::Nap) => "Nap",
+        (Some(_), _) => "Sleep",
+    };
+    let mut lines = vec![
+        content_line("BEGIN", "VEVENT"),
+        content_line("UID", &format!("{}@sleep", state.id)),
+        content_line("DTSTAMP", &ics_time(now)),
+        content_line("DTSTART", &ics_time(state.start)),
+        content_line(
+            "DTEND",
+            &ics_time(state.end.unwrap_or(now).max(state.start)),
+        ),
+        content_line("SUMMARY", summary),
+        content_line("TRANSP", "TRANSPARENT"),
+    ];
+    if let Some(comment) = &state.comment {
+        lines.push(content_line("DESCRIPTION", &escape_text(comment)));
+    }
+    if !state.tags.is_empty() {
+        let tags: Vec<String> = state
+            .tags
+            .iter()
+            .map(|tag| escape_text(&tag.name))
+            .collect();
+        lines.push(content_line("CATEGORIES", &tags.join(",")));
+    }
+    lines.push(content_line("END", "VEVENT"));
+    lines.concat()
+}
+
+/// A time in UTC, in the form iCalendar calls "form #2".
+fn ics_time(instant: DateTimeUtc) -> String {
+    instant.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape the characters that have a meaning in a text value, as in RFC 5545 section 3.3.11.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+/// A content line ending with CRLF, folded so that no line is longer than [`MAX_LINE_OCTETS`].
+/// Lines are only folded between characters, so that multi-byte characters stay whole.
+fn content_line(name: &str, value: &str) -> String {
+    let line = format!("{name}:{value}");
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut octets = 0;
+    for char in line.chars() {
+        if octets + char.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(char);
+        octets += char.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
@@ -1,19 +1,31 @@
-use api_types::{v1::SleepState, Snowflake};
+use api_types::{
+    v1::{SleepState, SubjectQuery},
+    Snowflake,
+};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
-use sqlx::query;
+use sqlx::query_as;
+
+use crate::{
+    v1::{settings::SleepContext, subjects::resolve_subject, ResultResponse},
+    AppState, RequireUser,
+};
 
-use crate::{datetime_utc_from_timestamp, v1::ResultResponse, AppState, RequireUser};
+use super::row::{load_state, SleepStateRow};
 
 pub async fn get_by_id(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
     Path(id): Path<Snowflake>,
 ) -> ResultResponse<Json<SleepState>> {
-    let row = query!(
-        "SELECT * FROM sleep_state WHERE user_id=? AND id=?",
+    let row = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL"#,
         conn_user.id,
         id
     )
@@ -21,12 +33,10 @@ pub async fn get_by_id(
     .await?;
 
     match row {
-        Some(row) => Ok(Json(SleepState {
-            id: row.id.into(),
-            start: datetime_utc_from_timestamp(row.started_at_unix_time),
-            end: row.ended_at_unix_time.map(datetime_utc_from_timestamp),
-            comment: row.comment,
-        })),
+        Some(row) => {
+            let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+            Ok(Json(load_state(&app_state.db, &context, row).await?))
+        }
         None => Err(crate::v1::ApiError::NotFound)?,
     }
 }
@@ -34,21 +44,25 @@ pub async fn get_by_id(
 pub async fn get_current(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
 ) -> ResultResponse<Json<SleepState>> {
-    let row = query!(
-        "SELECT * FROM sleep_state WHERE user_id=? AND ended_at_unix_time IS NULL",
-        conn_user.id,
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let row = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#,
+        subject_id,
     )
     .fetch_optional(&app_state.db)
     .await?;
 
     match row {
-        Some(row) => Ok(Json(SleepState {
-            id: row.id.into(),
-            start: datetime_utc_from_timestamp(row.started_at_unix_time),
-            end: row.ended_at_unix_time.map(datetime_utc_from_timestamp),
-            comment: row.comment,
-        })),
+        Some(row) => {
+            let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+            Ok(Json(load_state(&app_state.db, &context, row).await?))
+        }
         None => Err(crate::v1::ApiError::NotFound)?,
     }
 }
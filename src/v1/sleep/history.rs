@@ -0,0 +1,232 @@
+use api_types::{
+    v1::{DateTimeUtc, SleepState, SleepStateRevision},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as, SqliteConnection};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{settings::SleepContext, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::SleepChange,
+    interruptions::find_sleep,
+    row::{load_state, SleepStateRow},
+    update::other_sleep_going_on,
+};
+
+/// A row of the `sleep_state_revision` table.
+#[derive(Debug, Clone)]
+pub struct SleepStateRevisionRow {
+    pub id: i64,
+    pub sleep_state_id: i64,
+    pub changed_by_token_id: Option<i64>,
+    pub changed_at_unix_time: i64,
+    pub started_at_unix_time: i64,
+    pub ended_at_unix_time: Option<i64>,
+    pub comment: Option<String>,
+    pub kind: Option<String>,
+    pub quality: Option<i64>,
+    pub sleep_latency_minutes: Option<i64>,
+    pub awakenings: Option<i64>,
+    pub restedness: Option<i64>,
+    pub dream_recall: Option<String>,
+    pub auto_closed: i64,
+}
+
+impl SleepStateRevisionRow {
+    /// The values of the sleep state at this revision.
+    ///
+    /// Sleep states cannot be moved to another subject, so revisions do not record it,
+    /// and the subject is taken from the live sleep state instead.
+    fn values(&self, subject_id: Option<i64>) -> SleepStateRow {
+        SleepStateRow {
+            id: self.sleep_state_id,
+            started_at_unix_time: self.started_at_unix_time,
+            ended_at_unix_time: self.ended_at_unix_time,
+            comment: self.comment.clone(),
+            kind: self.kind.clone(),
+            quality: self.quality,
+            sleep_latency_minutes: self.sleep_latency_minutes,
+            awakenings: self.awakenings,
+            restedness: self.restedness,
+            dream_recall: self.dream_recall.clone(),
+            auto_closed: self.auto_closed,
+            deleted_at_unix_time: None,
+            change_sequence: 0,
+            subject_id,
+        }
+    }
+
+    /// The revision of a sleep state of the given subject.
+    pub fn into_api(self, subject_id: Option<i64>) -> SleepStateRevision {
+        let values = self.values(subject_id);
+        SleepStateRevision {
+            id: self.id.into(),
+            sleep_state_id: self.sleep_state_id.into(),
+            changed_at: datetime_utc_from_timestamp(self.changed_at_unix_time),
+            changed_by_token_id: self.changed_by_token_id.map(Snowflake::from),
+            start: datetime_utc_from_timestamp(self.started_at_unix_time),
+            end: self.ended_at_unix_time.map(datetime_utc_from_timestamp),
+            check_in: values.check_in(),
+            explicit_kind: values.explicit_kind(),
+            comment: self.comment,
+            auto_closed: self.auto_closed != 0,
+        }
+    }
+}
+
+/// Save the current values of a sleep state as a revision, right before they are changed.
+///
+/// This should run in the same transaction as the change, so that no change goes unrecorded.
+/// Returns whether the sleep state exists.
+pub async fn record_revision(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    sleep_state_id: Snowflake,
+    token_id: Option<Snowflake>,
+    changed_at: DateTimeUtc,
+) -> Result<bool, sqlx::Error> {
+    let id = Snowflake::new().await;
+    let changed_at = changed_at.timestamp();
+    let result = query!(
+        r#"INSERT INTO sleep_state_revision
+            (id, sleep_state_id, user_id, changed_by_token_id, changed_at_unix_time,
+                started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+                sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed)
+            SELECT ?, id, user_id, ?, ?,
+                started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+                sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed
+            FROM sleep_state
+            WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL"#,
+        id,
+        token_id,
+        changed_at,
+        user_id,
+        sleep_state_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Find the ID of the subject's sleep state that is not completed.
+pub async fn find_current_id(
+    conn: &mut SqliteConnection,
+    subject_id: Snowflake,
+) -> Result<Option<Snowflake>, sqlx::Error> {
+    let row = query!(
+        r#"SELECT id FROM sleep_state
+            WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#,
+        subject_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row.map(|row| row.id.into()))
+}
+
+/// The previous values of a sleep state, most recent first.
+pub async fn get_history(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Vec<SleepStateRevision>>> {
+    let sleep = find_sleep(&app_state.db, conn_user.id, id).await?;
+    let rows = query_as!(
+        SleepStateRevisionRow,
+        r#"SELECT id, sleep_state_id, changed_by_token_id, changed_at_unix_time,
+                started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+                sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed
+            FROM sleep_state_revision
+            WHERE sleep_state_id=?
+            ORDER BY changed_at_unix_time DESC, id DESC"#,
+        sleep.id,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| row.into_api(sleep.subject_id))
+            .collect(),
+    ))
+}
+
+/// Change a sleep state back to the values of one of its revisions.
+///
+/// The values before reverting are recorded as a new revision, so reverting can be undone too.
+/// This returns 409 if the revision is not completed, and another sleep state of the subject is going on.
+pub async fn revert_to_revision(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, conn_token)): RequireUser,
+    Path((id, revision_id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<Result<Json<SleepState>, StatusCode>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let sleep = find_sleep(&app_state.db, conn_user.id, id).await?;
+    let revision = query_as!(
+        SleepStateRevisionRow,
+        r#"SELECT id, sleep_state_id, changed_by_token_id, changed_at_unix_time,
+                started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+                sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed
+            FROM sleep_state_revision WHERE sleep_state_id=? AND id=?"#,
+        sleep.id,
+        revision_id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let mut tx = app_state.db.begin().await?;
+    if revision.ended_at_unix_time.is_none() && other_sleep_going_on(&mut tx, id).await? {
+        return Ok(Err(StatusCode::CONFLICT));
+    }
+    record_revision(
+        &mut tx,
+        conn_user.id,
+        id,
+        Some(conn_token.id),
+        app_state.clock.now(),
+    )
+    .await?;
+    query!(
+        r#"UPDATE sleep_state SET
+                started_at_unix_time=?,
+                ended_at_unix_time=?,
+                comment=?,
+                kind=?,
+                quality=?,
+                sleep_latency_minutes=?,
+                awakenings=?,
+                restedness=?,
+                dream_recall=?,
+                auto_closed=?
+            WHERE id=?"#,
+        revision.started_at_unix_time,
+        revision.ended_at_unix_time,
+        revision.comment,
+        revision.kind,
+        revision.quality,
+        revision.sleep_latency_minutes,
+        revision.awakenings,
+        revision.restedness,
+        revision.dream_recall,
+        revision.auto_closed,
+        id,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Updated(id));
+
+    let row = find_sleep(&app_state.db, conn_user.id, id).await?;
+    Ok(Ok(Json(load_state(&app_state.db, &context, row).await?)))
+}
@@ -0,0 +1,287 @@
+mod apple_health;
+mod csv;
+mod fitbit;
+mod generic_csv;
+mod sleep_as_android;
+
+use api_types::{
+    v1::{
+        DateTimeUtc, ImportedSleep, ImportedSleepStatus, SleepImportError, SleepImportFormat,
+        SleepImportQuery, SleepImportReport, SleepKind,
+    },
+    Snowflake,
+};
+use axum::{
+    body::Body,
+    extract::{Query, RawBody, State},
+    Json,
+};
+use chrono::{Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use futures_util::StreamExt;
+use http_body::{LengthLimitError, Limited};
+use sqlx::{query, SqliteConnection};
+use tokio::io::AsyncBufRead;
+use tokio_util::io::StreamReader;
+
+use crate::{
+    v1::{
+        settings::{LocalDay, SleepContext},
+        subjects::resolve_subject,
+        ApiError, ResultResponse,
+    },
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::SleepChange, check_in::CheckInColumns, create::insert_sleep_state,
+    update::SleepStateColumns,
+};
+
+/// The largest file that can be imported, for the formats that are read as a whole.
+const MAX_FILE_BYTES: usize = 16 * 1024 * 1024;
+
+/// The largest Apple Health export that can be imported.
+/// These are big, since they contain everything the phone ever measured,
+/// so they are read as they are uploaded instead of as a whole.
+const MAX_STREAMED_FILE_BYTES: usize = 2 * 1024 * 1024 * 1024;
+
+/// The longest sleep that is believed to be real, in hours.
+const MAX_SLEEP_HOURS: i64 = 48;
+
+/// A sleep read from a file, before it is checked against the sleep states that already exist.
+#[derive(Debug, Clone)]
+pub struct ParsedSleep {
+    pub record: u32,
+    pub start: DateTimeUtc,
+    pub end: DateTimeUtc,
+    pub explicit_kind: Option<SleepKind>,
+    pub comment: Option<String>,
+    pub quality: Option<u8>,
+}
+
+/// Everything that was read from a file.
+#[derive(Debug, Default)]
+pub struct ParsedFile {
+    pub sleeps: Vec<ParsedSleep>,
+    pub errors: Vec<SleepImportError>,
+}
+
+impl ParsedFile {
+    fn error(&mut self, record: u32, reason: impl Into<String>) {
+        self.errors.push(SleepImportError {
+            record,
+            reason: reason.into(),
+        });
+    }
+}
+
+/// Import sleep states from a file exported by another app.
+///
+/// Sleeps that overlap a sleep state that already exists, or one earlier in the file, are skipped,
+/// so importing the same file twice does not import anything the second time.
+/// Records that cannot be read are reported, and do not stop the others from being imported.
+pub async fn import_sleep(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(params): Query<SleepImportQuery>,
+    RawBody(body): RawBody,
+) -> ResultResponse<Json<SleepImportReport>> {
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, params.subject_id).await?;
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let local_day = match &params.timezone {
+        Some(timezone) => LocalDay {
+            tz: timezone
+                .parse::<Tz>()
+                .map_err(|_| ApiError::BadRequest(format!("unknown timezone {timezone:?}")))?,
+            ..context.local_day
+        },
+        None => context.local_day,
+    };
+
+    let mut parsed = match params.format {
+        SleepImportFormat::GenericCsv => {
+            generic_csv::parse(&read_text(body).await?, &local_day, &params)?
+        }
+        SleepImportFormat::SleepAsAndroid => {
+            sleep_as_android::parse(&read_text(body).await?, &local_day)?
+        }
+        SleepImportFormat::Fitbit => fitbit::parse(&read_text(body).await?, &local_day)?,
+        SleepImportFormat::AppleHealth => apple_health::parse(stream(body)).await?,
+    };
+    let mut sleeps = vec![];
+    for sleep in std::mem::take(&mut parsed.sleeps) {
+        match validate(&sleep) {
+            Ok(()) => sleeps.push(sleep),
+            Err(reason) => parsed.error(sleep.record, reason),
+        }
+    }
+    sleeps.sort_by_key(|sleep| (sleep.start, sleep.record));
+    parsed.errors.sort_by_key(|error| error.record);
+
+    let now = app_state.clock.now();
+    let mut tx = app_state.db.begin().await?;
+    let mut report = SleepImportReport {
+        dry_run: params.dry_run,
+        found_count: sleeps.len() as u32,
+        imported_count: 0,
+        duplicate_count: 0,
+        sleeps: vec![],
+        errors: parsed.errors,
+    };
+    // The sleep from the file that ends last so far, which a later one may overlap
+    let mut latest: Option<(DateTimeUtc, u32)> = None;
+    for sleep in sleeps {
+        let status = match latest {
+            Some((latest_end, record)) if sleep.start < latest_end => {
+                ImportedSleepStatus::DuplicateInFile { record }
+            }
+            _ => {
+                latest = Some((sleep.end, sleep.record));
+                match find_overlapping(&mut tx, subject_id, &sleep, now).await? {
+                    Some(id) => ImportedSleepStatus::DuplicateOfExisting { id },
+                    None if params.dry_run => ImportedSleepStatus::Imported { id: None },
+                    None => ImportedSleepStatus::Imported {
+                        id: Some(insert(&mut tx, conn_user.id, subject_id, &sleep).await?),
+                    },
+                }
+            }
+        };
+        match status {
+            ImportedSleepStatus::Imported { .. } => report.imported_count += 1,
+            _ => report.duplicate_count += 1,
+        }
+        report.sleeps.push(ImportedSleep {
+            record: sleep.record,
+            start: sleep.start,
+            end: sleep.end,
+            explicit_kind: sleep.explicit_kind,
+            comment: sleep.comment,
+            quality: sleep.quality,
+            status,
+        });
+    }
+    if !params.dry_run {
+        tx.commit().await?;
+        for sleep in &report.sleeps {
+            if let ImportedSleepStatus::Imported { id: Some(id) } = sleep.status {
+                app_state
+                    .sleep_events
+                    .publish(conn_user.id, SleepChange::Created(id));
+            }
+        }
+    }
+    Ok(Json(report))
+}
+
+/// Read a whole file as text, without the byte order mark that some apps put in front.
+async fn read_text(body: Body) -> Result<String, ApiError> {
+    let body = hyper::body::to_bytes(Limited::new(body, MAX_FILE_BYTES))
+        .await
+        .map_err(|err| match err.downcast_ref::<LengthLimitError>() {
+            Some(_) => {
+                ApiError::BadRequest(format!("the file is larger than {MAX_FILE_BYTES} bytes"))
+            }
+            None => ApiError::BadRequest(format!("cannot read the file: {err}")),
+        })?;
+    let text = String::from_utf8(body.into())
+        .map_err(|_| ApiError::BadRequest("the file must be encoded as UTF-8".to_string()))?;
+    Ok(match text.strip_prefix('\u{feff}') {
+        Some(text) => text.to_string(),
+        None => text,
+    })
+}
+
+/// Read a file as it is uploaded, failing once it is larger than [`MAX_STREAMED_FILE_BYTES`].
+fn stream(body: Body) -> impl AsyncBufRead + Unpin {
+    let mut read = 0;
+    StreamReader::new(body.map(move |chunk| {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        read += chunk.len();
+        if read > MAX_STREAMED_FILE_BYTES {
+            return Err(std::io::Error::other(format!(
+                "the file is larger than {MAX_STREAMED_FILE_BYTES} bytes"
+            )));
+        }
+        Ok(chunk)
+    }))
+}
+
+fn validate(sleep: &ParsedSleep) -> Result<(), String> {
+    if sleep.end <= sleep.start {
+        return Err("the sleep does not end after it starts".to_string());
+    }
+    if sleep.end - sleep.start > Duration::hours(MAX_SLEEP_HOURS) {
+        return Err(format!("the sleep is longer than {MAX_SLEEP_HOURS} hours"));
+    }
+    if let Some(quality) = sleep.quality {
+        if !(1..=5).contains(&quality) {
+            return Err(format!("quality must be between 1 and 5, not {quality}"));
+        }
+    }
+    Ok(())
+}
+
+/// Find a sleep state of the subject that overlaps the sleep.
+/// A sleep state that is going on lasts until `now`.
+async fn find_overlapping(
+    conn: &mut SqliteConnection,
+    subject_id: Snowflake,
+    sleep: &ParsedSleep,
+    now: DateTimeUtc,
+) -> Result<Option<Snowflake>, sqlx::Error> {
+    let start = sleep.start.timestamp();
+    let end = sleep.end.timestamp();
+    let now = now.timestamp();
+    let row = query!(
+        r#"SELECT id FROM sleep_state
+            WHERE subject_id=? AND deleted_at_unix_time IS NULL
+                AND started_at_unix_time<? AND COALESCE(ended_at_unix_time, ?)>?"#,
+        subject_id,
+        end,
+        now,
+        start,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row.map(|row| row.id.into()))
+}
+
+async fn insert(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    subject_id: Snowflake,
+    sleep: &ParsedSleep,
+) -> Result<Snowflake, sqlx::Error> {
+    let id = Snowflake::new().await;
+    let values = SleepStateColumns {
+        start: sleep.start.timestamp(),
+        end: Some(sleep.end.timestamp()),
+        comment: sleep.comment.clone(),
+        kind: sleep.explicit_kind.map(|kind| kind.to_string()),
+        check_in: CheckInColumns {
+            quality: sleep.quality.map(i64::from),
+            sleep_latency_minutes: None,
+            awakenings: None,
+            restedness: None,
+            dream_recall: None,
+        },
+    };
+    // A completed sleep can always be inserted
+    insert_sleep_state(conn, user_id, subject_id, id, &values).await?;
+    Ok(id)
+}
+
+/// Parse a time without a timezone in any of the formats, as wall clock time in the timezone.
+fn parse_local_time(text: &str, formats: &[&str], local_day: &LocalDay) -> Option<DateTimeUtc> {
+    formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text.trim(), format).ok())
+        .map(|local| local_day.instant_at(local))
+}
+
+/// An empty string becomes nothing, so that empty cells and fields are not imported as comments.
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,218 @@
+use api_types::v1::DateTimeUtc;
+use chrono::{DateTime, Duration, Utc};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use tokio::io::AsyncBufRead;
+
+use crate::v1::ApiError;
+
+use super::{ParsedFile, ParsedSleep};
+
+const SLEEP_ANALYSIS_TYPE: &str = "HKCategoryTypeIdentifierSleepAnalysis";
+
+/// The value of the records that say the user was awake, which are not part of a sleep.
+const AWAKE_VALUE: &str = "HKCategoryValueSleepAnalysisAwake";
+
+/// The format of the times in the export, like `2023-01-01 23:00:00 +0100`.
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// Records that are at most this far apart, in minutes, belong to the same sleep.
+const MERGE_GAP_MINUTES: i64 = 30;
+
+/// Read the sleep analysis records of an Apple Health export, as it is uploaded.
+///
+/// Apple Health has a record for every sleep stage, from every device, so they overlap a lot.
+/// Records that overlap or nearly touch are merged into a single sleep.
+/// The export can be far larger than what it takes to keep its sleeps,
+/// so it is read as a stream, and only the `Record` elements are looked at.
+pub async fn parse(reader: impl AsyncBufRead + Unpin) -> Result<ParsedFile, ApiError> {
+    let mut reader = Reader::from_reader(reader);
+    // Some versions of iOS write exports with mismatched tags, which are of no concern here
+    reader.check_end_names(false);
+    let mut parsed = ParsedFile::default();
+    let mut intervals: Vec<(DateTimeUtc, DateTimeUtc, u32)> = vec![];
+    let mut record = 0;
+    let mut buf = vec![];
+    loop {
+        let element = match reader.read_event_into_async(&mut buf).await {
+            Ok(Event::Start(element) | Event::Empty(element)) => element,
+            Ok(Event::Eof) => break,
+            Ok(_) => {
+                buf.clear();
+                continue;
+            }
+            Err(err) => {
+                return Err(ApiError::BadRequest(format!(
+                    "cannot read the file at byte {}: {err}",
+                    reader.buffer_position()
+                )))
+            }
+        };
+        if element.name().as_ref() == b"Record" {
+            let record_type = attribute(&element, "type")?;
+            if record_type.as_deref() == Some(SLEEP_ANALYSIS_TYPE) {
+                record += 1;
+                if attribute(&element, "value")?.as_deref() != Some(AWAKE_VALUE) {
+                    let time = |name: &str| -> Result<Option<DateTimeUtc>, ApiError> {
+                        Ok(attribute(&element, name)?
+                            .and_then(|time| DateTime::parse_from_str(&time, TIME_FORMAT).ok())
+                            .map(|time| time.with_timezone(&Utc)))
+                    };
+                    match (time("startDate")?, time("endDate")?) {
+                        (Some(start), Some(end)) => intervals.push((start, end, record)),
+                        _ => parsed.error(record, "cannot read the start and end dates"),
+                    }
+                }
+            }
+        }
+        buf.clear();
+    }
+
+    intervals.sort();
+    let mut merged: Vec<(DateTimeUtc, DateTimeUtc, u32)> = vec![];
+    for (start, end, record) in intervals {
+        match merged.last_mut() {
+            Some((_, last_end, last_record))
+                if start <= *last_end + Duration::minutes(MERGE_GAP_MINUTES) =>
+            {
+                *last_end = (*last_end).max(end);
+                *last_record = (*last_record).min(record);
+            }
+            _ => merged.push((start, end, record)),
+        }
+    }
+    parsed.sleeps = merged
+        .into_iter()
+        .map(|(start, end, record)| ParsedSleep {
+            record,
+            start,
+            end,
+            explicit_kind: None,
+            comment: None,
+            quality: None,
+        })
+        .collect();
+    Ok(parsed)
+}
+
+/// The value of an attribute of an element, with its references replaced.
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, ApiError> {
+    let invalid = |err: quick_xml::Error| {
+        ApiError::BadRequest(format!(
+            "the {name} attribute of a Record element is not valid: {err}"
+        ))
+    };
+    let Some(attribute) = element.try_get_attribute(name).map_err(invalid)? else {
+        return Ok(None);
+    };
+    Ok(Some(
+        attribute.unescape_value().map_err(invalid)?.into_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(time: &str) -> DateTimeUtc {
+        time.parse().unwrap()
+    }
+
+    fn intervals(parsed: &ParsedFile) -> Vec<(DateTimeUtc, DateTimeUtc, u32)> {
+        parsed
+            .sleeps
+            .iter()
+            .map(|sleep| (sleep.start, sleep.end, sleep.record))
+            .collect()
+    }
+
+    fn sleep_record(start: &str, end: &str, value: &str) -> String {
+        format!(
+            r#"<Record type="{SLEEP_ANALYSIS_TYPE}" sourceName="Watch" startDate="{start}" endDate="{end}" value="{value}"/>"#
+        )
+    }
+
+    #[tokio::test]
+    async fn reads_an_export() {
+        let file = include_bytes!("fixtures/apple_health.xml");
+        let parsed = parse(&file[..]).await.unwrap();
+
+        assert_eq!(
+            intervals(&parsed),
+            vec![
+                // The stages of the night, within the time in bed of the phone
+                (utc("2023-10-06T21:04:00Z"), utc("2023-10-07T04:58:00Z"), 1),
+                // A nap in the afternoon
+                (utc("2023-10-07T12:05:00Z"), utc("2023-10-07T12:35:00Z"), 5),
+                // The next night, after flying to New York
+                (utc("2023-10-08T03:30:00Z"), utc("2023-10-08T11:10:00Z"), 6),
+            ]
+        );
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].record, 7);
+    }
+
+    #[tokio::test]
+    async fn merges_stages_across_short_awake_gaps() {
+        let file = format!(
+            "<HealthData>{}{}{}{}</HealthData>",
+            sleep_record(
+                "2023-10-06 23:00:00 +0000",
+                "2023-10-07 01:00:00 +0000",
+                "HKCategoryValueSleepAnalysisAsleepCore"
+            ),
+            sleep_record(
+                "2023-10-07 01:00:00 +0000",
+                "2023-10-07 01:20:00 +0000",
+                AWAKE_VALUE
+            ),
+            // Back to sleep 20 minutes later, which is still the same sleep
+            sleep_record(
+                "2023-10-07 01:20:00 +0000",
+                "2023-10-07 03:00:00 +0000",
+                "HKCategoryValueSleepAnalysisAsleepREM"
+            ),
+            // Up for 45 minutes, which starts another sleep
+            sleep_record(
+                "2023-10-07 03:45:00 +0000",
+                "2023-10-07 06:00:00 +0000",
+                "HKCategoryValueSleepAnalysisAsleepCore"
+            ),
+        );
+        let parsed = parse(file.as_bytes()).await.unwrap();
+
+        assert_eq!(
+            intervals(&parsed),
+            vec![
+                (utc("2023-10-06T23:00:00Z"), utc("2023-10-07T03:00:00Z"), 1),
+                (utc("2023-10-07T03:45:00Z"), utc("2023-10-07T06:00:00Z"), 4),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn tolerates_mismatched_end_tags() {
+        let file = format!(
+            "<HealthData><Workout>{}</Workouts></HealthData>",
+            sleep_record(
+                "2023-10-06 23:00:00 +0000",
+                "2023-10-07 06:00:00 +0000",
+                "HKCategoryValueSleepAnalysisAsleepCore"
+            ),
+        );
+        let parsed = parse(file.as_bytes()).await.unwrap();
+        assert_eq!(parsed.sleeps.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_broken_records() {
+        let unquoted =
+            r#"<HealthData><Record type=HKCategoryTypeIdentifierSleepAnalysis/></HealthData>"#;
+        assert!(parse(unquoted.as_bytes()).await.is_err());
+
+        let cut_off = r#"<HealthData><Record type="HKCategoryTypeIdentifierSleepAnalysis"#;
+        assert!(parse(cut_off.as_bytes()).await.is_err());
+    }
+}
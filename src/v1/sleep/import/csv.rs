@@ -0,0 +1,149 @@
+/// A row of a CSV file, with the line it starts on.
+#[derive(Debug, Clone)]
+pub struct CsvRecord {
+    pub line: u32,
+    pub fields: Vec<String>,
+}
+
+/// Read the records of a CSV file as in RFC 4180.
+///
+/// Quoted fields may contain commas, doubled quotes and line breaks.
+/// Rows may have different numbers of fields, and lines with nothing on them are skipped.
+pub fn read_records(text: &str) -> Result<Vec<CsvRecord>, String> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut records = vec![];
+    // The reader counts lines differently after blank lines and lone carriage returns,
+    // so the line a record starts on is counted from its byte position instead
+    let mut line = 1;
+    let mut counted_up_to = 0;
+    for record in reader.records() {
+        let record = record.map_err(|err| format!("cannot read the CSV file: {err}"))?;
+        let position = record
+            .position()
+            .map_or(0, |position| position.byte() as usize);
+        let start = text.len() - text[position..].trim_start_matches(['\r', '\n']).len();
+        line += count_line_breaks(&text[counted_up_to..start]);
+        counted_up_to = start;
+        let is_empty = record.len() == 1 && record[0].trim().is_empty();
+        if is_empty {
+            continue;
+        }
+        records.push(CsvRecord {
+            line,
+            fields: record.iter().map(str::to_string).collect(),
+        });
+    }
+    Ok(records)
+}
+
+/// How many line breaks there are in the text, where `\r\n`, `\n` and `\r` each count as one.
+fn count_line_breaks(text: &str) -> u32 {
+    let crlf = text.matches("\r\n").count();
+    (text.matches('\n').count() + text.matches('\r').count() - crlf) as u32
+}
+
+/// Find the position of a column by its header, ignoring case and surrounding spaces.
+pub fn find_column(header: &[String], name: &str) -> Option<usize> {
+    header
+        .iter()
+        .position(|column| column.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(records: &[CsvRecord]) -> Vec<(u32, Vec<&str>)> {
+        records
+            .iter()
+            .map(|record| {
+                (
+                    record.line,
+                    record.fields.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_quoted_fields() {
+        let text = "start,end,comment\r\n\
+            2023-10-06 23:00,2023-10-07 07:00,\"slept well, for once\"\r\n\
+            2023-10-07 23:30,2023-10-08 06:45,\"the \"\"good\"\" pillow\"\r\n";
+        let records = read_records(text).unwrap();
+        assert_eq!(
+            fields(&records),
+            vec![
+                (1, vec!["start", "end", "comment"]),
+                (
+                    2,
+                    vec![
+                        "2023-10-06 23:00",
+                        "2023-10-07 07:00",
+                        "slept well, for once"
+                    ]
+                ),
+                (
+                    3,
+                    vec![
+                        "2023-10-07 23:30",
+                        "2023-10-08 06:45",
+                        "the \"good\" pillow"
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_line_numbers_across_embedded_newlines() {
+        let text = "start,comment\n\
+            2023-10-06 23:00,\"woke up twice:\nonce at 2,\nonce at 4\"\n\
+            \n\
+            2023-10-07 23:30,short\n";
+        let records = read_records(text).unwrap();
+        assert_eq!(
+            fields(&records),
+            vec![
+                (1, vec!["start", "comment"]),
+                (
+                    2,
+                    vec!["2023-10-06 23:00", "woke up twice:\nonce at 2,\nonce at 4"]
+                ),
+                // The empty line is skipped, but still counted
+                (6, vec!["2023-10-07 23:30", "short"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_any_kind_of_line_break() {
+        let records = read_records("a\r\n\r\nb\rc\n\nd").unwrap();
+        let lines: Vec<u32> = records.iter().map(|record| record.line).collect();
+        assert_eq!(lines, vec![1, 3, 4, 6]);
+    }
+
+    #[test]
+    fn allows_rows_of_any_length() {
+        let records = read_records("a,b,c\n1\n1,2,3,4\n").unwrap();
+        assert_eq!(
+            fields(&records),
+            vec![
+                (1, vec!["a", "b", "c"]),
+                (2, vec!["1"]),
+                (3, vec!["1", "2", "3", "4"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_columns_by_header() {
+        let header = vec![" Start ".to_string(), "END".to_string()];
+        assert_eq!(find_column(&header, "start"), Some(0));
+        assert_eq!(find_column(&header, "end"), Some(1));
+        assert_eq!(find_column(&header, "comment"), None);
+    }
+}
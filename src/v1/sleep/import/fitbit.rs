@@ -0,0 +1,152 @@
+use api_types::v1::SleepKind;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::v1::{settings::LocalDay, ApiError};
+
+use super::{parse_local_time, ParsedFile, ParsedSleep};
+
+/// The format of the times in the export, which are in the local time of the tracker.
+const TIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S"];
+
+/// A sleep log, with only the fields that are imported.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SleepLog {
+    start_time: String,
+    end_time: String,
+    /// Called `isMainSleep` in the Web API, and `mainSleep` in the data export
+    #[serde(alias = "isMainSleep")]
+    main_sleep: Option<bool>,
+}
+
+/// Read the sleep logs of a Fitbit data export.
+///
+/// This is either a list of logs, as in the `sleep-<date>.json` files,
+/// or an object with the list in `sleep`, as returned by the Web API.
+pub fn parse(text: &str, local_day: &LocalDay) -> Result<ParsedFile, ApiError> {
+    let json: Value = serde_json::from_str(text)
+        .map_err(|err| ApiError::BadRequest(format!("the file is not valid JSON: {err}")))?;
+    let logs = match json {
+        Value::Array(logs) => logs,
+        Value::Object(mut object) => match object.remove("sleep") {
+            Some(Value::Array(logs)) => logs,
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "the file has no list of sleep logs".to_string(),
+                ))
+            }
+        },
+        _ => {
+            return Err(ApiError::BadRequest(
+                "the file has no list of sleep logs".to_string(),
+            ))
+        }
+    };
+
+    let mut parsed = ParsedFile::default();
+    for (index, log) in logs.into_iter().enumerate() {
+        let record = index as u32 + 1;
+        let log: SleepLog = match serde_json::from_value(log) {
+            Ok(log) => log,
+            Err(err) => {
+                parsed.error(record, format!("not a sleep log: {err}"));
+                continue;
+            }
+        };
+        let Some(start) = parse_local_time(&log.start_time, TIME_FORMATS, local_day) else {
+            parsed.error(
+                record,
+                format!("cannot read the start time {:?}", log.start_time),
+            );
+            continue;
+        };
+        let Some(end) = parse_local_time(&log.end_time, TIME_FORMATS, local_day) else {
+            parsed.error(
+                record,
+                format!("cannot read the end time {:?}", log.end_time),
+            );
+            continue;
+        };
+        parsed.sleeps.push(ParsedSleep {
+            record,
+            start,
+            end,
+            explicit_kind: log.main_sleep.map(|main_sleep| {
+                if main_sleep {
+                    SleepKind::Main
+                } else {
+                    SleepKind::Nap
+                }
+            }),
+            comment: None,
+            quality: None,
+        });
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use api_types::v1::DateTimeUtc;
+
+    use super::*;
+
+    fn utc(time: &str) -> DateTimeUtc {
+        time.parse().unwrap()
+    }
+
+    fn london() -> LocalDay {
+        LocalDay {
+            tz: chrono_tz::Europe::London,
+            day_boundary_hour: 12,
+        }
+    }
+
+    #[test]
+    fn reads_an_export() {
+        let file = include_str!("fixtures/fitbit.json");
+        let parsed = parse(file, &london()).unwrap();
+
+        let sleeps: Vec<_> = parsed
+            .sleeps
+            .iter()
+            .map(|sleep| (sleep.record, sleep.start, sleep.end, sleep.explicit_kind))
+            .collect();
+        // The times are in British Summer Time
+        assert_eq!(
+            sleeps,
+            vec![
+                (
+                    1,
+                    utc("2023-10-06T22:18:30Z"),
+                    utc("2023-10-07T06:02:00Z"),
+                    Some(SleepKind::Main)
+                ),
+                (
+                    2,
+                    utc("2023-10-07T13:12:00Z"),
+                    utc("2023-10-07T13:58:00Z"),
+                    Some(SleepKind::Nap)
+                ),
+            ]
+        );
+        let errors: Vec<_> = parsed.errors.iter().map(|error| error.record).collect();
+        assert_eq!(errors, vec![3, 4]);
+    }
+
+    #[test]
+    fn reads_a_web_api_response() {
+        let file = r#"{"sleep": [{"startTime": "2023-12-01T23:00:00.000", "endTime": "2023-12-02T07:00:00.000", "isMainSleep": true}], "summary": {}}"#;
+        let parsed = parse(file, &london()).unwrap();
+        assert_eq!(parsed.sleeps.len(), 1);
+        assert_eq!(parsed.sleeps[0].start, utc("2023-12-01T23:00:00Z"));
+        assert_eq!(parsed.sleeps[0].explicit_kind, Some(SleepKind::Main));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse("logId,startTime\n1,2023-12-01", &london()).is_err());
+        assert!(parse(r#"{"activities": []}"#, &london()).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use api_types::v1::{DateTimeUtc, SleepImportQuery};
+use chrono::{DateTime, Utc};
+
+use crate::v1::{settings::LocalDay, ApiError};
+
+use super::{
+    csv::{find_column, read_records},
+    non_empty, parse_local_time, ParsedFile, ParsedSleep,
+};
+
+/// The formats of times without a timezone that are recognized.
+const LOCAL_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+];
+
+/// Read a CSV file with a header row, using the columns given in the query.
+///
+/// Times may be in RFC 3339 format, or in one of [`LOCAL_TIME_FORMATS`] in the timezone of the import.
+pub fn parse(
+    text: &str,
+    local_day: &LocalDay,
+    params: &SleepImportQuery,
+) -> Result<ParsedFile, ApiError> {
+    let mut records = read_records(text)
+        .map_err(ApiError::BadRequest)?
+        .into_iter();
+    let Some(header) = records.next() else {
+        return Ok(ParsedFile::default());
+    };
+    let column = |name: &Option<String>, param: &str| -> Result<Option<usize>, ApiError> {
+        match name {
+            None => Ok(None),
+            Some(name) => find_column(&header.fields, name).map(Some).ok_or_else(|| {
+                ApiError::BadRequest(format!("the file has no column {name:?} for {param}"))
+            }),
+        }
+    };
+    let (Some(start_column), Some(end_column)) = (
+        column(&params.start_column, "start_column")?,
+        column(&params.end_column, "end_column")?,
+    ) else {
+        return Err(ApiError::BadRequest(
+            "start_column and end_column are needed to import generic CSV".to_string(),
+        ));
+    };
+    let comment_column = column(&params.comment_column, "comment_column")?;
+    let quality_column = column(&params.quality_column, "quality_column")?;
+
+    let mut parsed = ParsedFile::default();
+    for record in records {
+        let field = |index: usize| record.fields.get(index).map_or("", String::as_str);
+        let Some(start) = parse_time(field(start_column), local_day) else {
+            parsed.error(
+                record.line,
+                format!("cannot read the start time {:?}", field(start_column)),
+            );
+            continue;
+        };
+        let Some(end) = parse_time(field(end_column), local_day) else {
+            parsed.error(
+                record.line,
+                format!("cannot read the end time {:?}", field(end_column)),
+            );
+            continue;
+        };
+        let quality = match quality_column.map(field).and_then(non_empty) {
+            None => None,
+            Some(quality) => match quality.parse::<f64>() {
+                Ok(quality) => Some(quality.round().clamp(0.0, u8::MAX as f64) as u8),
+                Err(_) => {
+                    parsed.error(record.line, format!("cannot read the quality {quality:?}"));
+                    continue;
+                }
+            },
+        };
+        parsed.sleeps.push(ParsedSleep {
+            record: record.line,
+            start,
+            end,
+            explicit_kind: None,
+            comment: comment_column.map(field).and_then(non_empty),
+            quality,
+        });
+    }
+    Ok(parsed)
+}
+
+fn parse_time(text: &str, local_day: &LocalDay) -> Option<DateTimeUtc> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| parse_local_time(text, LOCAL_TIME_FORMATS, local_day))
+}
+
+#[cfg(test)]
+mod tests {
+    use api_types::v1::SleepImportFormat;
+
+    use super::*;
+
+    fn utc(time: &str) -> DateTimeUtc {
+        time.parse().unwrap()
+    }
+
+    fn query() -> SleepImportQuery {
+        SleepImportQuery {
+            format: SleepImportFormat::GenericCsv,
+            dry_run: false,
+            timezone: None,
+            start_column: Some("went to bed".to_string()),
+            end_column: Some("Woke up".to_string()),
+            comment_column: Some("Notes".to_string()),
+            quality_column: Some("Quality".to_string()),
+            subject_id: None,
+        }
+    }
+
+    fn berlin() -> LocalDay {
+        LocalDay {
+            tz: chrono_tz::Europe::Berlin,
+            day_boundary_hour: 12,
+        }
+    }
+
+    #[test]
+    fn reads_a_file() {
+        let file = include_str!("fixtures/generic.csv");
+        let parsed = parse(file, &berlin(), &query()).unwrap();
+
+        let sleeps: Vec<_> = parsed
+            .sleeps
+            .iter()
+            .map(|sleep| {
+                (
+                    sleep.record,
+                    sleep.start,
+                    sleep.end,
+                    sleep.quality,
+                    sleep.comment.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sleeps,
+            vec![
+                (
+                    2,
+                    utc("2023-03-24T22:10:00Z"),
+                    utc("2023-03-25T06:05:00Z"),
+                    Some(4),
+                    None
+                ),
+                // The clocks went forward in the night, so the sleep ends at UTC+2
+                (
+                    3,
+                    utc("2023-03-25T22:40:00Z"),
+                    utc("2023-03-26T05:20:00Z"),
+                    Some(4),
+                    Some("clocks went forward, felt it")
+                ),
+                (
+                    4,
+                    utc("2023-03-26T20:55:00Z"),
+                    utc("2023-03-27T04:30:00Z"),
+                    Some(5),
+                    Some("dreamt of \"the sea\"\nand woke up at 3")
+                ),
+            ]
+        );
+        // The comment of line 4 goes on to line 5, so the next record is on line 6
+        let errors: Vec<_> = parsed.errors.iter().map(|error| error.record).collect();
+        assert_eq!(errors, vec![6, 7]);
+    }
+
+    #[test]
+    fn needs_the_columns_to_exist() {
+        let file = include_str!("fixtures/generic.csv");
+        let mut query = query();
+        query.comment_column = Some("Comment".to_string());
+        assert!(parse(file, &berlin(), &query).is_err());
+
+        let mut query = self::query();
+        query.end_column = None;
+        assert!(parse(file, &berlin(), &query).is_err());
+    }
+
+    #[test]
+    fn an_empty_file_has_nothing() {
+        let parsed = parse("", &berlin(), &query()).unwrap();
+        assert!(parsed.sleeps.is_empty());
+        assert!(parsed.errors.is_empty());
+    }
+}
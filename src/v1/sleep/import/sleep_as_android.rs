@@ -0,0 +1,185 @@
+use chrono_tz::Tz;
+
+use crate::v1::{settings::LocalDay, ApiError};
+
+use super::{
+    csv::{find_column, read_records, CsvRecord},
+    non_empty, parse_local_time, ParsedFile, ParsedSleep,
+};
+
+/// The format of the times in the backup, like `16. 12. 2014 22:49`.
+const TIME_FORMATS: &[&str] = &["%d. %m. %Y %H:%M"];
+
+/// The columns of a sleep record, as found in the header row before it.
+struct Columns {
+    tz: usize,
+    from: usize,
+    to: usize,
+    rating: Option<usize>,
+    comment: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &CsvRecord) -> Result<Self, String> {
+        let required = |name: &str| {
+            find_column(&header.fields, name)
+                .ok_or_else(|| format!("the header has no {name} column"))
+        };
+        Ok(Self {
+            tz: required("Tz")?,
+            from: required("From")?,
+            to: required("To")?,
+            rating: find_column(&header.fields, "Rating"),
+            comment: find_column(&header.fields, "Comment"),
+        })
+    }
+}
+
+/// Read a Sleep as Android backup.
+///
+/// Every sleep record comes after a header row starting with `Id`, whose columns it follows.
+/// Other rows, like the ones with the events of a sleep, are skipped.
+/// Each record has its own timezone; the one of the import is only used if it is missing.
+pub fn parse(text: &str, local_day: &LocalDay) -> Result<ParsedFile, ApiError> {
+    let records = read_records(text).map_err(ApiError::BadRequest)?;
+    let mut parsed = ParsedFile::default();
+    let mut columns: Option<Columns> = None;
+    for record in records {
+        let first = record.fields[0].trim();
+        if first == "Id" {
+            match Columns::from_header(&record) {
+                Ok(header_columns) => columns = Some(header_columns),
+                Err(reason) => {
+                    parsed.error(record.line, reason);
+                    columns = None;
+                }
+            }
+            continue;
+        }
+        let Some(columns) = &columns else {
+            continue;
+        };
+        if first.parse::<i64>().is_err() {
+            continue;
+        }
+
+        let field = |index: usize| record.fields.get(index).map_or("", String::as_str);
+        let record_day = match field(columns.tz).trim() {
+            "" => *local_day,
+            timezone => match timezone.parse::<Tz>() {
+                Ok(tz) => LocalDay { tz, ..*local_day },
+                Err(_) => {
+                    parsed.error(record.line, format!("unknown timezone {timezone:?}"));
+                    continue;
+                }
+            },
+        };
+        let Some(start) = parse_local_time(field(columns.from), TIME_FORMATS, &record_day) else {
+            parsed.error(
+                record.line,
+                format!("cannot read the start time {:?}", field(columns.from)),
+            );
+            continue;
+        };
+        let Some(end) = parse_local_time(field(columns.to), TIME_FORMATS, &record_day) else {
+            parsed.error(
+                record.line,
+                format!("cannot read the end time {:?}", field(columns.to)),
+            );
+            continue;
+        };
+        // Ratings go from 0 to 5 in half stars, and 0 means that the sleep was not rated
+        let quality = columns
+            .rating
+            .and_then(|rating| field(rating).trim().parse::<f64>().ok())
+            .filter(|rating| *rating > 0.0)
+            .map(|rating| rating.round().clamp(1.0, 5.0) as u8);
+        parsed.sleeps.push(ParsedSleep {
+            record: record.line,
+            start,
+            end,
+            explicit_kind: None,
+            comment: columns.comment.map(field).and_then(non_empty),
+            quality,
+        });
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use api_types::v1::DateTimeUtc;
+
+    use super::*;
+
+    fn utc(time: &str) -> DateTimeUtc {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn reads_a_backup() {
+        let file = include_str!("fixtures/sleep_as_android.csv");
+        // Only used for the record without a timezone
+        let local_day = LocalDay {
+            tz: chrono_tz::Asia::Tokyo,
+            day_boundary_hour: 12,
+        };
+        let parsed = parse(file, &local_day).unwrap();
+
+        let sleeps: Vec<_> = parsed
+            .sleeps
+            .iter()
+            .map(|sleep| {
+                (
+                    sleep.record,
+                    sleep.start,
+                    sleep.end,
+                    sleep.quality,
+                    sleep.comment.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sleeps,
+            vec![
+                (
+                    2,
+                    utc("2023-10-06T20:44:00Z"),
+                    utc("2023-10-07T04:51:00Z"),
+                    Some(5),
+                    Some("#home")
+                ),
+                // Not rated, in the timezone of the record
+                (
+                    5,
+                    utc("2023-10-08T02:02:00Z"),
+                    utc("2023-10-08T09:40:00Z"),
+                    None,
+                    None
+                ),
+                (
+                    8,
+                    utc("2023-10-09T14:30:00Z"),
+                    utc("2023-10-09T22:15:00Z"),
+                    Some(2),
+                    Some("#travel #jetlag")
+                ),
+            ]
+        );
+        let errors: Vec<_> = parsed.errors.iter().map(|error| error.record).collect();
+        assert_eq!(errors, vec![7, 9]);
+    }
+
+    #[test]
+    fn reports_headers_without_the_needed_columns() {
+        let file = "Id,From,To\n\"1\",\"6. 10. 2023 22:44\",\"7. 10. 2023 6:51\"\n";
+        let local_day = LocalDay {
+            tz: chrono_tz::UTC,
+            day_boundary_hour: 12,
+        };
+        let parsed = parse(file, &local_day).unwrap();
+        assert!(parsed.sleeps.is_empty());
+        let errors: Vec<_> = parsed.errors.iter().map(|error| error.record).collect();
+        assert_eq!(errors, vec![1]);
+    }
+}
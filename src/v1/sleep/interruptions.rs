@@ -0,0 +1,139 @@
+mod create;
+mod delete;
+mod get;
+mod list;
+mod update;
+
+pub use create::{create_interruption, wake_up_now};
+pub use delete::{delete_current_interruption, delete_interruption};
+pub use get::{get_current_interruption, get_interruption};
+pub use list::{list_current_interruptions, list_interruptions};
+pub use update::{end_current_interruption, put_interruption};
+
+use api_types::{v1::SleepInterruption, Snowflake};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{subjects::resolve_subject, ApiError},
+};
+
+use super::row::SleepStateRow;
+
+/// A row of the `sleep_interruption` table, as returned by `SELECT *`.
+#[derive(Debug, Clone)]
+pub struct InterruptionRow {
+    pub id: i64,
+    pub sleep_state_id: i64,
+    pub started_at_unix_time: i64,
+    pub ended_at_unix_time: Option<i64>,
+    pub reason: Option<String>,
+}
+
+impl InterruptionRow {
+    pub fn into_api(self) -> SleepInterruption {
+        SleepInterruption {
+            id: self.id.into(),
+            sleep_state_id: self.sleep_state_id.into(),
+            start: datetime_utc_from_timestamp(self.started_at_unix_time),
+            end: self.ended_at_unix_time.map(datetime_utc_from_timestamp),
+            reason: self.reason,
+        }
+    }
+}
+
+/// Find a sleep state by ID, making sure that it belongs to the user.
+pub(super) async fn find_sleep(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<SleepStateRow, ApiError> {
+    query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL"#,
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// Find the sleep state of one of the user's subjects that is not completed.
+async fn find_current_sleep(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    subject_id: Option<Snowflake>,
+) -> Result<SleepStateRow, ApiError> {
+    let subject_id = resolve_subject(db, user_id, subject_id).await?;
+    query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE subject_id=? AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#,
+        subject_id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// Check that an interruption with the given times fits inside the sleep.
+fn validate_times(sleep: &SleepStateRow, start: i64, end: Option<i64>) -> Result<(), ApiError> {
+    if start < sleep.started_at_unix_time {
+        return Err(ApiError::BadRequest(
+            "interruption cannot start before the sleep".to_string(),
+        ));
+    }
+    if let Some(end) = end {
+        if end < start {
+            return Err(ApiError::BadRequest(
+                "interruption cannot end before it starts".to_string(),
+            ));
+        }
+    }
+    if let Some(sleep_end) = sleep.ended_at_unix_time {
+        match end {
+            None => {
+                return Err(ApiError::BadRequest(
+                    "interruption of a completed sleep must have an end".to_string(),
+                ))
+            }
+            Some(end) if end > sleep_end => {
+                return Err(ApiError::BadRequest(
+                    "interruption cannot end after the sleep".to_string(),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether an interruption from `start` to `end` would overlap another interruption of the sleep than `id`.
+/// Interruptions that are going on have no end yet, and ones that only touch at the ends don't overlap.
+async fn overlaps_other(
+    db: &SqlitePool,
+    sleep_id: i64,
+    id: Snowflake,
+    start: i64,
+    end: Option<i64>,
+) -> Result<bool, sqlx::Error> {
+    let row = query!(
+        r#"SELECT id FROM sleep_interruption
+            WHERE sleep_state_id=?1 AND id!=?2
+                AND (ended_at_unix_time IS NULL OR ended_at_unix_time>?3)
+                AND (?4 IS NULL OR started_at_unix_time<?4)"#,
+        sleep_id,
+        id,
+        start,
+        end,
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row.is_some())
+}
@@ -0,0 +1,102 @@
+use api_types::{
+    v1::{NewSleepInterruption, SleepInterruption, SubjectQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{sleep::SleepChange, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_current_sleep, find_sleep, overlaps_other, validate_times};
+
+pub async fn create_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(sleep_id): Path<Snowflake>,
+    Json(new_interruption): Json<NewSleepInterruption>,
+) -> ResultResponse<Result<(StatusCode, Json<SleepInterruption>), StatusCode>> {
+    let sleep = find_sleep(&app_state.db, conn_user.id, sleep_id).await?;
+    let start = new_interruption.start.timestamp();
+    let end = new_interruption.end.map(|end| end.timestamp());
+    validate_times(&sleep, start, end)?;
+
+    let id = Snowflake::new().await;
+    if overlaps_other(&app_state.db, sleep.id, id, start, end).await? {
+        return Ok(Err(StatusCode::CONFLICT));
+    }
+    query!(
+        r#"INSERT INTO sleep_interruption
+            (id, sleep_state_id, started_at_unix_time, ended_at_unix_time, reason)
+            VALUES (?,?,?,?,?)"#,
+        id,
+        sleep.id,
+        start,
+        end,
+        new_interruption.reason,
+    )
+    .execute(&app_state.db)
+    .await?;
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Updated(sleep_id));
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(SleepInterruption {
+            id,
+            sleep_state_id: sleep_id,
+            start: new_interruption.start,
+            end: new_interruption.end,
+            reason: new_interruption.reason,
+        }),
+    )))
+}
+
+/// "I'm awake": start an interruption of the current sleep now, or 409 if one is already going on.
+pub async fn wake_up_now(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<Result<(StatusCode, Json<SleepInterruption>), StatusCode>> {
+    let sleep = find_current_sleep(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let id = Snowflake::new().await;
+    let start = app_state.clock.now().timestamp();
+    if overlaps_other(&app_state.db, sleep.id, id, start, None).await? {
+        return Ok(Err(StatusCode::CONFLICT));
+    }
+
+    query!(
+        r#"INSERT INTO sleep_interruption
+            (id, sleep_state_id, started_at_unix_time, ended_at_unix_time, reason)
+            VALUES (?,?,?,?,?)"#,
+        id,
+        sleep.id,
+        start,
+        Option::<i64>::None,
+        Option::<String>::None,
+    )
+    .execute(&app_state.db)
+    .await?;
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Updated(sleep.id.into()));
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(SleepInterruption {
+            id,
+            sleep_state_id: sleep.id.into(),
+            start: datetime_utc_from_timestamp(start),
+            end: None,
+            reason: None,
+        }),
+    )))
+}
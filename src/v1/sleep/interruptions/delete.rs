@@ -0,0 +1,62 @@
+use api_types::{v1::SubjectQuery, Snowflake};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{sleep::SleepChange, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_current_sleep, find_sleep};
+
+pub async fn delete_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((sleep_id, id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<StatusCode> {
+    let sleep = find_sleep(&app_state.db, conn_user.id, sleep_id).await?;
+    let row = query!(
+        "DELETE FROM sleep_interruption WHERE sleep_state_id=? AND id=? RETURNING id",
+        sleep.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => {
+            app_state
+                .sleep_events
+                .publish(conn_user.id, SleepChange::Updated(sleep.id.into()));
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn delete_current_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<StatusCode> {
+    let sleep = find_current_sleep(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let row = query!(
+        "DELETE FROM sleep_interruption WHERE sleep_state_id=? AND ended_at_unix_time IS NULL RETURNING id",
+        sleep.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => {
+            app_state
+                .sleep_events
+                .publish(conn_user.id, SleepChange::Updated(sleep.id.into()));
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(ApiError::NotFound)?,
+    }
+}
@@ -0,0 +1,57 @@
+use api_types::{
+    v1::{SleepInterruption, SubjectQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_current_sleep, find_sleep, InterruptionRow};
+
+pub async fn get_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((sleep_id, id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<Json<SleepInterruption>> {
+    let sleep = find_sleep(&app_state.db, conn_user.id, sleep_id).await?;
+    let row = query_as!(
+        InterruptionRow,
+        "SELECT * FROM sleep_interruption WHERE sleep_state_id=? AND id=?",
+        sleep.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
+
+pub async fn get_current_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<Json<SleepInterruption>> {
+    let sleep = find_current_sleep(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let row = query_as!(
+        InterruptionRow,
+        "SELECT * FROM sleep_interruption WHERE sleep_state_id=? AND ended_at_unix_time IS NULL",
+        sleep.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
@@ -0,0 +1,45 @@
+use api_types::{
+    v1::{SleepInterruption, SubjectQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::{query_as, SqlitePool};
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::{find_current_sleep, find_sleep, InterruptionRow};
+
+async fn list_for_sleep(
+    db: &SqlitePool,
+    sleep_state_id: i64,
+) -> Result<Vec<SleepInterruption>, sqlx::Error> {
+    let rows = query_as!(
+        InterruptionRow,
+        "SELECT * FROM sleep_interruption WHERE sleep_state_id=? ORDER BY started_at_unix_time",
+        sleep_state_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(InterruptionRow::into_api).collect())
+}
+
+pub async fn list_interruptions(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Vec<SleepInterruption>>> {
+    let sleep = find_sleep(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(list_for_sleep(&app_state.db, sleep.id).await?))
+}
+
+pub async fn list_current_interruptions(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<Json<Vec<SleepInterruption>>> {
+    let sleep = find_current_sleep(&app_state.db, conn_user.id, subject.subject_id).await?;
+    Ok(Json(list_for_sleep(&app_state.db, sleep.id).await?))
+}
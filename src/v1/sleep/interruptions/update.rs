@@ -0,0 +1,91 @@
+use api_types::{
+    v1::{SleepInterruption, SubjectQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{sleep::SleepChange, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{find_current_sleep, find_sleep, overlaps_other, validate_times};
+
+pub async fn put_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((sleep_id, id)): Path<(Snowflake, Snowflake)>,
+    Json(new_interruption): Json<SleepInterruption>,
+) -> ResultResponse<StatusCode> {
+    if new_interruption.id != id || new_interruption.sleep_state_id != sleep_id {
+        return Ok(StatusCode::CONFLICT);
+    }
+    let sleep = find_sleep(&app_state.db, conn_user.id, sleep_id).await?;
+    let start = new_interruption.start.timestamp();
+    let end = new_interruption.end.map(|end| end.timestamp());
+    validate_times(&sleep, start, end)?;
+    if overlaps_other(&app_state.db, sleep.id, id, start, end).await? {
+        return Ok(StatusCode::CONFLICT);
+    }
+
+    let row = query!(
+        r#"
+            UPDATE sleep_interruption SET
+                started_at_unix_time=?,
+                ended_at_unix_time=?,
+                reason=?
+            WHERE sleep_state_id=? AND id=?
+            RETURNING id"#,
+        start,
+        end,
+        new_interruption.reason,
+        sleep.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => {
+            app_state
+                .sleep_events
+                .publish(conn_user.id, SleepChange::Updated(sleep_id));
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Ok(StatusCode::NOT_FOUND),
+    }
+}
+
+/// "Back to sleep": end the current interruption of the current sleep now.
+pub async fn end_current_interruption(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<StatusCode> {
+    let sleep = find_current_sleep(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let now = app_state.clock.now().timestamp();
+    let row = query!(
+        r#"UPDATE sleep_interruption
+        SET ended_at_unix_time=?
+        WHERE sleep_state_id=? AND ended_at_unix_time IS NULL
+        RETURNING id"#,
+        now,
+        sleep.id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    match row {
+        Some(_row) => {
+            app_state
+                .sleep_events
+                .publish(conn_user.id, SleepChange::Updated(sleep.id.into()));
+            Ok(StatusCode::OK)
+        }
+        None => Ok(StatusCode::NOT_FOUND),
+    }
+}
@@ -1,25 +1,115 @@
-use api_types::v1::SleepState;
-use axum::{extract::State, Json};
-use sqlx::query;
+use api_types::{
+    v1::{ShareAccess, SleepState, SleepStateListQuery},
+    Snowflake,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use sqlx::{query_as, SqlitePool};
 
-use crate::{datetime_utc_from_timestamp, v1::ResultResponse, AppState, RequireUser};
+use crate::{
+    v1::{
+        settings::SleepContext, sharing::access::resolve_access, subjects::resolve_subject,
+        ResultResponse,
+    },
+    AppState, RequireUser,
+};
+
+use super::row::{load_states, SleepStateRow};
+
+/// Find the sleep states of one of the user's subjects that match the filter, in chronological order.
+pub async fn find_states(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    filter: &SleepStateListQuery,
+) -> ResultResponse<Vec<SleepState>> {
+    let subject_id = resolve_subject(db, user_id, filter.subject_id).await?;
+    let context = SleepContext::load(db, user_id).await?;
+    let local_day = &context.local_day;
+
+    // The database only knows about UTC, so first narrow the rows down with a generous margin,
+    // then filter precisely by the local sleep date.
+    let lower = filter
+        .from_date
+        .map(|date| local_day.lower_bound_utc(date).timestamp())
+        .unwrap_or(i64::MIN);
+    let upper = filter
+        .to_date
+        .map(|date| local_day.upper_bound_utc(date).timestamp())
+        .unwrap_or(i64::MAX);
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND started_at_unix_time>=? AND started_at_unix_time<?
+                AND deleted_at_unix_time IS NULL
+            ORDER BY started_at_unix_time"#,
+        subject_id,
+        lower,
+        upper,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(load_states(db, &context, rows)
+        .await?
+        .into_iter()
+        .filter(|state| matches_filter(state, filter))
+        .collect())
+}
+
+/// Find the sleep states that match the filter, of the user or of the user given in the filter
+/// if they shared theirs, leaving out what the grant does not allow to see.
+pub async fn find_shared_states(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    filter: &SleepStateListQuery,
+) -> ResultResponse<Vec<SleepState>> {
+    let access = resolve_access(db, user_id, filter.user_id, ShareAccess::Read).await?;
+    let mut filter = filter.clone();
+    access.restrict_filter(&mut filter)?;
+    let states = find_states(db, access.owner_id, &filter).await?;
+    Ok(states
+        .into_iter()
+        .map(|state| access.redact(state))
+        .collect())
+}
+
+fn matches_filter(state: &SleepState, filter: &SleepStateListQuery) -> bool {
+    let date = state.sleep_date.expect("sleep date is always computed");
+    let check_in = &state.check_in;
+    // A sleep without a rating never matches a filter on that rating
+    let rating_in_range = |rating: Option<u8>, min: Option<u8>, max: Option<u8>| {
+        (min.is_none() && max.is_none())
+            || rating.is_some_and(|rating| {
+                min.is_none_or(|min| rating >= min) && max.is_none_or(|max| rating <= max)
+            })
+    };
+
+    filter.from_date.is_none_or(|from| date >= from)
+        && filter.to_date.is_none_or(|to| date <= to)
+        && filter.kind.is_none_or(|kind| state.kind == kind)
+        && rating_in_range(check_in.quality, filter.min_quality, filter.max_quality)
+        && rating_in_range(
+            check_in.restedness,
+            filter.min_restedness,
+            filter.max_restedness,
+        )
+        && filter
+            .dream_recall
+            .is_none_or(|recall| check_in.dream_recall == Some(recall))
+        && filter.tag_filter().matches(&state.tags)
+}
 
 pub async fn list_states(
     State(app_state): State<AppState>,
     RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(filter): Query<SleepStateListQuery>,
 ) -> ResultResponse<Json<Vec<SleepState>>> {
-    let rows = query!("SELECT * FROM sleep_state WHERE user_id=?", conn_user.id)
-        .fetch_all(&app_state.db)
-        .await?;
-
     Ok(Json(
-        rows.iter()
-            .map(|row| SleepState {
-                id: row.id.into(),
-                start: datetime_utc_from_timestamp(row.started_at_unix_time),
-                end: row.ended_at_unix_time.map(datetime_utc_from_timestamp),
-                comment: row.comment.clone(),
-            })
-            .collect(),
+        find_shared_states(&app_state.db, conn_user.id, &filter).await?,
     ))
 }
@@ -0,0 +1,216 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use api_types::{
+    v1::{
+        DateTimeUtc, ExportedSleepState, ShareLink, SharedField, SleepExportColumn,
+        SleepInterruption, SleepReport, SleepState, SleepStateListQuery,
+    },
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use sqlx::{query, query_as};
+
+use crate::{
+    v1::{
+        settings::SleepContext,
+        sharing::{access::SleepAccess, links::LinkRow},
+        ApiError, ResultResponse,
+    },
+    AppState,
+};
+
+use super::{
+    export::{csv_line, csv_value},
+    list::find_states,
+    row::load_extras,
+    summary::summarize,
+};
+
+/// The report that a public link leads to, as JSON.
+///
+/// There is no login here: the token in the URL is the only credential, like for the calendar feed.
+/// Every time the report is opened, the access count of the link goes up.
+/// This returns 410 once the link has expired, and 404 once it was revoked.
+pub async fn get_report(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+) -> ResultResponse<Result<Json<SleepReport>, (StatusCode, String)>> {
+    let mut report = match open_report(&app_state, &token).await? {
+        Ok(report) => report,
+        Err(err) => return Ok(Err(err)),
+    };
+    Ok(Ok(Json(SleepReport {
+        from_date: report.link.from_date,
+        to_date: report.link.to_date,
+        generated_at: report.generated_at,
+        expires_at: report.link.expires_at,
+        summary: summarize(&report.states),
+        sleep_states: report
+            .states
+            .into_iter()
+            .map(|state| ExportedSleepState {
+                interruptions: report
+                    .interruptions
+                    .remove(&state.id.into())
+                    .unwrap_or_default(),
+                sleep_state: state,
+            })
+            .collect(),
+    })))
+}
+
+/// The report that a public link leads to, as CSV, like [`get_report`].
+pub async fn get_report_csv(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+) -> ResultResponse<Result<Response, (StatusCode, String)>> {
+    let report = match open_report(&app_state, &token).await? {
+        Ok(report) => report,
+        Err(err) => return Ok(Err(err)),
+    };
+
+    let columns: Vec<SleepExportColumn> = SleepExportColumn::ALL
+        .iter()
+        .copied()
+        .filter(|column| {
+            shared_field(*column).is_none_or(|field| !report.link.hidden_fields.contains(&field))
+        })
+        .collect();
+    let tz = SleepContext::load(&app_state.db, report.owner_id)
+        .await?
+        .local_day
+        .tz;
+    let mut csv = csv_line(columns.iter().map(|column| Cow::from(column.to_string())));
+    for state in &report.states {
+        let interruptions = report
+            .interruptions
+            .get(&state.id.into())
+            .map_or(&[][..], Vec::as_slice);
+        csv.push_str(&csv_line(
+            columns
+                .iter()
+                .map(|column| csv_value(*column, state, interruptions, tz)),
+        ));
+    }
+    Ok(Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"sleep-report.csv\"",
+            ),
+        ],
+        csv,
+    )
+        .into_response()))
+}
+
+/// What a public link shows, at the time it was opened.
+struct Report {
+    link: ShareLink,
+    owner_id: Snowflake,
+    generated_at: DateTimeUtc,
+    /// The sleep states in the range of the link, without the fields that it hides.
+    states: Vec<SleepState>,
+    /// The interruptions of each of the sleep states, by its ID.
+    interruptions: HashMap<i64, Vec<SleepInterruption>>,
+}
+
+/// Find the link with the token, count the access, and load what it shows.
+async fn open_report(
+    app_state: &AppState,
+    token: &str,
+) -> ResultResponse<Result<Report, (StatusCode, String)>> {
+    let now = app_state.clock.now();
+    let now_timestamp = now.timestamp();
+    let row = query_as!(
+        LinkRow,
+        r#"SELECT id, user_id, subject_id, from_date, to_date, hidden_fields, created_at_unix_time,
+                expires_at_unix_time, access_count, last_accessed_at_unix_time
+            FROM share_link WHERE token=?"#,
+        token
+    )
+    .fetch_optional(&app_state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    if row.expires_at_unix_time <= now_timestamp {
+        return Ok(Err((
+            StatusCode::GONE,
+            "this link has expired; ask for a new one".to_string(),
+        )));
+    }
+    query!(
+        r#"UPDATE share_link SET access_count=access_count+1, last_accessed_at_unix_time=?
+            WHERE id=?"#,
+        now_timestamp,
+        row.id,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    let owner_id = row.user_id.into();
+    let link = row.into_api();
+    let access = SleepAccess::limited(
+        owner_id,
+        Some(link.from_date),
+        Some(link.to_date),
+        link.hidden_fields.clone(),
+    );
+    let mut filter = SleepStateListQuery {
+        subject_id: Some(link.subject_id),
+        ..Default::default()
+    };
+    access.restrict_filter(&mut filter)?;
+    let states: Vec<SleepState> = find_states(&app_state.db, owner_id, &filter)
+        .await?
+        .into_iter()
+        .map(|state| access.redact(state))
+        .collect();
+
+    let ids: Vec<i64> = states.iter().map(|state| state.id.into()).collect();
+    let hides_reasons = link.hidden_fields.contains(&SharedField::Comment);
+    let interruptions = load_extras(&app_state.db, &ids)
+        .await?
+        .into_iter()
+        .map(|(id, extras)| {
+            let interruptions = extras
+                .interruptions
+                .into_iter()
+                .map(|row| {
+                    let mut interruption = row.into_api();
+                    // The reason is written like a comment, so it is hidden along with the comments
+                    if hides_reasons {
+                        interruption.reason = None;
+                    }
+                    interruption
+                })
+                .collect();
+            (id, interruptions)
+        })
+        .collect();
+    Ok(Ok(Report {
+        link,
+        owner_id,
+        generated_at: now,
+        states,
+        interruptions,
+    }))
+}
+
+fn shared_field(column: SleepExportColumn) -> Option<SharedField> {
+    match column {
+        SleepExportColumn::Quality
+        | SleepExportColumn::SleepLatencyMinutes
+        | SleepExportColumn::Awakenings
+        | SleepExportColumn::Restedness
+        | SleepExportColumn::DreamRecall => Some(SharedField::CheckIn),
+        SleepExportColumn::Tags => Some(SharedField::Tags),
+        SleepExportColumn::Comment => Some(SharedField::Comment),
+        _ => None,
+    }
+}
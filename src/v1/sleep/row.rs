@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use api_types::{
+    v1::{DateTimeUtc, SleepCheckIn, SleepKind, SleepState},
+    Snowflake,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{datetime_utc_from_timestamp, v1::settings::SleepContext};
+
+use crate::v1::tags::TagRow;
+
+use super::interruptions::InterruptionRow;
+
+/// A row of the `sleep_state` table.
+#[derive(Debug, Clone)]
+pub struct SleepStateRow {
+    pub id: i64,
+    pub started_at_unix_time: i64,
+    pub ended_at_unix_time: Option<i64>,
+    pub comment: Option<String>,
+    pub kind: Option<String>,
+    pub quality: Option<i64>,
+    pub sleep_latency_minutes: Option<i64>,
+    pub awakenings: Option<i64>,
+    pub restedness: Option<i64>,
+    pub dream_recall: Option<String>,
+    pub auto_closed: i64,
+    pub deleted_at_unix_time: Option<i64>,
+    pub change_sequence: i64,
+    // Set for every row, but SQLite could only add the column as nullable
+    pub subject_id: Option<i64>,
+}
+
+/// Data from other tables that belongs to a sleep state.
+#[derive(Debug, Clone, Default)]
+pub struct SleepExtras {
+    pub interruptions: Vec<InterruptionRow>,
+    pub tags: Vec<TagRow>,
+}
+
+impl SleepExtras {
+    /// The periods of being awake during the given sleep, clipped to it, sorted and merged.
+    ///
+    /// An interruption that has not ended lasts until the end of the sleep.
+    pub fn awake_intervals(
+        &self,
+        start: DateTimeUtc,
+        end: DateTimeUtc,
+    ) -> Vec<(DateTimeUtc, DateTimeUtc)> {
+        let mut intervals: Vec<(DateTimeUtc, DateTimeUtc)> = self
+            .interruptions
+            .iter()
+            .map(|interruption| {
+                let awake_start = datetime_utc_from_timestamp(interruption.started_at_unix_time);
+                let awake_end = interruption
+                    .ended_at_unix_time
+                    .map(datetime_utc_from_timestamp)
+                    .unwrap_or(end);
+                (awake_start.max(start), awake_end.min(end))
+            })
+            .filter(|(awake_start, awake_end)| awake_start < awake_end)
+            .collect();
+        intervals.sort();
+
+        let mut merged: Vec<(DateTimeUtc, DateTimeUtc)> = vec![];
+        for (awake_start, awake_end) in intervals {
+            match merged.last_mut() {
+                Some((_, last_end)) if awake_start <= *last_end => {
+                    *last_end = (*last_end).max(awake_end)
+                }
+                _ => merged.push((awake_start, awake_end)),
+            }
+        }
+        merged
+    }
+}
+
+impl SleepStateRow {
+    pub fn explicit_kind(&self) -> Option<SleepKind> {
+        // The database has a CHECK constraint on this column, so parsing cannot fail
+        self.kind.as_deref().and_then(|kind| kind.parse().ok())
+    }
+
+    pub fn check_in(&self) -> SleepCheckIn {
+        // The database has CHECK constraints on these columns, so the conversions cannot fail
+        SleepCheckIn {
+            quality: self.quality.map(|quality| quality as u8),
+            sleep_latency_minutes: self.sleep_latency_minutes.map(|minutes| minutes as u32),
+            awakenings: self.awakenings.map(|awakenings| awakenings as u32),
+            restedness: self.restedness.map(|restedness| restedness as u8),
+            dream_recall: self
+                .dream_recall
+                .as_deref()
+                .and_then(|recall| recall.parse().ok()),
+        }
+    }
+
+    pub fn into_api(self, context: &SleepContext, extras: &SleepExtras) -> SleepState {
+        let start = datetime_utc_from_timestamp(self.started_at_unix_time);
+        let end = self.ended_at_unix_time.map(datetime_utc_from_timestamp);
+        let explicit_kind = self.explicit_kind();
+        let check_in = self.check_in();
+        let kind = explicit_kind.unwrap_or_else(|| {
+            context.kind_rules.infer(
+                context.local_day.local_time(start),
+                end.map(|end| end - start),
+            )
+        });
+        let time_in_bed_seconds = end.map(|end| (end - start).num_seconds());
+        let net_sleep_seconds = end.map(|end| {
+            let awake_seconds: i64 = extras
+                .awake_intervals(start, end)
+                .iter()
+                .map(|(awake_start, awake_end)| (*awake_end - *awake_start).num_seconds())
+                .sum();
+            (end - start).num_seconds() - awake_seconds
+        });
+        SleepState {
+            id: self.id.into(),
+            start,
+            end,
+            comment: self.comment,
+            check_in,
+            tags: extras.tags.iter().cloned().map(TagRow::into_api).collect(),
+            sleep_date: Some(context.local_day.sleep_date(start)),
+            kind,
+            explicit_kind,
+            time_in_bed_seconds,
+            net_sleep_seconds,
+            auto_closed: self.auto_closed != 0,
+            subject_id: self.subject_id.map(Into::into),
+        }
+    }
+}
+
+/// Load the data from other tables that belongs to the given sleep states.
+///
+/// Sleep states that have no such data are missing from the map.
+pub async fn load_extras(
+    db: &SqlitePool,
+    ids: &[i64],
+) -> Result<HashMap<i64, SleepExtras>, sqlx::Error> {
+    // SQLite cannot bind arrays, so pass the IDs as a JSON array instead
+    let ids = serde_json::to_string(ids).expect("a list of integers is always valid JSON");
+    let interruptions = query_as!(
+        InterruptionRow,
+        r#"SELECT * FROM sleep_interruption
+            WHERE sleep_state_id IN (SELECT value FROM json_each(?))
+            ORDER BY started_at_unix_time"#,
+        ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    let tags = query!(
+        r#"SELECT
+            sleep_state_tag.sleep_state_id,
+            tag.id, tag.name, tag.color
+        FROM sleep_state_tag INNER JOIN tag ON tag.id = sleep_state_tag.tag_id
+        WHERE sleep_state_tag.sleep_state_id IN (SELECT value FROM json_each(?))
+        ORDER BY tag.name"#,
+        ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut extras: HashMap<i64, SleepExtras> = HashMap::new();
+    for interruption in interruptions {
+        extras
+            .entry(interruption.sleep_state_id)
+            .or_default()
+            .interruptions
+            .push(interruption);
+    }
+    for tag in tags {
+        extras
+            .entry(tag.sleep_state_id)
+            .or_default()
+            .tags
+            .push(TagRow {
+                id: tag.id,
+                name: tag.name,
+                color: tag.color,
+            });
+    }
+    Ok(extras)
+}
+
+/// Convert rows to the API representation, loading all the data that belongs to them.
+pub async fn load_states(
+    db: &SqlitePool,
+    context: &SleepContext,
+    rows: Vec<SleepStateRow>,
+) -> Result<Vec<SleepState>, sqlx::Error> {
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let extras = load_extras(db, &ids).await?;
+    let no_extras = SleepExtras::default();
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let row_extras = extras.get(&row.id).unwrap_or(&no_extras);
+            row.into_api(context, row_extras)
+        })
+        .collect())
+}
+
+/// Convert a single row to the API representation, loading all the data that belongs to it.
+pub async fn load_state(
+    db: &SqlitePool,
+    context: &SleepContext,
+    row: SleepStateRow,
+) -> Result<SleepState, sqlx::Error> {
+    let mut states = load_states(db, context, vec![row]).await?;
+    Ok(states.remove(0))
+}
+
+/// Load one of the user's sleep states by ID, or nothing if it does not exist or is in the trash.
+pub async fn find_state(
+    db: &SqlitePool,
+    context: &SleepContext,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<Option<SleepState>, sqlx::Error> {
+    let row = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL"#,
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?;
+    match row {
+        Some(row) => load_state(db, context, row).await.map(Some),
+        None => Ok(None),
+    }
+}
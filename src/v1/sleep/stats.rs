@@ -0,0 +1,149 @@
+pub(super) mod compute;
+
+use api_types::v1::{ShareAccess, SleepStats, SleepStatsQuery};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::Duration;
+use sqlx::query_as;
+
+use crate::{
+    v1::{
+        goals::{goal_for_night, load_goals},
+        settings::SleepContext,
+        sharing::access::resolve_access,
+        subjects::resolve_subject,
+        ApiError, ResultResponse,
+    },
+    AppState, RequireUser,
+};
+
+use self::compute::{compute_period, sleep_debt_by_day, split_periods, SleepSample};
+
+use super::row::{load_extras, SleepExtras, SleepStateRow};
+
+/// The longest range that statistics can be requested for, in days.
+pub(super) const MAX_RANGE_DAYS: i64 = 3660;
+
+pub async fn get_stats(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(query): Query<SleepStatsQuery>,
+) -> ResultResponse<Json<SleepStats>> {
+    if query.from > query.to {
+        return Err(ApiError::BadRequest(
+            "`from` must not be after `to`".to_string(),
+        ))?;
+    }
+    if query.to - query.from > Duration::days(MAX_RANGE_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "cannot compute statistics for more than {MAX_RANGE_DAYS} days at once"
+        )))?;
+    }
+
+    let access = resolve_access(
+        &app_state.db,
+        conn_user.id,
+        query.user_id,
+        ShareAccess::Read,
+    )
+    .await?;
+    access.check_tag_filter(&query.tag_filter())?;
+    let subject_id = resolve_subject(&app_state.db, access.owner_id, query.subject_id).await?;
+
+    let context = SleepContext::load(&app_state.db, access.owner_id).await?;
+    let local_day = &context.local_day;
+    // Each night is held to the minimum duration of its goal, if that sets one
+    let goals = load_goals(&app_state.db, access.owner_id).await?;
+    let default_goal_seconds = context.settings.sleep_goal_minutes as i64 * 60;
+    let goal_seconds = |sleep_date| {
+        goal_for_night(&goals, sleep_date)
+            .and_then(|goal| goal.min_duration_minutes)
+            .map_or(default_goal_seconds, |minutes| minutes as i64 * 60)
+    };
+
+    let lower = local_day.lower_bound_utc(query.from).timestamp();
+    let upper = local_day.upper_bound_utc(query.to).timestamp();
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND started_at_unix_time>=? AND started_at_unix_time<?
+                AND ended_at_unix_time IS NOT NULL AND deleted_at_unix_time IS NULL
+            ORDER BY started_at_unix_time"#,
+        subject_id,
+        lower,
+        upper,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let extras = load_extras(&app_state.db, &ids).await?;
+    let no_extras = SleepExtras::default();
+
+    let all_samples: Vec<SleepSample> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let row_extras = extras.get(&row.id).unwrap_or(&no_extras);
+            let state = row.into_api(&context, row_extras);
+            if query.kind.is_some_and(|kind| state.kind != kind)
+                || !query.tag_filter().matches(&state.tags)
+            {
+                return None;
+            }
+            let end = state.end?;
+            let sleep_date = local_day.sleep_date(state.start);
+            if !access.includes(sleep_date) {
+                return None;
+            }
+            Some(SleepSample {
+                sleep_date,
+                start: state.start,
+                end,
+                bedtime: local_day.local_time(state.start).time(),
+                wake_time: local_day.local_time(end).time(),
+                net_sleep_seconds: state.net_sleep_seconds?,
+                awake: row_extras.awake_intervals(state.start, end),
+            })
+        })
+        .collect();
+    let samples: Vec<SleepSample> = all_samples
+        .iter()
+        .filter(|sample| (query.from..=query.to).contains(&sample.sleep_date))
+        .cloned()
+        .collect();
+
+    let debt_by_day = sleep_debt_by_day(&samples, query.from, query.to, goal_seconds);
+    let make_period = |(from, to)| {
+        let period_samples: Vec<SleepSample> = samples
+            .iter()
+            .filter(|sample| (from..=to).contains(&sample.sleep_date))
+            .cloned()
+            .collect();
+        let window = (
+            local_day.day_start_utc(from),
+            local_day.day_start_utc(to + Duration::days(1)),
+        );
+        compute_period(
+            (from, to),
+            &period_samples,
+            &all_samples,
+            window,
+            debt_by_day[&to],
+        )
+    };
+
+    Ok(Json(SleepStats {
+        granularity: query.granularity,
+        goal_seconds: goal_seconds(query.to),
+        overall: make_period((query.from, query.to)),
+        periods: split_periods(query.from, query.to, query.granularity)
+            .into_iter()
+            .map(make_period)
+            .collect(),
+    }))
+}
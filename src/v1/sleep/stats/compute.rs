@@ -0,0 +1,228 @@
+use std::{collections::HashMap, f64::consts::TAU};
+
+use api_types::v1::{DateTimeUtc, SleepStatsPeriod, StatsGranularity};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Timelike};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// A completed sleep, prepared for computing statistics.
+#[derive(Debug, Clone)]
+pub struct SleepSample {
+    pub sleep_date: NaiveDate,
+    pub start: DateTimeUtc,
+    pub end: DateTimeUtc,
+    /// Local time of day when the sleep started
+    pub bedtime: NaiveTime,
+    /// Local time of day when the sleep ended
+    pub wake_time: NaiveTime,
+    /// The time in bed minus the interruptions
+    pub net_sleep_seconds: i64,
+    /// The interruptions, clipped to the sleep, sorted and merged
+    pub awake: Vec<(DateTimeUtc, DateTimeUtc)>,
+}
+
+impl SleepSample {
+    pub fn time_in_bed_seconds(&self) -> i64 {
+        (self.end - self.start).num_seconds().max(0)
+    }
+}
+
+/// Split the range `from..=to` into periods of the given granularity.
+/// The first and last periods are cut to fit the range.
+pub fn split_periods(
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: StatsGranularity,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut periods = vec![];
+    let mut period_start = from;
+    while period_start <= to {
+        let natural_end = match granularity {
+            StatsGranularity::Day => period_start,
+            StatsGranularity::Week => {
+                period_start
+                    + Duration::days(6 - period_start.weekday().num_days_from_monday() as i64)
+            }
+            StatsGranularity::Month => {
+                let (year, month) = if period_start.month() == 12 {
+                    (period_start.year() + 1, 1)
+                } else {
+                    (period_start.year(), period_start.month() + 1)
+                };
+                NaiveDate::from_ymd_opt(year, month, 1).expect("first of month is a valid date")
+                    - Duration::days(1)
+            }
+        };
+        let period_end = natural_end.min(to);
+        periods.push((period_start, period_end));
+        period_start = period_end + Duration::days(1);
+    }
+    periods
+}
+
+/// Compute the sleep debt at the end of every day in `from..=to`.
+///
+/// The debt grows by the shortfall against the goal of each night,
+/// shrinks by any surplus, and never goes below zero.
+pub fn sleep_debt_by_day(
+    samples: &[SleepSample],
+    from: NaiveDate,
+    to: NaiveDate,
+    goal_seconds: impl Fn(NaiveDate) -> i64,
+) -> HashMap<NaiveDate, i64> {
+    let mut slept: HashMap<NaiveDate, i64> = HashMap::new();
+    for sample in samples {
+        *slept.entry(sample.sleep_date).or_default() += sample.net_sleep_seconds;
+    }
+
+    let mut debt_by_day = HashMap::new();
+    let mut debt = 0;
+    let mut day = from;
+    while day <= to {
+        let slept = slept.get(&day).copied().unwrap_or(0);
+        debt = (debt + goal_seconds(day) - slept).max(0);
+        debt_by_day.insert(day, debt);
+        day += Duration::days(1);
+    }
+    debt_by_day
+}
+
+/// Compute the statistics for one period.
+///
+/// `samples` must only contain sleeps whose sleep date is in the period,
+/// while `all_samples` may contain any sleeps: those overlapping `window_start..window_end`
+/// are used for the sleep regularity index.
+pub fn compute_period(
+    (from, to): (NaiveDate, NaiveDate),
+    samples: &[SleepSample],
+    all_samples: &[SleepSample],
+    (window_start, window_end): (DateTimeUtc, DateTimeUtc),
+    sleep_debt_seconds: i64,
+) -> SleepStatsPeriod {
+    let times_in_bed: Vec<f64> = samples
+        .iter()
+        .map(|sample| sample.time_in_bed_seconds() as f64)
+        .collect();
+    let (average_time_in_bed_seconds, _) = mean_and_std_dev(&times_in_bed);
+
+    let durations: Vec<f64> = samples
+        .iter()
+        .map(|sample| sample.net_sleep_seconds as f64)
+        .collect();
+    let (average_duration_seconds, duration_std_dev_seconds) = mean_and_std_dev(&durations);
+
+    let bedtimes: Vec<NaiveTime> = samples.iter().map(|sample| sample.bedtime).collect();
+    let (average_bedtime, bedtime_std_dev_seconds) = circular_mean_and_std_dev(&bedtimes);
+
+    let wake_times: Vec<NaiveTime> = samples.iter().map(|sample| sample.wake_time).collect();
+    let (average_wake_time, wake_time_std_dev_seconds) = circular_mean_and_std_dev(&wake_times);
+
+    SleepStatsPeriod {
+        from,
+        to,
+        sleep_count: samples.len() as u32,
+        total_time_in_bed_seconds: samples.iter().map(SleepSample::time_in_bed_seconds).sum(),
+        average_time_in_bed_seconds,
+        total_duration_seconds: samples.iter().map(|sample| sample.net_sleep_seconds).sum(),
+        average_duration_seconds,
+        duration_std_dev_seconds,
+        average_bedtime,
+        bedtime_std_dev_seconds,
+        average_wake_time,
+        wake_time_std_dev_seconds,
+        sleep_regularity_index: sleep_regularity_index(all_samples, window_start, window_end),
+        sleep_debt_seconds,
+    }
+}
+
+/// The mean, and the sample standard deviation, of the given values.
+pub fn mean_and_std_dev(values: &[f64]) -> (Option<f64>, Option<f64>) {
+    if values.is_empty() {
+        return (None, None);
+    }
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    if values.len() < 2 {
+        return (Some(mean), None);
+    }
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (count - 1.0);
+    (Some(mean), Some(variance.sqrt()))
+}
+
+/// The circular mean and circular standard deviation (in seconds) of times of day.
+///
+/// Each time is treated as an angle on a 24-hour clock, so times on both sides of midnight
+/// average to a time near midnight, rather than to noon.
+/// If the times are spread evenly around the clock, there is no meaningful mean.
+pub fn circular_mean_and_std_dev(times: &[NaiveTime]) -> (Option<NaiveTime>, Option<f64>) {
+    if times.is_empty() {
+        return (None, None);
+    }
+    let count = times.len() as f64;
+    let (sin_sum, cos_sum) = times.iter().fold((0.0, 0.0), |(sin_sum, cos_sum), time| {
+        let angle = time.num_seconds_from_midnight() as f64 / SECONDS_PER_DAY * TAU;
+        (sin_sum + angle.sin(), cos_sum + angle.cos())
+    });
+    let (sin_mean, cos_mean) = (sin_sum / count, cos_sum / count);
+    let resultant_length = sin_mean.hypot(cos_mean);
+    if resultant_length < 1e-9 {
+        return (None, None);
+    }
+
+    let mean_angle = sin_mean.atan2(cos_mean).rem_euclid(TAU);
+    let mean_seconds = ((mean_angle / TAU * SECONDS_PER_DAY).round() as u32) % 86400;
+    let mean = NaiveTime::from_num_seconds_from_midnight_opt(mean_seconds, 0)
+        .expect("seconds are reduced modulo a day");
+
+    let std_dev = if times.len() < 2 {
+        None
+    } else {
+        Some((-2.0 * resultant_length.ln()).max(0.0).sqrt() / TAU * SECONDS_PER_DAY)
+    };
+    (Some(mean), std_dev)
+}
+
+/// Compute the Sleep Regularity Index over the window, with one-minute epochs.
+///
+/// This is the probability of being in the same state at two epochs 24 hours apart,
+/// scaled to the range from -100 (always different) to 100 (always the same).
+fn sleep_regularity_index(
+    samples: &[SleepSample],
+    window_start: DateTimeUtc,
+    window_end: DateTimeUtc,
+) -> Option<f64> {
+    const EPOCH_SECONDS: i64 = 60;
+    const EPOCHS_PER_DAY: usize = 86400 / EPOCH_SECONDS as usize;
+
+    let epoch_count = ((window_end - window_start).num_seconds() / EPOCH_SECONDS).max(0) as usize;
+    if epoch_count <= EPOCHS_PER_DAY {
+        return None;
+    }
+
+    let mut asleep = vec![false; epoch_count];
+    let mut mark = |start: DateTimeUtc, end: DateTimeUtc, state: bool| {
+        let first = (start - window_start).num_seconds() / EPOCH_SECONDS;
+        let last = (end - window_start).num_seconds() / EPOCH_SECONDS;
+        let first = first.clamp(0, epoch_count as i64) as usize;
+        let last = last.clamp(0, epoch_count as i64) as usize;
+        asleep[first..last.max(first)]
+            .iter_mut()
+            .for_each(|epoch| *epoch = state);
+    };
+    for sample in samples {
+        mark(sample.start, sample.end, true);
+        for (awake_start, awake_end) in &sample.awake {
+            mark(*awake_start, *awake_end, false);
+        }
+    }
+
+    let pair_count = epoch_count - EPOCHS_PER_DAY;
+    let matching = (0..pair_count)
+        .filter(|&epoch| asleep[epoch] == asleep[epoch + EPOCHS_PER_DAY])
+        .count();
+    Some(200.0 * matching as f64 / pair_count as f64 - 100.0)
+}
@@ -0,0 +1,99 @@
+use std::convert::Infallible;
+
+use api_types::{
+    v1::{SleepState, SleepStateEvent},
+    Snowflake,
+};
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream};
+use sqlx::SqlitePool;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::{
+    v1::{settings::SleepContext, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::{SleepChange, SleepEvent},
+    row::find_state,
+};
+
+/// Push the changes to the user's sleep states as Server-Sent Events, as they happen.
+///
+/// Every event is named `sleep_state`, and its data is a [`SleepStateEvent`].
+/// The changes made before connecting are not sent, so clients should fetch the sleep states
+/// they need after connecting, and again whenever they get a `Lagged` event.
+pub async fn stream_sleep_events(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let receiver = app_state.sleep_events.subscribe();
+    let subscription = Subscription {
+        db: app_state.db,
+        context,
+        user_id: conn_user.id,
+        receiver,
+    };
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next_event().await?;
+        let event = Event::default()
+            .event("sleep_state")
+            .json_data(&event)
+            .expect("an event can always be serialized");
+        Some((Ok(event), subscription))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// The changes to sleep states that one stream listens to.
+struct Subscription {
+    db: SqlitePool,
+    context: SleepContext,
+    user_id: Snowflake,
+    receiver: Receiver<SleepEvent>,
+}
+
+impl Subscription {
+    /// Wait for the next change to a sleep state of the user, or nothing if the server is shutting down.
+    async fn next_event(&mut self) -> Option<SleepStateEvent> {
+        loop {
+            let change = match self.receiver.recv().await {
+                Ok(event) if event.user_id == self.user_id => event.change,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => return Some(SleepStateEvent::Lagged { missed }),
+                Err(RecvError::Closed) => return None,
+            };
+            let event = match change {
+                SleepChange::Deleted(id) => Some(SleepStateEvent::Deleted { id }),
+                SleepChange::Created(id) => self
+                    .load(id)
+                    .await
+                    .map(|sleep_state| SleepStateEvent::Created { sleep_state }),
+                SleepChange::Updated(id) | SleepChange::Ended(id) => self
+                    .load(id)
+                    .await
+                    .map(|sleep_state| SleepStateEvent::Updated { sleep_state }),
+            };
+            if let Some(event) = event {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Load the sleep state as it is now.
+    /// If it was deleted in the meantime, nothing is sent, since the deletion has an event of its own.
+    async fn load(&self, id: Snowflake) -> Option<SleepState> {
+        match find_state(&self.db, &self.context, self.user_id, id).await {
+            Ok(state) => state,
+            Err(err) => {
+                tracing::error!("Failed to load sleep state {id} for the event stream: {err}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use api_types::v1::{
+    DreamRecall, DreamRecallCounts, SleepState, SleepStateListQuery, SleepStateSummary,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::list::find_shared_states;
+
+/// Aggregate the sleep states that match the same filters as the list endpoint, including `user_id`.
+pub async fn summarize_states(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(filter): Query<SleepStateListQuery>,
+) -> ResultResponse<Json<SleepStateSummary>> {
+    let states = find_shared_states(&app_state.db, conn_user.id, &filter).await?;
+    Ok(Json(summarize(&states)))
+}
+
+/// Averages of the check-ins of the sleep states.
+pub fn summarize(states: &[SleepState]) -> SleepStateSummary {
+    fn average(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+        let (sum, count) = values
+            .flatten()
+            .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+        (count > 0).then(|| sum / count as f64)
+    }
+
+    let mut dream_recall_counts = DreamRecallCounts::default();
+    for state in states {
+        match state.check_in.dream_recall {
+            Some(DreamRecall::None) => dream_recall_counts.none += 1,
+            Some(DreamRecall::Vague) => dream_recall_counts.vague += 1,
+            Some(DreamRecall::Vivid) => dream_recall_counts.vivid += 1,
+            None => {}
+        }
+    }
+
+    SleepStateSummary {
+        count: states.len() as u32,
+        average_net_sleep_seconds: average(
+            states
+                .iter()
+                .map(|state| state.net_sleep_seconds.map(|seconds| seconds as f64)),
+        ),
+        average_quality: average(
+            states
+                .iter()
+                .map(|state| state.check_in.quality.map(f64::from)),
+        ),
+        average_sleep_latency_minutes: average(
+            states
+                .iter()
+                .map(|state| state.check_in.sleep_latency_minutes.map(f64::from)),
+        ),
+        average_awakenings: average(
+            states
+                .iter()
+                .map(|state| state.check_in.awakenings.map(f64::from)),
+        ),
+        average_restedness: average(
+            states
+                .iter()
+                .map(|state| state.check_in.restedness.map(f64::from)),
+        ),
+        dream_recall_counts,
+    }
+}
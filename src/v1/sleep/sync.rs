@@ -0,0 +1,341 @@
+use api_types::{
+    v1::{
+        ClientSleepChange, ClientSleepChangeResult, SleepChangeBatch, SleepChangeBatchResult,
+        SleepChanges, SleepChangesQuery, SleepState, SleepStateChange, SubjectQuery,
+    },
+    Snowflake,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    datetime_utc_from_timestamp,
+    v1::{settings::SleepContext, subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    batch::MAX_BATCH_SIZE,
+    bus::SleepChange,
+    create::insert_sleep_state,
+    delete::move_to_trash,
+    row::{load_states, SleepStateRow},
+    update::{replace_sleep_state, validate_sleep_state, ChangeError},
+};
+
+/// How many changes are returned at once if the client does not say.
+const DEFAULT_LIMIT: u32 = 500;
+
+/// The most changes that are returned at once.
+const MAX_LIMIT: u32 = 1000;
+
+/// The sleep states that changed after the cursor, including the ones that were deleted.
+///
+/// This returns 410 if the cursor is too old to tell what changed since,
+/// because sleep states deleted after it were purged from the trash already,
+/// or if it does not come from this server.
+/// The client should then sync everything again, without a cursor.
+pub async fn get_changes(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(params): Query<SleepChangesQuery>,
+) -> ResultResponse<Result<Json<SleepChanges>, (StatusCode, String)>> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )))?;
+    }
+    let since = i64::try_from(params.since)
+        .map_err(|_| ApiError::BadRequest("the cursor is not valid".to_string()))?;
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, params.subject_id).await?;
+
+    // Read the sequence and the rows at the same point in time
+    let mut tx = app_state.db.begin().await?;
+    let sync = query!(
+        "SELECT last_sequence, purged_sequence FROM sleep_state_sync WHERE user_id=?",
+        conn_user.id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+    let (last_sequence, purged_sequence) = sync
+        .map(|sync| (sync.last_sequence, sync.purged_sequence))
+        .unwrap_or((0, 0));
+    if since > last_sequence || (since != 0 && since < purged_sequence) {
+        return Ok(Err((
+            StatusCode::GONE,
+            "this cursor has expired; sync again without `since`".to_string(),
+        )));
+    }
+
+    // Fetch one more than asked for, to know whether there are more
+    let fetch_limit = limit + 1;
+    let mut rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE user_id=? AND change_sequence>? AND subject_id=?
+            ORDER BY change_sequence
+            LIMIT ?"#,
+        conn_user.id,
+        since,
+        subject_id,
+        fetch_limit,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    // Purged sleep states leave gaps in the sequence, which the cursor can skip
+    let cursor = match rows.last() {
+        Some(row) if has_more => row.change_sequence,
+        _ => last_sequence,
+    };
+    let changes = into_changes(&app_state.db, &context, rows).await?;
+
+    Ok(Ok(Json(SleepChanges {
+        changes,
+        cursor: cursor as u64,
+        has_more,
+    })))
+}
+
+/// Apply changes that a client made while it was offline, in order.
+/// Sleep states are created for the subject given in the query.
+///
+/// Each change is applied on its own: one that conflicts or is not valid does not stop the others.
+pub async fn upload_changes(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+    Json(batch): Json<SleepChangeBatch>,
+) -> ResultResponse<Json<SleepChangeBatchResult>> {
+    if batch.changes.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "cannot upload more than {MAX_BATCH_SIZE} changes at once"
+        )))?;
+    }
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+
+    let mut results = vec![];
+    for change in batch.changes {
+        let outcome = match change {
+            ClientSleepChange::Create { sleep_state } => {
+                create(&app_state, conn_user.id, subject_id, &sleep_state).await?
+            }
+            ClientSleepChange::Update {
+                sleep_state,
+                base_sequence,
+            } => {
+                update(
+                    &app_state,
+                    conn_user.id,
+                    conn_token.id,
+                    &sleep_state,
+                    base_sequence,
+                )
+                .await?
+            }
+            ClientSleepChange::Delete { id, base_sequence } => {
+                delete(&app_state, conn_user.id, id, base_sequence).await?
+            }
+        };
+        let result = match outcome {
+            Outcome::Applied(change) => {
+                app_state.sleep_events.publish(conn_user.id, change);
+                ClientSleepChangeResult::Applied {
+                    change: load_change(&app_state.db, &context, conn_user.id, change.id()).await?,
+                }
+            }
+            Outcome::Conflict(id) => ClientSleepChangeResult::Conflict {
+                current: load_change(&app_state.db, &context, conn_user.id, id).await?,
+            },
+            Outcome::Rejected(reason) => ClientSleepChangeResult::Rejected { reason },
+        };
+        results.push(result);
+    }
+    Ok(Json(SleepChangeBatchResult { results }))
+}
+
+/// What happened to an uploaded change.
+enum Outcome {
+    Applied(SleepChange),
+    Conflict(Snowflake),
+    Rejected(String),
+}
+
+async fn create(
+    app_state: &AppState,
+    user_id: Snowflake,
+    subject_id: Snowflake,
+    sleep_state: &SleepState,
+) -> Result<Outcome, ApiError> {
+    let values = match validate_sleep_state(sleep_state) {
+        Ok(values) => values,
+        Err(err) => return Ok(Outcome::Rejected(err.to_string())),
+    };
+    let id = Snowflake::new().await;
+
+    let mut conn = app_state.db.acquire().await?;
+    if !insert_sleep_state(&mut conn, user_id, subject_id, id, &values).await? {
+        return Ok(Outcome::Rejected(ChangeError::AlreadyGoingOn.message()));
+    }
+    Ok(Outcome::Applied(SleepChange::Created(id)))
+}
+
+async fn update(
+    app_state: &AppState,
+    user_id: Snowflake,
+    token_id: Snowflake,
+    sleep_state: &SleepState,
+    base_sequence: u64,
+) -> Result<Outcome, ApiError> {
+    let id = sleep_state.id;
+    let values = match validate_sleep_state(sleep_state) {
+        Ok(values) => values,
+        Err(err) => return Ok(Outcome::Rejected(err.to_string())),
+    };
+    let base_sequence = base_sequence as i64;
+
+    let mut tx = app_state.db.begin().await?;
+    let Some(current) = query!(
+        "SELECT change_sequence, deleted_at_unix_time FROM sleep_state WHERE user_id=? AND id=?",
+        user_id,
+        id,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    else {
+        return Ok(Outcome::Rejected(format!(
+            "sleep state {id} does not exist"
+        )));
+    };
+    if current.change_sequence != base_sequence {
+        return Ok(Outcome::Conflict(id));
+    }
+    if current.deleted_at_unix_time.is_some() {
+        return Ok(Outcome::Rejected(format!(
+            "sleep state {id} is in the trash, and must be restored before changing it"
+        )));
+    }
+    let changed_at = app_state.clock.now();
+    let replaced = replace_sleep_state(
+        &mut tx,
+        user_id,
+        id,
+        token_id,
+        changed_at,
+        &values,
+        Some(base_sequence),
+    )
+    .await?;
+    match replaced {
+        Ok(()) => {}
+        Err(ChangeError::AlreadyGoingOn) => {
+            return Ok(Outcome::Rejected(ChangeError::AlreadyGoingOn.message()))
+        }
+        // Changed by another request in the meantime, so the revision is dropped along with the update
+        Err(ChangeError::NotFound | ChangeError::Changed) => return Ok(Outcome::Conflict(id)),
+    }
+    tx.commit().await?;
+    Ok(Outcome::Applied(SleepChange::Updated(id)))
+}
+
+async fn delete(
+    app_state: &AppState,
+    user_id: Snowflake,
+    id: Snowflake,
+    base_sequence: u64,
+) -> Result<Outcome, ApiError> {
+    let base_sequence = base_sequence as i64;
+    let Some(current) = query!(
+        "SELECT change_sequence, deleted_at_unix_time FROM sleep_state WHERE user_id=? AND id=?",
+        user_id,
+        id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?
+    else {
+        return Ok(Outcome::Rejected(format!(
+            "sleep state {id} does not exist"
+        )));
+    };
+    if current.change_sequence != base_sequence {
+        return Ok(Outcome::Conflict(id));
+    }
+    if current.deleted_at_unix_time.is_some() {
+        // Deleting it again is what the client wanted anyway
+        return Ok(Outcome::Applied(SleepChange::Deleted(id)));
+    }
+
+    let mut conn = app_state.db.acquire().await?;
+    let now = app_state.clock.now();
+    if !move_to_trash(&mut conn, user_id, id, now, Some(base_sequence)).await? {
+        return Ok(Outcome::Conflict(id));
+    }
+    Ok(Outcome::Applied(SleepChange::Deleted(id)))
+}
+
+/// The latest version of one of the user's sleep states, including the ones in the trash.
+async fn load_change(
+    db: &SqlitePool,
+    context: &SleepContext,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<SleepStateChange, ApiError> {
+    let row = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE user_id=? AND id=?"#,
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    let mut changes = into_changes(db, context, vec![row]).await?;
+    Ok(changes.remove(0))
+}
+
+/// Convert rows to changes, keeping their order.
+/// Sleep states in the trash become tombstones.
+async fn into_changes(
+    db: &SqlitePool,
+    context: &SleepContext,
+    rows: Vec<SleepStateRow>,
+) -> Result<Vec<SleepStateChange>, sqlx::Error> {
+    let entries: Vec<(i64, Option<i64>, i64)> = rows
+        .iter()
+        .map(|row| (row.id, row.deleted_at_unix_time, row.change_sequence))
+        .collect();
+    let live_rows = rows
+        .into_iter()
+        .filter(|row| row.deleted_at_unix_time.is_none())
+        .collect();
+    let mut live_states = load_states(db, context, live_rows).await?.into_iter();
+
+    Ok(entries
+        .into_iter()
+        .map(|(id, deleted_at, sequence)| SleepStateChange {
+            id: id.into(),
+            sequence: sequence as u64,
+            sleep_state: match deleted_at {
+                Some(_) => None,
+                None => live_states.next(),
+            },
+            deleted_at: deleted_at.map(datetime_utc_from_timestamp),
+        })
+        .collect())
+}
@@ -0,0 +1,110 @@
+use api_types::{v1::Tag, Snowflake};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as};
+
+use crate::{
+    v1::{tags::TagRow, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::bus::SleepChange;
+
+/// Make sure that both the sleep state and the tag exist and belong to the user.
+async fn check_ownership(
+    app_state: &AppState,
+    user_id: Snowflake,
+    sleep_id: Snowflake,
+    tag_id: Snowflake,
+) -> Result<(), ApiError> {
+    let row = query!(
+        r#"SELECT sleep_state.id FROM sleep_state, tag
+        WHERE sleep_state.user_id=? AND sleep_state.id=? AND sleep_state.deleted_at_unix_time IS NULL
+            AND tag.user_id=? AND tag.id=?"#,
+        user_id,
+        sleep_id,
+        user_id,
+        tag_id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    row.map(|_| ()).ok_or(ApiError::NotFound)
+}
+
+pub async fn list_sleep_tags(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(sleep_id): Path<Snowflake>,
+) -> ResultResponse<Json<Vec<Tag>>> {
+    let sleep = query!(
+        "SELECT id FROM sleep_state WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL",
+        conn_user.id,
+        sleep_id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    if sleep.is_none() {
+        return Err(ApiError::NotFound)?;
+    }
+
+    let rows = query_as!(
+        TagRow,
+        r#"SELECT tag.id, tag.name, tag.color
+        FROM tag INNER JOIN sleep_state_tag ON sleep_state_tag.tag_id = tag.id
+        WHERE sleep_state_tag.sleep_state_id=?
+        ORDER BY tag.name"#,
+        sleep_id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(rows.into_iter().map(TagRow::into_api).collect()))
+}
+
+pub async fn attach_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((sleep_id, tag_id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<StatusCode> {
+    check_ownership(&app_state, conn_user.id, sleep_id, tag_id).await?;
+    let result = query!(
+        "INSERT OR IGNORE INTO sleep_state_tag (sleep_state_id, tag_id) VALUES (?,?)",
+        sleep_id,
+        tag_id
+    )
+    .execute(&app_state.db)
+    .await?;
+    // Attaching a tag that is already attached changes nothing
+    if result.rows_affected() > 0 {
+        app_state
+            .sleep_events
+            .publish(conn_user.id, SleepChange::Updated(sleep_id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn detach_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((sleep_id, tag_id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<StatusCode> {
+    check_ownership(&app_state, conn_user.id, sleep_id, tag_id).await?;
+    let row = query!(
+        "DELETE FROM sleep_state_tag WHERE sleep_state_id=? AND tag_id=? RETURNING tag_id",
+        sleep_id,
+        tag_id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    match row {
+        Some(_row) => {
+            app_state
+                .sleep_events
+                .publish(conn_user.id, SleepChange::Updated(sleep_id));
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(ApiError::NotFound)?,
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use api_types::{
+    v1::{SleepState, SubjectQuery, TrashedSleepState},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Duration;
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    clock::Clock,
+    datetime_utc_from_timestamp,
+    v1::{settings::SleepContext, subjects::resolve_subject, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::SleepChange,
+    row::{load_state, load_states, SleepStateRow},
+};
+
+/// How long deleted sleep states stay in the trash before they are purged, in days.
+const RETENTION_DAYS: i64 = 30;
+
+/// How often to purge the trash.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// The subject's sleep states in the trash, most recently deleted first.
+pub async fn list_trash(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+) -> ResultResponse<Json<Vec<TrashedSleepState>>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let rows = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state
+            WHERE subject_id=? AND deleted_at_unix_time IS NOT NULL
+            ORDER BY deleted_at_unix_time DESC"#,
+        subject_id,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    let deleted_at: Vec<i64> = rows
+        .iter()
+        .filter_map(|row| row.deleted_at_unix_time)
+        .collect();
+    let states = load_states(&app_state.db, &context, rows).await?;
+    Ok(Json(
+        states
+            .into_iter()
+            .zip(deleted_at)
+            .map(|(sleep_state, deleted_at)| trashed(sleep_state, deleted_at))
+            .collect(),
+    ))
+}
+
+/// Take a sleep state out of the trash.
+///
+/// This returns 409 if it is not completed, and another sleep state of its subject is going on by now.
+pub async fn restore_by_id(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Result<Json<SleepState>, StatusCode>> {
+    let context = SleepContext::load(&app_state.db, conn_user.id).await?;
+    let row = query!(
+        r#"UPDATE sleep_state SET deleted_at_unix_time=NULL
+            WHERE user_id=? AND id=? AND deleted_at_unix_time IS NOT NULL
+                AND (ended_at_unix_time IS NOT NULL OR NOT EXISTS (
+                    SELECT 1 FROM sleep_state AS other
+                    WHERE other.subject_id=sleep_state.subject_id
+                        AND other.ended_at_unix_time IS NULL AND other.deleted_at_unix_time IS NULL
+                ))
+            RETURNING sleep_state.id"#,
+        conn_user.id,
+        id,
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+    if row.is_none() {
+        // Tell apart a sleep state that is not in the trash from one that cannot be restored
+        let trashed = query!(
+            "SELECT id FROM sleep_state WHERE user_id=? AND id=? AND deleted_at_unix_time IS NOT NULL",
+            conn_user.id,
+            id
+        )
+        .fetch_optional(&app_state.db)
+        .await?;
+        return match trashed {
+            Some(_row) => Ok(Err(StatusCode::CONFLICT)),
+            None => Err(ApiError::NotFound)?,
+        };
+    }
+    // For the other devices, it is as if the sleep state was created again
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Created(id));
+
+    let row = query_as!(
+        SleepStateRow,
+        r#"SELECT id, started_at_unix_time, ended_at_unix_time, comment, kind, quality,
+            sleep_latency_minutes, awakenings, restedness, dream_recall, auto_closed,
+            deleted_at_unix_time, change_sequence, subject_id
+            FROM sleep_state WHERE user_id=? AND id=?"#,
+        conn_user.id,
+        id
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+    Ok(Ok(Json(load_state(&app_state.db, &context, row).await?)))
+}
+
+fn trashed(sleep_state: SleepState, deleted_at: i64) -> TrashedSleepState {
+    let deleted_at = datetime_utc_from_timestamp(deleted_at);
+    TrashedSleepState {
+        sleep_state,
+        deleted_at,
+        purge_at: deleted_at + Duration::days(RETENTION_DAYS),
+    }
+}
+
+/// Deletes sleep states for good once they have been in the trash for long enough.
+pub struct TrashPurger {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+}
+
+impl TrashPurger {
+    pub fn new(db: SqlitePool, clock: Arc<dyn Clock>) -> Self {
+        Self { db, clock }
+    }
+
+    /// Purge every hour, in the background.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.purge().await {
+                    tracing::error!("Failed to purge the trash: {err}");
+                }
+            }
+        });
+    }
+
+    /// Delete the sleep states whose retention period is over at the current time of the clock.
+    pub async fn purge(&self) -> Result<(), sqlx::Error> {
+        let cutoff = (self.clock.now() - Duration::days(RETENTION_DAYS)).timestamp();
+        // The interruptions and tags of the sleep states are removed by the foreign key cascade
+        let result = query!(
+            "DELETE FROM sleep_state WHERE deleted_at_unix_time<=?",
+            cutoff
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() > 0 {
+            tracing::info!(
+                "Purged {} sleep states from the trash",
+                result.rows_affected()
+            );
+        }
+        Ok(())
+    }
+}
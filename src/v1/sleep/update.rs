@@ -1,96 +1,290 @@
-use std::time::SystemTime;
-
 use api_types::{
-    v1::{DateTimeUtc, SleepState},
+    v1::{DateTimeUtc, ShareAccess, SharedUserQuery, SleepCheckIn, SleepState, SubjectQuery},
     Snowflake,
 };
 use axum::{
-    extract::{Path, State},
+    body::Bytes,
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use sqlx::query;
+use sqlx::{query, SqliteConnection};
+
+use crate::{
+    v1::{
+        body::parse_optional_json, sharing::access::resolve_access, subjects::resolve_subject,
+        ApiError, ResultResponse,
+    },
+    AppState, RequireUser,
+};
+
+use super::{
+    bus::SleepChange,
+    check_in::{validate_check_in, CheckInColumns},
+    history::{find_current_id, record_revision},
+};
+
+/// The values of a sleep state sent by a client, checked and converted for storing.
+pub struct SleepStateColumns {
+    pub start: i64,
+    pub end: Option<i64>,
+    pub comment: Option<String>,
+    pub kind: Option<String>,
+    pub check_in: CheckInColumns,
+}
+
+/// Check that the values of a sleep state make sense, and convert them for storing.
+///
+/// The values computed by the server are ignored.
+pub fn validate_sleep_state(state: &SleepState) -> Result<SleepStateColumns, ApiError> {
+    if state.end.is_some_and(|end| end < state.start) {
+        return Err(ApiError::BadRequest(
+            "a sleep cannot end before it starts".to_string(),
+        ));
+    }
+    Ok(SleepStateColumns {
+        start: state.start.timestamp(),
+        end: state.end.map(|end| end.timestamp()),
+        comment: state.comment.clone(),
+        kind: state.explicit_kind.map(|kind| kind.to_string()),
+        check_in: validate_check_in(&state.check_in)?,
+    })
+}
+
+/// Why a change to a sleep state could not be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeError {
+    /// The sleep state does not exist, or it is in the trash.
+    NotFound,
+    /// The sleep state would be going on, but another sleep of its subject already is.
+    AlreadyGoingOn,
+    /// The sleep state changed since the sequence that the client based its change on.
+    Changed,
+}
+
+impl ChangeError {
+    pub fn message(self) -> String {
+        match self {
+            Self::NotFound => ApiError::NotFound.to_string(),
+            Self::AlreadyGoingOn => "a sleep is already going on".to_string(),
+            Self::Changed => "the sleep state was changed in the meantime".to_string(),
+        }
+    }
 
-use crate::{v1::ResultResponse, AppState, RequireUser};
+    /// The status code and message that the endpoints return for the error.
+    pub fn into_response(self) -> (StatusCode, String) {
+        let code = match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::AlreadyGoingOn | Self::Changed => StatusCode::CONFLICT,
+        };
+        (code, self.message())
+    }
+}
 
+/// Whether a sleep state that is not ended would overlap another sleep of its subject that is going on.
+pub async fn other_sleep_going_on(
+    conn: &mut SqliteConnection,
+    id: Snowflake,
+) -> Result<bool, sqlx::Error> {
+    let other_open = query!(
+        r#"SELECT id FROM sleep_state
+            WHERE subject_id=(SELECT subject_id FROM sleep_state WHERE id=?) AND id!=?
+                AND ended_at_unix_time IS NULL AND deleted_at_unix_time IS NULL"#,
+        id,
+        id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(other_open.is_some())
+}
+
+/// Replace the values of a sleep state, confirming any times that were guessed,
+/// and record the previous values in the history.
+///
+/// If `base_sequence` is given, the sleep state is only changed if it has not changed since.
+/// This must run in a transaction that is rolled back if it fails,
+/// since the revision is recorded before the values are replaced.
+pub async fn replace_sleep_state(
+    conn: &mut SqliteConnection,
+    user_id: Snowflake,
+    id: Snowflake,
+    token_id: Snowflake,
+    changed_at: DateTimeUtc,
+    values: &SleepStateColumns,
+    base_sequence: Option<i64>,
+) -> Result<Result<(), ChangeError>, sqlx::Error> {
+    if values.end.is_none() && other_sleep_going_on(&mut *conn, id).await? {
+        return Ok(Err(ChangeError::AlreadyGoingOn));
+    }
+    if !record_revision(&mut *conn, user_id, id, Some(token_id), changed_at).await? {
+        return Ok(Err(ChangeError::NotFound));
+    }
+    let result = query!(
+        r#"
+            UPDATE sleep_state SET
+                started_at_unix_time=?,
+                ended_at_unix_time=?,
+                comment=?,
+                kind=?,
+                quality=?,
+                sleep_latency_minutes=?,
+                awakenings=?,
+                restedness=?,
+                dream_recall=?,
+                auto_closed=0
+            WHERE user_id=? AND id=? AND deleted_at_unix_time IS NULL
+                AND (?12 IS NULL OR change_sequence=?12)"#,
+        values.start,
+        values.end,
+        values.comment,
+        values.kind,
+        values.check_in.quality,
+        values.check_in.sleep_latency_minutes,
+        values.check_in.awakenings,
+        values.check_in.restedness,
+        values.check_in.dream_recall,
+        user_id,
+        id,
+        base_sequence,
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        // The revision showed that it exists, so it was changed by another request in the meantime
+        return Ok(Err(ChangeError::Changed));
+    }
+    Ok(Ok(()))
+}
+
+/// Replace the values of a sleep state of the user,
+/// or of the user given as `user_id` if they gave the user a read-write grant.
 pub async fn put_by_id(
     State(app_state): State<AppState>,
-    RequireUser((conn_user, _conn_token)): RequireUser,
+    RequireUser((conn_user, conn_token)): RequireUser,
     Path(id): Path<Snowflake>,
+    Query(shared): Query<SharedUserQuery>,
     Json(new_state): Json<SleepState>,
-) -> ResultResponse<StatusCode> {
+) -> ResultResponse<Result<StatusCode, (StatusCode, String)>> {
     if new_state.id != id {
-        return Ok(StatusCode::CONFLICT);
+        return Ok(Err((
+            StatusCode::CONFLICT,
+            "the ID in the body does not match the URL".to_string(),
+        )));
     }
-    let start = new_state.start.timestamp();
-    let end = new_state.end.map(|i| i.timestamp());
-    let row = query!(
-        r#"
-            UPDATE sleep_state SET
-                started_at_unix_time=?,
-                ended_at_unix_time=?,
-                comment=?
-            WHERE user_id=? AND id=?
-            RETURNING *"#,
-        start,
-        end,
-        new_state.comment,
+    let values = validate_sleep_state(&new_state)?;
+    let access = resolve_access(
+        &app_state.db,
         conn_user.id,
-        id
+        shared.user_id,
+        ShareAccess::ReadWrite,
     )
-    .fetch_optional(&app_state.db)
     .await?;
+    let owner_id = access.owner_id;
 
-    match row {
-        None => Ok(StatusCode::NOT_FOUND),
-        Some(row) => {
-            if row.id == Some(id.into()) {
-                Ok(StatusCode::NO_CONTENT)
-            } else {
-                Ok(StatusCode::NOT_FOUND)
-            }
-        }
+    let mut tx = app_state.db.begin().await?;
+    let changed_at = app_state.clock.now();
+    let replaced = replace_sleep_state(
+        &mut tx,
+        owner_id,
+        id,
+        conn_token.id,
+        changed_at,
+        &values,
+        None,
+    )
+    .await?;
+    if let Err(err) = replaced {
+        return Ok(Err(err.into_response()));
     }
+    tx.commit().await?;
+    app_state
+        .sleep_events
+        .publish(owner_id, SleepChange::Updated(id));
+    Ok(Ok(StatusCode::NO_CONTENT))
 }
 
+/// End the current sleep now.
+///
+/// The body may optionally contain a [`SleepCheckIn`] with the user's morning assessment of the sleep.
+/// Any fields of the check-in that are not given are left as they were.
 pub async fn set_current_end(
     State(app_state): State<AppState>,
-    RequireUser((conn_user, _conn_token)): RequireUser,
+    RequireUser((conn_user, conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
+    body: Bytes,
 ) -> ResultResponse<StatusCode> {
-    let now = DateTimeUtc::from(SystemTime::now()).timestamp();
-    let row = query!(
+    let check_in: SleepCheckIn = parse_optional_json(&body)?;
+    let check_in = validate_check_in(&check_in)?;
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+
+    let mut tx = app_state.db.begin().await?;
+    let Some(id) = find_current_id(&mut tx, subject_id).await? else {
+        return Ok(StatusCode::NOT_FOUND);
+    };
+    let now = app_state.clock.now();
+    record_revision(&mut tx, conn_user.id, id, Some(conn_token.id), now).await?;
+
+    let now = now.timestamp();
+    query!(
         r#"UPDATE sleep_state
+        SET ended_at_unix_time=?,
+            quality=COALESCE(?, quality),
+            sleep_latency_minutes=COALESCE(?, sleep_latency_minutes),
+            awakenings=COALESCE(?, awakenings),
+            restedness=COALESCE(?, restedness),
+            dream_recall=COALESCE(?, dream_recall)
+        WHERE id=?"#,
+        now,
+        check_in.quality,
+        check_in.sleep_latency_minutes,
+        check_in.awakenings,
+        check_in.restedness,
+        check_in.dream_recall,
+        id,
+    )
+    .execute(&mut tx)
+    .await?;
+    // Waking up for good also ends any interruption that was going on
+    query!(
+        r#"UPDATE sleep_interruption
         SET ended_at_unix_time=?
-        WHERE user_id=? AND ended_at_unix_time IS NULL
-        RETURNING sleep_state.id"#,
+        WHERE sleep_state_id=? AND ended_at_unix_time IS NULL"#,
         now,
-        conn_user.id,
+        id,
     )
-    .fetch_optional(&app_state.db)
+    .execute(&mut tx)
     .await?;
-    match row {
-        Some(_row) => Ok(StatusCode::OK),
-        None => Ok(StatusCode::NOT_FOUND),
-    }
+    tx.commit().await?;
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Ended(id));
+    Ok(StatusCode::OK)
 }
 
 pub async fn set_current_start(
     State(app_state): State<AppState>,
-    RequireUser((conn_user, _conn_token)): RequireUser,
+    RequireUser((conn_user, conn_token)): RequireUser,
+    Query(subject): Query<SubjectQuery>,
 ) -> ResultResponse<StatusCode> {
-    let now = DateTimeUtc::from(SystemTime::now()).timestamp();
-    let row = query!(
-        r#"UPDATE sleep_state
-        SET started_at_unix_time=?
-        WHERE user_id=? AND ended_at_unix_time IS NULL
-        RETURNING sleep_state.id"#,
+    let subject_id = resolve_subject(&app_state.db, conn_user.id, subject.subject_id).await?;
+    let mut tx = app_state.db.begin().await?;
+    let Some(id) = find_current_id(&mut tx, subject_id).await? else {
+        return Ok(StatusCode::NOT_FOUND);
+    };
+    let now = app_state.clock.now();
+    record_revision(&mut tx, conn_user.id, id, Some(conn_token.id), now).await?;
+
+    let now = now.timestamp();
+    query!(
+        "UPDATE sleep_state SET started_at_unix_time=? WHERE id=?",
         now,
-        conn_user.id,
+        id,
     )
-    .fetch_optional(&app_state.db)
+    .execute(&mut tx)
     .await?;
-    match row {
-        Some(_row) => Ok(StatusCode::NO_CONTENT),
-        None => Ok(StatusCode::NOT_FOUND),
-    }
+    tx.commit().await?;
+    app_state
+        .sleep_events
+        .publish(conn_user.id, SleepChange::Updated(id));
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,100 @@
+mod create;
+mod delete;
+mod get;
+mod list;
+mod update;
+
+use api_types::{v1::Subject, Snowflake};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use sqlx::{query, SqlitePool};
+
+use crate::{datetime_utc_from_timestamp, v1::ApiError, AppState};
+
+use self::{
+    create::create_subject, delete::delete_subject, get::get_subject, list::list_subjects,
+    update::put_subject,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_subjects))
+        .route("/new", post(create_subject))
+        .route(
+            "/:id",
+            get(get_subject).put(put_subject).delete(delete_subject),
+        )
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Subject API\n",
+        "A subject is someone whose sleep you track: you, or someone in your care, like a baby or a pet\n",
+        "The sleep endpoints use your own subject, which has your user ID, unless you pass ?subject_id=<id>\n",
+        "GET /list -- list of all your subjects, starting with yourself\n",
+        "POST /new -- create a subject, or 409 if you already have one with that name\n",
+        "GET /<id> -- get subject by ID\n",
+        "PUT /<id> -- rename subject by ID, or 409 if you already have one with that name\n",
+        "DELETE /<id> -- delete subject by ID along with the sleep states of it in the trash, or 409 if it has other sleep states (your own subject cannot be deleted)\n",
+    )
+}
+
+/// A row of the `subject` table.
+#[derive(Debug, Clone)]
+pub struct SubjectRow {
+    pub id: i64,
+    pub name: String,
+    pub is_self: i64,
+    pub created_at_unix_time: i64,
+}
+
+impl SubjectRow {
+    pub fn into_api(self) -> Subject {
+        Subject {
+            id: self.id.into(),
+            name: self.name,
+            is_self: self.is_self != 0,
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+        }
+    }
+}
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Check that the name of a subject makes sense.
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "subject name cannot be empty".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "subject name cannot be longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Find the subject that a request is about: the given one, making sure that it belongs to the user,
+/// or the user themselves if none is given.
+pub async fn resolve_subject(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    subject_id: Option<Snowflake>,
+) -> Result<Snowflake, ApiError> {
+    let row = query!(
+        r#"SELECT id FROM subject
+            WHERE user_id=? AND (id=? OR (? IS NULL AND is_self))"#,
+        user_id,
+        subject_id,
+        subject_id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    Ok(row.id.into())
+}
@@ -0,0 +1,49 @@
+use api_types::{
+    v1::{NewSubject, Subject},
+    Snowflake,
+};
+use axum::{extract::State, http::StatusCode, Json};
+use sqlx::query;
+
+use crate::{
+    v1::{is_unique_violation, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::validate_name;
+
+pub async fn create_subject(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_subject): Json<NewSubject>,
+) -> ResultResponse<Result<(StatusCode, Json<Subject>), StatusCode>> {
+    validate_name(&new_subject.name)?;
+
+    let id = Snowflake::new().await;
+    let created = app_state.clock.now();
+    let created_timestamp = created.timestamp();
+    let result = query!(
+        "INSERT INTO subject (id, user_id, name, is_self, created_at_unix_time) VALUES (?,?,?,0,?)",
+        id,
+        conn_user.id,
+        new_subject.name,
+        created_timestamp,
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Ok(Err(StatusCode::CONFLICT)),
+        Err(err) => return Err(err)?,
+    }
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(Subject {
+            id,
+            name: new_subject.name,
+            is_self: false,
+            created_at: created,
+        }),
+    )))
+}
@@ -0,0 +1,89 @@
+use api_types::Snowflake;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{sleep::SleepChange, ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+/// Delete a subject, and the sleep states of it that are in the trash.
+///
+/// Sleep states that are not in the trash have to be deleted first, so that they are not lost by accident.
+/// The sync cursors of the user stay valid for their other subjects.
+pub async fn delete_subject(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Result<StatusCode, (StatusCode, String)>> {
+    let mut tx = app_state.db.begin().await?;
+    let subject = query!(
+        "SELECT is_self FROM subject WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    if subject.is_self != 0 {
+        return Err(ApiError::BadRequest(
+            "the subject that stands for yourself cannot be deleted".to_string(),
+        ))?;
+    }
+
+    let kept = query!(
+        "SELECT COUNT(*) AS count FROM sleep_state WHERE subject_id=? AND deleted_at_unix_time IS NULL",
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    if kept.count > 0 {
+        return Ok(Err((
+            StatusCode::CONFLICT,
+            format!(
+                "the subject still has {} sleep states, which have to be deleted first",
+                kept.count
+            ),
+        )));
+    }
+
+    // Purging sleep states makes the sync cursors from before them expire, since their tombstones are gone.
+    // Here, that would only matter for syncing this subject, which cannot be done anymore,
+    // so the cursors for the user's other subjects are kept working
+    let sync = query!(
+        "SELECT purged_sequence FROM sleep_state_sync WHERE user_id=?",
+        conn_user.id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+    // The interruptions and tags of the sleep states are removed by the foreign key cascade
+    let purged = query!(
+        r#"DELETE FROM sleep_state WHERE subject_id=? RETURNING id AS "id!""#,
+        id
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    if let Some(sync) = sync {
+        query!(
+            "UPDATE sleep_state_sync SET purged_sequence=? WHERE user_id=?",
+            sync.purged_sequence,
+            conn_user.id
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+    query!("DELETE FROM subject WHERE id=?", id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    // They were in the trash, but open event streams still hear that they are gone for good
+    for row in purged {
+        app_state
+            .sleep_events
+            .publish(conn_user.id, SleepChange::Deleted(row.id.into()));
+    }
+    Ok(Ok(StatusCode::NO_CONTENT))
+}
@@ -0,0 +1,33 @@
+use api_types::{v1::Subject, Snowflake};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::SubjectRow;
+
+pub async fn get_subject(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Subject>> {
+    let row = query_as!(
+        SubjectRow,
+        "SELECT id, name, is_self, created_at_unix_time FROM subject WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
@@ -0,0 +1,21 @@
+use api_types::v1::Subject;
+use axum::{extract::State, Json};
+use sqlx::query_as;
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::SubjectRow;
+
+pub async fn list_subjects(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<Subject>>> {
+    let rows = query_as!(
+        SubjectRow,
+        "SELECT id, name, is_self, created_at_unix_time FROM subject WHERE user_id=? ORDER BY is_self DESC, name",
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(rows.into_iter().map(SubjectRow::into_api).collect()))
+}
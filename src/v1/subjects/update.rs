@@ -0,0 +1,39 @@
+use api_types::{v1::NewSubject, Snowflake};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{is_unique_violation, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::validate_name;
+
+pub async fn put_subject(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+    Json(new_subject): Json<NewSubject>,
+) -> ResultResponse<StatusCode> {
+    validate_name(&new_subject.name)?;
+
+    let row = query!(
+        "UPDATE subject SET name=? WHERE user_id=? AND id=? RETURNING id",
+        new_subject.name,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await;
+
+    match row {
+        Ok(Some(_row)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Ok(StatusCode::NOT_FOUND),
+        Err(err) if is_unique_violation(&err) => Ok(StatusCode::CONFLICT),
+        Err(err) => Err(err)?,
+    }
+}
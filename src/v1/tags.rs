@@ -0,0 +1,77 @@
+mod create;
+mod delete;
+mod get;
+mod list;
+mod update;
+
+use api_types::v1::Tag;
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::{v1::ApiError, AppState};
+
+use self::{
+    create::create_tag, delete::delete_tag, get::get_tag, list::list_tags, update::put_tag,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_tags))
+        .route("/new", post(create_tag))
+        .route("/:id", get(get_tag).put(put_tag).delete(delete_tag))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Tag API\n",
+        "GET /list -- list of all tags you have defined\n",
+        "POST /new -- create a tag, or 409 if you already have a tag with that name\n",
+        "GET /<id> -- get tag by ID\n",
+        "PUT /<id> -- change tag by ID (ID in body must match the entry's data)\n",
+        "DELETE /<id> -- delete tag by ID (removing it from all sleep states), or 404\n",
+    )
+}
+
+/// A row of the `tag` table.
+#[derive(Debug, Clone)]
+pub struct TagRow {
+    pub id: i64,
+    pub name: String,
+    pub color: String,
+}
+
+impl TagRow {
+    pub fn into_api(self) -> Tag {
+        Tag {
+            id: self.id.into(),
+            name: self.name,
+            color: self.color,
+        }
+    }
+}
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Check that the name and color of a tag make sense.
+fn validate_tag(name: &str, color: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::BadRequest("tag name cannot be empty".to_string()));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "tag name cannot be longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+    let is_hex_color = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex_color {
+        return Err(ApiError::BadRequest(format!(
+            "tag color must be written as #RRGGBB, not {color:?}"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use api_types::{
+    v1::{NewTag, Tag},
+    Snowflake,
+};
+use axum::{extract::State, http::StatusCode, Json};
+use sqlx::query;
+
+use crate::{
+    v1::{is_unique_violation, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::validate_tag;
+
+pub async fn create_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(new_tag): Json<NewTag>,
+) -> ResultResponse<Result<(StatusCode, Json<Tag>), StatusCode>> {
+    validate_tag(&new_tag.name, &new_tag.color)?;
+
+    let id = Snowflake::new().await;
+    let result = query!(
+        "INSERT INTO tag (id, user_id, name, color) VALUES (?,?,?,?)",
+        id,
+        conn_user.id,
+        new_tag.name,
+        new_tag.color,
+    )
+    .execute(&app_state.db)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Ok(Err(StatusCode::CONFLICT)),
+        Err(err) => return Err(err)?,
+    }
+
+    Ok(Ok((
+        StatusCode::CREATED,
+        Json(Tag {
+            id,
+            name: new_tag.name,
+            color: new_tag.color,
+        }),
+    )))
+}
@@ -0,0 +1,31 @@
+use api_types::Snowflake;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+pub async fn delete_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    // The tag is removed from the sleep states by the foreign key cascade
+    let row = query!(
+        "DELETE FROM tag WHERE user_id=? AND id=? RETURNING id",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(_row) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound)?,
+    }
+}
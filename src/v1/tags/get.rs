@@ -0,0 +1,33 @@
+use api_types::{v1::Tag, Snowflake};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::query_as;
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::TagRow;
+
+pub async fn get_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Tag>> {
+    let row = query_as!(
+        TagRow,
+        "SELECT id, name, color FROM tag WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Json(row.into_api())),
+        None => Err(ApiError::NotFound)?,
+    }
+}
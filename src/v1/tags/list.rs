@@ -0,0 +1,22 @@
+use api_types::v1::Tag;
+use axum::{extract::State, Json};
+use sqlx::query_as;
+
+use crate::{v1::ResultResponse, AppState, RequireUser};
+
+use super::TagRow;
+
+pub async fn list_tags(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<Tag>>> {
+    let rows = query_as!(
+        TagRow,
+        "SELECT id, name, color FROM tag WHERE user_id=? ORDER BY name",
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(TagRow::into_api).collect()))
+}
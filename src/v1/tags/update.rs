@@ -0,0 +1,43 @@
+use api_types::{v1::Tag, Snowflake};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::query;
+
+use crate::{
+    v1::{is_unique_violation, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::validate_tag;
+
+pub async fn put_tag(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+    Json(new_tag): Json<Tag>,
+) -> ResultResponse<StatusCode> {
+    if new_tag.id != id {
+        return Ok(StatusCode::CONFLICT);
+    }
+    validate_tag(&new_tag.name, &new_tag.color)?;
+
+    let row = query!(
+        "UPDATE tag SET name=?, color=? WHERE user_id=? AND id=? RETURNING id",
+        new_tag.name,
+        new_tag.color,
+        conn_user.id,
+        id
+    )
+    .fetch_optional(&app_state.db)
+    .await;
+
+    match row {
+        Ok(Some(_row)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Ok(StatusCode::NOT_FOUND),
+        Err(err) if is_unique_violation(&err) => Ok(StatusCode::CONFLICT),
+        Err(err) => Err(err)?,
+    }
+}
@@ -0,0 +1,158 @@
+mod deliveries;
+mod subscriptions;
+mod worker;
+
+pub use worker::WebhookWorker;
+
+use api_types::v1::{
+    WebhookDelivery, WebhookDeliveryStatus, WebhookEventType, WebhookSubscription,
+    WebhookSubscriptionSettings,
+};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::{datetime_utc_from_timestamp, outbound::OutboundPolicy, v1::ApiError, AppState};
+
+use self::{
+    deliveries::{list_deliveries, redeliver},
+    subscriptions::{
+        create_subscription, delete_subscription, get_subscription, list_subscriptions,
+        put_subscription,
+    },
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/list", get(list_subscriptions))
+        .route("/new", post(create_subscription))
+        .route(
+            "/:id",
+            get(get_subscription)
+                .put(put_subscription)
+                .delete(delete_subscription),
+        )
+        .route("/:id/deliveries", get(list_deliveries))
+        .route("/:id/deliveries/:delivery_id/redeliver", post(redeliver))
+}
+
+async fn root() -> &'static str {
+    concat!(
+        "Webhook API\n",
+        "Payloads are POSTed as JSON, with an X-Oyasumi-Signature header like t=<unix time>,v1=<signature>, where the signature is the hexadecimal HMAC-SHA256 of <unix time>.<body> with the secret of the subscription\n",
+        "Failed deliveries are retried with exponential backoff, up to 8 attempts over about an hour\n",
+        "URLs must be HTTPS URLs of public servers, unless the server runs with ALLOW_LOCAL_WEBHOOKS=true for trying webhooks out against a local stand-in\n",
+        "GET /list -- your webhook subscriptions\n",
+        "POST /new -- subscribe an HTTPS URL to some of the events sleep_started, sleep_ended, sleep_updated and sleep_deleted; the response has the secret that payloads are signed with, which is never shown again\n",
+        "GET /<id> -- get webhook subscription by ID\n",
+        "PUT /<id> -- change the URL, events or active flag of a webhook subscription\n",
+        "DELETE /<id> -- delete webhook subscription by ID, along with its delivery log, or 404\n",
+        "GET /<id>/deliveries -- the last 100 deliveries of a webhook subscription, newest first\n",
+        "POST /<id>/deliveries/<delivery id>/redeliver -- send a delivery again within a few seconds, with the same payload\n",
+    )
+}
+
+/// A row of the `webhook_subscription` table, without the secret,
+/// which is never shown again after the subscription is created.
+#[derive(Debug, Clone)]
+pub struct SubscriptionRow {
+    pub id: i64,
+    pub url: String,
+    pub event_types: String,
+    pub active: i64,
+    pub created_at_unix_time: i64,
+}
+
+impl SubscriptionRow {
+    /// The subscription without its secret, which is only shown when it is created.
+    pub fn into_api(self) -> WebhookSubscription {
+        WebhookSubscription {
+            id: self.id.into(),
+            settings: WebhookSubscriptionSettings {
+                url: self.url,
+                event_types: parse_event_types(&self.event_types),
+                active: self.active != 0,
+            },
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            secret: None,
+        }
+    }
+}
+
+/// A row of the `webhook_delivery` table, as returned by `SELECT *`.
+#[derive(Debug, Clone)]
+pub struct DeliveryRow {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i64,
+    pub created_at_unix_time: i64,
+    pub next_attempt_at_unix_time: Option<i64>,
+    pub last_attempt_at_unix_time: Option<i64>,
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+    pub delivered_at_unix_time: Option<i64>,
+}
+
+impl DeliveryRow {
+    pub fn into_api(self) -> WebhookDelivery {
+        WebhookDelivery {
+            id: self.id.into(),
+            subscription_id: self.subscription_id.into(),
+            // The database has CHECK constraints on these columns, so parsing cannot fail
+            event_type: self
+                .event_type
+                .parse()
+                .unwrap_or(WebhookEventType::SleepUpdated),
+            status: self.status.parse().unwrap_or(WebhookDeliveryStatus::Failed),
+            attempts: self.attempts as u32,
+            created_at: datetime_utc_from_timestamp(self.created_at_unix_time),
+            next_attempt_at: self
+                .next_attempt_at_unix_time
+                .map(datetime_utc_from_timestamp),
+            last_attempt_at: self
+                .last_attempt_at_unix_time
+                .map(datetime_utc_from_timestamp),
+            last_status_code: self.last_status_code.map(|code| code as u16),
+            last_error: self.last_error,
+            delivered_at: self.delivered_at_unix_time.map(datetime_utc_from_timestamp),
+            payload: serde_json::from_str(&self.payload)
+                .expect("payloads are always stored as valid JSON"),
+        }
+    }
+}
+
+/// The event types as stored in the database, like `sleep_started,sleep_ended`.
+fn format_event_types(event_types: &[WebhookEventType]) -> String {
+    let names: Vec<String> = event_types.iter().map(ToString::to_string).collect();
+    names.join(",")
+}
+
+fn parse_event_types(event_types: &str) -> Vec<WebhookEventType> {
+    event_types
+        .split(',')
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// Check that the settings of a subscription can actually be used.
+///
+/// The URL must be an HTTPS URL of a public server, unless the policy allows local ones for trying things out.
+fn validate_settings(
+    settings: &WebhookSubscriptionSettings,
+    outbound: &OutboundPolicy,
+) -> Result<(), ApiError> {
+    outbound
+        .check_url(&settings.url)
+        .map_err(|err| ApiError::BadRequest(format!("invalid webhook URL: {err}")))?;
+    if settings.event_types.is_empty() {
+        return Err(ApiError::BadRequest(
+            "a webhook subscription needs at least one event type".to_string(),
+        ));
+    }
+    Ok(())
+}
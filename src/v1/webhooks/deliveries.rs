@@ -0,0 +1,86 @@
+use api_types::{
+    v1::{DateTimeUtc, WebhookDelivery},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{subscriptions::find_subscription, DeliveryRow};
+
+/// How many deliveries the log shows.
+const LOG_LENGTH: i64 = 100;
+
+/// The last deliveries of a subscription, newest first.
+pub async fn list_deliveries(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<Vec<WebhookDelivery>>> {
+    let subscription = find_subscription(&app_state.db, conn_user.id, id).await?;
+    let rows = query_as!(
+        DeliveryRow,
+        r#"SELECT * FROM webhook_delivery WHERE subscription_id=?
+            ORDER BY created_at_unix_time DESC, id DESC
+            LIMIT ?"#,
+        subscription.id,
+        LOG_LENGTH,
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(rows.into_iter().map(DeliveryRow::into_api).collect()))
+}
+
+/// Put a delivery back in the queue to be sent within a few seconds, with the same payload.
+///
+/// This works for deliveries that succeeded too, for receivers that lost what they got.
+/// The attempts start again from zero, so a delivery that fails again is retried as usual.
+pub async fn redeliver(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path((id, delivery_id)): Path<(Snowflake, Snowflake)>,
+) -> ResultResponse<(StatusCode, Json<WebhookDelivery>)> {
+    let subscription = find_subscription(&app_state.db, conn_user.id, id).await?;
+    let now = app_state.clock.now();
+    if !requeue_delivery(&app_state.db, subscription.id.into(), delivery_id, now).await? {
+        return Err(ApiError::NotFound)?;
+    }
+    let row = query_as!(
+        DeliveryRow,
+        "SELECT * FROM webhook_delivery WHERE id=?",
+        delivery_id
+    )
+    .fetch_one(&app_state.db)
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(row.into_api())))
+}
+
+/// Make a delivery of the subscription due at `now`, with its attempts starting again from zero.
+/// Returns whether the delivery exists.
+pub(super) async fn requeue_delivery(
+    db: &SqlitePool,
+    subscription_id: Snowflake,
+    delivery_id: Snowflake,
+    now: DateTimeUtc,
+) -> Result<bool, sqlx::Error> {
+    let now = now.timestamp();
+    let result = query!(
+        r#"UPDATE webhook_delivery
+            SET status='pending', attempts=0, next_attempt_at_unix_time=?, delivered_at_unix_time=NULL
+            WHERE subscription_id=? AND id=?"#,
+        now,
+        subscription_id,
+        delivery_id,
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
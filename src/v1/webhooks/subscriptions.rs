@@ -0,0 +1,148 @@
+use api_types::{
+    v1::{WebhookSubscription, WebhookSubscriptionSettings},
+    Snowflake,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use crypto::token::generate_token;
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    v1::{ApiError, ResultResponse},
+    AppState, RequireUser,
+};
+
+use super::{format_event_types, validate_settings, SubscriptionRow};
+
+const SECRET_LENGTH: u16 = 32;
+
+/// Find a webhook subscription by ID, making sure that it belongs to the user.
+pub(super) async fn find_subscription(
+    db: &SqlitePool,
+    user_id: Snowflake,
+    id: Snowflake,
+) -> Result<SubscriptionRow, ApiError> {
+    query_as!(
+        SubscriptionRow,
+        "SELECT id, url, event_types, active, created_at_unix_time FROM webhook_subscription WHERE user_id=? AND id=?",
+        user_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+pub async fn list_subscriptions(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+) -> ResultResponse<Json<Vec<WebhookSubscription>>> {
+    let rows = query_as!(
+        SubscriptionRow,
+        "SELECT id, url, event_types, active, created_at_unix_time FROM webhook_subscription WHERE user_id=? ORDER BY created_at_unix_time",
+        conn_user.id
+    )
+    .fetch_all(&app_state.db)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(SubscriptionRow::into_api).collect(),
+    ))
+}
+
+/// Subscribe a URL to events.
+/// The secret that payloads are signed with is only returned here.
+pub async fn create_subscription(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Json(settings): Json<WebhookSubscriptionSettings>,
+) -> ResultResponse<(StatusCode, Json<WebhookSubscription>)> {
+    validate_settings(&settings, &app_state.outbound)?;
+
+    let id = Snowflake::new().await;
+    let secret = generate_token(SECRET_LENGTH);
+    let event_types = format_event_types(&settings.event_types);
+    let created = app_state.clock.now();
+    let created_timestamp = created.timestamp();
+    query!(
+        r#"INSERT INTO webhook_subscription
+            (id, user_id, url, secret, event_types, active, created_at_unix_time)
+            VALUES (?,?,?,?,?,?,?)"#,
+        id,
+        conn_user.id,
+        settings.url,
+        secret,
+        event_types,
+        settings.active,
+        created_timestamp,
+    )
+    .execute(&app_state.db)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookSubscription {
+            id,
+            settings,
+            created_at: created,
+            secret: Some(secret),
+        }),
+    ))
+}
+
+pub async fn get_subscription(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<Json<WebhookSubscription>> {
+    let row = find_subscription(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+/// Change a subscription. The secret stays the same.
+pub async fn put_subscription(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+    Json(settings): Json<WebhookSubscriptionSettings>,
+) -> ResultResponse<Json<WebhookSubscription>> {
+    validate_settings(&settings, &app_state.outbound)?;
+
+    let event_types = format_event_types(&settings.event_types);
+    let result = query!(
+        "UPDATE webhook_subscription SET url=?, event_types=?, active=? WHERE user_id=? AND id=?",
+        settings.url,
+        event_types,
+        settings.active,
+        conn_user.id,
+        id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    let row = find_subscription(&app_state.db, conn_user.id, id).await?;
+    Ok(Json(row.into_api()))
+}
+
+pub async fn delete_subscription(
+    State(app_state): State<AppState>,
+    RequireUser((conn_user, _conn_token)): RequireUser,
+    Path(id): Path<Snowflake>,
+) -> ResultResponse<StatusCode> {
+    // The deliveries are removed by the foreign key cascade
+    let result = query!(
+        "DELETE FROM webhook_subscription WHERE user_id=? AND id=?",
+        conn_user.id,
+        id
+    )
+    .execute(&app_state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound)?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
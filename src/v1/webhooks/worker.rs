@@ -0,0 +1,596 @@
+use std::sync::Arc;
+
+use api_types::{
+    v1::{WebhookEventType, WebhookPayload},
+    Snowflake,
+};
+use chrono::Duration;
+use futures_util::{stream, StreamExt};
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use sqlx::{query, query_as, SqlitePool};
+
+use crate::{
+    clock::Clock,
+    outbound::OutboundPolicy,
+    v1::{
+        settings::{load_user_settings, SleepContext},
+        sleep::{row::find_state, SleepEventBus},
+    },
+};
+
+use super::{parse_event_types, DeliveryRow};
+
+/// How often to look for changes and deliveries that are due, if no change to a sleep state says to look sooner.
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How many times a payload is sent before giving up on it.
+const MAX_ATTEMPTS: i64 = 8;
+
+/// How long to wait before the first retry, in seconds. The wait doubles after every attempt.
+const FIRST_RETRY_SECONDS: i64 = 30;
+
+/// How many changes are queued and deliveries are sent in one tick, so that a backlog is worked through a bit at a time.
+const BATCH_SIZE: i64 = 50;
+
+/// How many deliveries are sent at the same time, so that slow receivers do not hold up the others.
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// How long a receiver has to answer.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Turns changes to sleep states into webhook deliveries, and sends them.
+///
+/// The changes are recorded in `webhook_event` by triggers in the same transaction that makes them,
+/// and deliveries are stored before they are sent, so that neither is lost when the server stops.
+/// Deliveries are retried with exponential backoff until they succeed or run out of attempts.
+pub struct WebhookWorker {
+    db: SqlitePool,
+    clock: Arc<dyn Clock>,
+    sleep_events: SleepEventBus,
+    outbound: OutboundPolicy,
+    client: reqwest::Client,
+}
+
+/// A row of the `webhook_event` table.
+struct EventRow {
+    id: i64,
+    user_id: i64,
+    sleep_state_id: i64,
+    event_type: String,
+}
+
+impl WebhookWorker {
+    pub fn new(
+        db: SqlitePool,
+        clock: Arc<dyn Clock>,
+        sleep_events: SleepEventBus,
+        outbound: OutboundPolicy,
+    ) -> Self {
+        Self {
+            db,
+            clock,
+            sleep_events,
+            outbound,
+            client: outbound.client(TIMEOUT),
+        }
+    }
+
+    /// Queue deliveries for changes to sleep states and send the due ones, in the background.
+    ///
+    /// This happens every few seconds, and right after sleep states change,
+    /// so that receivers hear about them without waiting for the next tick.
+    pub fn spawn(self) {
+        let mut receiver = self.sleep_events.subscribe();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    // Missed changes do not matter, since the triggers recorded them
+                    _ = receiver.recv() => {}
+                }
+                if let Err(err) = self.queue_events().await {
+                    tracing::error!("Failed to queue webhook deliveries: {err}");
+                }
+                if let Err(err) = self.tick().await {
+                    tracing::error!("Failed to send webhook deliveries: {err}");
+                }
+            }
+        });
+    }
+
+    /// Queue deliveries for the changes to sleep states that were recorded, oldest first.
+    pub async fn queue_events(&self) -> Result<(), sqlx::Error> {
+        let events = query_as!(
+            EventRow,
+            "SELECT * FROM webhook_event ORDER BY id LIMIT ?",
+            BATCH_SIZE
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for event in events {
+            match self.queue(&event).await {
+                Ok(()) => {}
+                // The database may work again on the next tick
+                Err(err) if err.is::<sqlx::Error>() => {
+                    tracing::error!(
+                        "Failed to queue webhook deliveries for event {}: {err}",
+                        event.id
+                    );
+                }
+                Err(err) => {
+                    tracing::error!("Dropping webhook event {}: {err}", event.id);
+                    query!("DELETE FROM webhook_event WHERE id=?", event.id)
+                        .execute(&self.db)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue a delivery of the change for every active subscription of the user that wants it,
+    /// and remove the change from the ones still to queue.
+    async fn queue(&self, event: &EventRow) -> anyhow::Result<()> {
+        let event_type: WebhookEventType = event.event_type.parse()?;
+        let mut subscriptions: Vec<_> = query!(
+            "SELECT id, event_types FROM webhook_subscription WHERE user_id=? AND active",
+            event.user_id
+        )
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .filter(|subscription| parse_event_types(&subscription.event_types).contains(&event_type))
+        .collect();
+
+        let id = Snowflake::from(event.sleep_state_id);
+        let mut sleep_state = None;
+        if !subscriptions.is_empty() && event_type != WebhookEventType::SleepDeleted {
+            let user_id = event.user_id.into();
+            let settings = load_user_settings(&self.db, user_id).await?;
+            let context = SleepContext::from_settings(settings)?;
+            sleep_state = find_state(&self.db, &context, user_id, id).await?;
+            if sleep_state.is_none() {
+                // Deleted in the meantime, which has an event of its own
+                subscriptions.clear();
+            }
+        }
+
+        let now = self.clock.now();
+        let now_timestamp = now.timestamp();
+        let mut tx = self.db.begin().await?;
+        for subscription in subscriptions {
+            let delivery_id = Snowflake::new().await;
+            let payload = WebhookPayload {
+                id: delivery_id,
+                event_type,
+                occurred_at: now,
+                sleep_state_id: id,
+                sleep_state: sleep_state.clone(),
+            };
+            let payload = serde_json::to_string(&payload)?;
+            query!(
+                r#"INSERT INTO webhook_delivery
+                    (id, subscription_id, event_type, payload, status, created_at_unix_time, next_attempt_at_unix_time)
+                    VALUES (?,?,?,?,'pending',?,?)"#,
+                delivery_id,
+                subscription.id,
+                event.event_type,
+                payload,
+                now_timestamp,
+                now_timestamp,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        query!("DELETE FROM webhook_event WHERE id=?", event.id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Send the deliveries that are due at the current time of the clock, oldest first.
+    /// Deliveries of subscriptions that are not active wait until they are active again.
+    pub async fn tick(&self) -> Result<(), sqlx::Error> {
+        let now = self.clock.now().timestamp();
+        let due = query!(
+            r#"SELECT webhook_delivery.id, webhook_subscription.url, webhook_subscription.secret
+                FROM webhook_delivery
+                    JOIN webhook_subscription ON webhook_subscription.id=webhook_delivery.subscription_id
+                WHERE webhook_delivery.status='pending' AND webhook_delivery.next_attempt_at_unix_time<=?
+                    AND webhook_subscription.active
+                ORDER BY webhook_delivery.next_attempt_at_unix_time
+                LIMIT ?"#,
+            now,
+            BATCH_SIZE,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        // A delivery that cannot be recorded stays due, and is sent again on a later tick
+        stream::iter(due)
+            .for_each_concurrent(MAX_CONCURRENT_DELIVERIES, |delivery| async move {
+                let id = delivery.id.into();
+                if let Err(err) = self.attempt(id, &delivery.url, &delivery.secret).await {
+                    tracing::error!("Failed to record webhook delivery {id}: {err}");
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Send a delivery once, and record how it went.
+    async fn attempt(&self, id: Snowflake, url: &str, secret: &str) -> Result<(), sqlx::Error> {
+        let Some(delivery) = query_as!(
+            DeliveryRow,
+            "SELECT * FROM webhook_delivery WHERE id=? AND status='pending'",
+            id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        else {
+            return Ok(());
+        };
+
+        let now = self.clock.now();
+        let timestamp = now.timestamp();
+        let signature = crypto::signature::sign(
+            secret.as_bytes(),
+            format!("{timestamp}.{}", delivery.payload).as_bytes(),
+        );
+        // The URL was checked when it was stored, but what is allowed may have changed since
+        let response = match self.outbound.check_url(url) {
+            Ok(url) => self
+                .client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(USER_AGENT, "Oyasumi-Webhooks/1")
+                .header("X-Oyasumi-Event", &delivery.event_type)
+                .header("X-Oyasumi-Delivery", id.to_string())
+                .header(
+                    "X-Oyasumi-Signature",
+                    format!("t={timestamp},v1={signature}"),
+                )
+                .body(delivery.payload)
+                .send()
+                .await
+                .map_err(|err| (err.status(), err.to_string())),
+            Err(err) => Err((None, err)),
+        };
+        let (status_code, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16() as i64), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16() as i64),
+                Some(format!("the URL answered with {}", response.status())),
+            ),
+            Err((status, err)) => (status.map(|status| status.as_u16() as i64), Some(err)),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let (status, next_attempt, delivered) = match &error {
+            None => ("delivered", None, Some(timestamp)),
+            Some(_) if attempts >= MAX_ATTEMPTS => ("failed", None, None),
+            Some(_) => {
+                let wait = Duration::seconds(FIRST_RETRY_SECONDS << (attempts - 1));
+                ("pending", Some((now + wait).timestamp()), None)
+            }
+        };
+        if let Some(error) = &error {
+            tracing::info!("Webhook delivery {id} failed on attempt {attempts}: {error}");
+        }
+        query!(
+            r#"UPDATE webhook_delivery
+                SET status=?, attempts=?, next_attempt_at_unix_time=?, last_attempt_at_unix_time=?,
+                    last_status_code=?, last_error=?, delivered_at_unix_time=?
+                WHERE id=?"#,
+            status,
+            attempts,
+            next_attempt,
+            timestamp,
+            status_code,
+            error,
+            delivered,
+            id,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU16, Ordering},
+        Mutex,
+    };
+
+    use api_types::v1::DateTimeUtc;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+
+    use crate::{
+        clock::SimulatedClock,
+        testing::{insert_user, test_db},
+        v1::webhooks::deliveries::requeue_delivery,
+    };
+
+    use super::*;
+
+    const USER_ID: i64 = 1;
+    const SUBSCRIPTION_ID: i64 = 10;
+    const SECRET: &str = "the secret";
+
+    /// A stand-in for a receiver, which keeps what it gets and answers with a status that can be changed.
+    #[derive(Clone)]
+    struct StandIn {
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+        status: Arc<AtomicU16>,
+    }
+
+    impl StandIn {
+        /// Start a stand-in on a free port, and return it with its URL.
+        async fn start(status: StatusCode) -> (Self, String) {
+            let stand_in = Self {
+                requests: Arc::default(),
+                status: Arc::new(AtomicU16::new(status.as_u16())),
+            };
+            let app = Router::new()
+                .route("/hook", post(receive))
+                .with_state(stand_in.clone());
+            let server =
+                axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+            let url = format!("http://{}/hook", server.local_addr());
+            tokio::spawn(server);
+            (stand_in, url)
+        }
+
+        fn answer_with(&self, status: StatusCode) {
+            self.status.store(status.as_u16(), Ordering::SeqCst);
+        }
+
+        fn requests(&self) -> Vec<(HeaderMap, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        State(stand_in): State<StandIn>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        stand_in.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(stand_in.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    fn start_time() -> DateTimeUtc {
+        "2023-10-06T22:00:00Z".parse().unwrap()
+    }
+
+    async fn set_up(db: &SqlitePool, url: &str) {
+        insert_user(db, USER_ID).await;
+        query!(
+            r#"INSERT INTO webhook_subscription (id, user_id, url, secret, event_types, created_at_unix_time)
+                VALUES (?, ?, ?, ?, 'sleep_started,sleep_ended,sleep_updated,sleep_deleted', 0)"#,
+            SUBSCRIPTION_ID,
+            USER_ID,
+            url,
+            SECRET,
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    fn worker(db: &SqlitePool, clock: &SimulatedClock, outbound: OutboundPolicy) -> WebhookWorker {
+        WebhookWorker::new(
+            db.clone(),
+            Arc::new(clock.clone()),
+            SleepEventBus::new(),
+            outbound,
+        )
+    }
+
+    const LOCAL: OutboundPolicy = OutboundPolicy { allow_local: true };
+
+    async fn start_sleep(db: &SqlitePool, user_id: i64) {
+        let started_at = start_time().timestamp();
+        query!(
+            r#"INSERT INTO sleep_state (id, user_id, subject_id, started_at_unix_time)
+                VALUES (1, ?, ?, ?)"#,
+            user_id,
+            user_id,
+            started_at,
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    async fn deliveries(db: &SqlitePool) -> Vec<DeliveryRow> {
+        query_as!(DeliveryRow, "SELECT * FROM webhook_delivery ORDER BY id")
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    async fn event_count(db: &SqlitePool) -> i32 {
+        query!("SELECT COUNT(*) AS count FROM webhook_event")
+            .fetch_one(db)
+            .await
+            .unwrap()
+            .count
+    }
+
+    #[tokio::test]
+    async fn sends_signed_payloads() {
+        let db = test_db().await;
+        let (stand_in, url) = StandIn::start(StatusCode::OK).await;
+        set_up(&db, &url).await;
+        let clock = SimulatedClock::stopped_at(start_time());
+        let worker = worker(&db, &clock, LOCAL);
+
+        start_sleep(&db, USER_ID).await;
+        worker.queue_events().await.unwrap();
+        worker.tick().await.unwrap();
+
+        let requests = stand_in.requests();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        assert_eq!(headers["X-Oyasumi-Event"], "sleep_started");
+        let timestamp = start_time().timestamp();
+        let signature =
+            crypto::signature::sign(SECRET.as_bytes(), format!("{timestamp}.{body}").as_bytes());
+        assert_eq!(
+            headers["X-Oyasumi-Signature"],
+            format!("t={timestamp},v1={signature}").as_str()
+        );
+        let payload: WebhookPayload = serde_json::from_str(body).unwrap();
+        assert_eq!(payload.sleep_state_id, Snowflake::from(1));
+        assert_eq!(payload.sleep_state.unwrap().start, start_time());
+
+        let deliveries = deliveries(&db).await;
+        assert_eq!(deliveries[0].status, "delivered");
+        assert_eq!(deliveries[0].delivered_at_unix_time, Some(timestamp));
+    }
+
+    #[tokio::test]
+    async fn queues_the_changes_that_were_recorded() {
+        let db = test_db().await;
+        let (_stand_in, url) = StandIn::start(StatusCode::OK).await;
+        set_up(&db, &url).await;
+        insert_user(&db, 2).await;
+        let clock = SimulatedClock::stopped_at(start_time());
+
+        // Nothing is running while the sleep starts and ends
+        start_sleep(&db, USER_ID).await;
+        let ended_at = (start_time() + Duration::hours(8)).timestamp();
+        query!(
+            "UPDATE sleep_state SET ended_at_unix_time=? WHERE id=1",
+            ended_at
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        // Users without subscriptions get no events
+        query!(
+            r#"INSERT INTO sleep_state (id, user_id, subject_id, started_at_unix_time)
+                VALUES (2, 2, 2, 0)"#
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(event_count(&db).await, 2);
+
+        worker(&db, &clock, LOCAL).queue_events().await.unwrap();
+        let event_types: Vec<_> = deliveries(&db)
+            .await
+            .into_iter()
+            .map(|delivery| (delivery.event_type, delivery.status))
+            .collect();
+        assert_eq!(
+            event_types,
+            vec![
+                ("sleep_started".to_string(), "pending".to_string()),
+                ("sleep_ended".to_string(), "pending".to_string()),
+            ]
+        );
+        assert_eq!(event_count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn retries_with_backoff_until_it_gives_up() {
+        let db = test_db().await;
+        let (stand_in, url) = StandIn::start(StatusCode::INTERNAL_SERVER_ERROR).await;
+        set_up(&db, &url).await;
+        let clock = SimulatedClock::stopped_at(start_time());
+        let worker = worker(&db, &clock, LOCAL);
+        start_sleep(&db, USER_ID).await;
+        worker.queue_events().await.unwrap();
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            worker.tick().await.unwrap();
+            assert_eq!(stand_in.requests().len() as i64, attempt);
+            let delivery = deliveries(&db).await.remove(0);
+            assert_eq!(delivery.attempts, attempt);
+            assert_eq!(delivery.last_status_code, Some(500));
+            if attempt == MAX_ATTEMPTS {
+                assert_eq!(delivery.status, "failed");
+                assert_eq!(delivery.next_attempt_at_unix_time, None);
+                break;
+            }
+            let wait = Duration::seconds(FIRST_RETRY_SECONDS << (attempt - 1));
+            assert_eq!(delivery.status, "pending");
+            assert_eq!(
+                delivery.next_attempt_at_unix_time,
+                Some((clock.now() + wait).timestamp())
+            );
+
+            // Not sent again before the wait is over
+            clock.advance(wait - Duration::seconds(1));
+            worker.tick().await.unwrap();
+            assert_eq!(stand_in.requests().len() as i64, attempt);
+            clock.advance(Duration::seconds(1));
+        }
+
+        clock.advance(Duration::days(1));
+        worker.tick().await.unwrap();
+        assert_eq!(stand_in.requests().len() as i64, MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn redelivers_the_same_payload() {
+        let db = test_db().await;
+        let (stand_in, url) = StandIn::start(StatusCode::OK).await;
+        set_up(&db, &url).await;
+        let clock = SimulatedClock::stopped_at(start_time());
+        let worker = worker(&db, &clock, LOCAL);
+        start_sleep(&db, USER_ID).await;
+        worker.queue_events().await.unwrap();
+        worker.tick().await.unwrap();
+
+        let delivery = deliveries(&db).await.remove(0);
+        clock.advance(Duration::hours(1));
+        let found = requeue_delivery(&db, SUBSCRIPTION_ID.into(), delivery.id.into(), clock.now())
+            .await
+            .unwrap();
+        assert!(found);
+        stand_in.answer_with(StatusCode::NO_CONTENT);
+        worker.tick().await.unwrap();
+
+        let requests = stand_in.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].1, requests[1].1);
+        let delivery = deliveries(&db).await.remove(0);
+        assert_eq!(delivery.status, "delivered");
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.last_status_code, Some(204));
+        assert_eq!(
+            delivery.delivered_at_unix_time,
+            Some(clock.now().timestamp())
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_send_to_local_addresses_unless_allowed() {
+        let db = test_db().await;
+        let (stand_in, url) = StandIn::start(StatusCode::OK).await;
+        set_up(&db, &url).await;
+        let clock = SimulatedClock::stopped_at(start_time());
+        let worker = worker(&db, &clock, OutboundPolicy::default());
+        start_sleep(&db, USER_ID).await;
+        worker.queue_events().await.unwrap();
+        worker.tick().await.unwrap();
+
+        assert!(stand_in.requests().is_empty());
+        let delivery = deliveries(&db).await.remove(0);
+        assert_eq!(delivery.status, "pending");
+        assert!(delivery.last_error.is_some());
+    }
+}